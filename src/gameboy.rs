@@ -2,18 +2,29 @@ use std::{fmt::Error, time::SystemTime};
 
 pub mod apu;
 pub mod cartridge;
+pub mod cheats;
 pub mod cpu;
+pub mod debug_windows;
+pub mod debugger;
+pub mod hooks;
 pub mod interrupts;
 pub mod joypad;
 pub mod memory;
 pub mod ppu;
+pub mod savestate;
+pub mod script;
 pub mod timer;
 
 use apu::APU;
+use cheats::Cheat;
 use cpu::CPU;
+use debug_windows::{EventViewer, HeatmapViewer, IoViewer, MapViewer, MemoryViewer, OamViewer, WatchViewer};
+use debugger::{Access, CrashReason, DebugEvent, Debugger};
+use hooks::Hooks;
 use memory::Memory;
 use minifb::Key;
 use ppu::PPU;
+use script::ScriptHost;
 
 use crate::screen::Screen;
 
@@ -47,7 +58,15 @@ trait GameboyModule {
 }
 
 impl Gameboy {
+    #[inline(always)]
     fn read8(&self, addr: u16) -> u8 {
+        let value = self.read8_bus(addr);
+        self.hooks.on_read(addr, value);
+        self.heatmap_log(addr, Access::Read);
+        value
+    }
+
+    fn read8_bus(&self, addr: u16) -> u8 {
         if self.dma_active {
             if addr < memory::HRAM.begin || addr > memory::HRAM.end {
                 log::warn!(
@@ -73,6 +92,7 @@ impl Gameboy {
             return res;
         }
         if let Some(res) = self.cartridge.read8(addr) {
+            self.cdl_log_read(addr, res);
             return res;
         }
         if let Some(res) = self.memory.read8(addr) {
@@ -82,6 +102,17 @@ impl Gameboy {
     }
 
     fn write8(&mut self, addr: u16, value: u8) {
+        if (addr >= memory::IO_REGISTERS.begin && addr <= memory::IO_REGISTERS.end) || addr == memory::interrupt::IE {
+            if addr == memory::ppu::DMA {
+                self.debugger.on_event(DebugEvent::DMA { source: value });
+            }
+            self.debugger.on_event(DebugEvent::IOWrite { addr, value });
+            self.log_io_write(addr, value);
+        }
+        self.debugger.on_write(addr);
+        self.hooks.on_write(addr, value);
+        self.cdl_log_write(addr, value);
+        self.heatmap_log(addr, Access::Write);
         if let Some(()) = self.apu.write8(addr, value) {
             return;
         }
@@ -97,7 +128,11 @@ impl Gameboy {
         if let Some(()) = self.timer.write8(addr, value) {
             return;
         }
+        let rumble = self.cartridge.rumble();
         if let Some(()) = self.cartridge.write8(addr, value) {
+            if self.cartridge.rumble() != rumble {
+                self.hooks.on_rumble(!rumble);
+            }
             return;
         }
         if let Some(()) = self.memory.write8(addr, value) {
@@ -151,6 +186,10 @@ pub struct Gameboy {
     memory: Memory,
     joypad: Joypad,
     timer: Timer,
+    debugger: Debugger,
+    cheats: Vec<Cheat>,
+    hooks: Hooks,
+    script: Option<ScriptHost>,
 
     dma_active: bool,
 
@@ -165,19 +204,26 @@ impl Gameboy {
     const TILE_DATA_COLUMNS: usize = 128;
     const SCREEN_ROWS: usize = 144;
     const SCREEN_COLUMNS: usize = 160;
-    const TILE_MAP_ROWS: usize = 256;
-    const TILE_MAP_COLUMNS: usize = 256;
 
     pub fn new(bootrom_path: String, rom_path: String) -> Self {
-        let gb = Self {
+        let screen = Screen::new(Self::SCREEN_ROWS, Self::SCREEN_COLUMNS, minifb::Scale::X4);
+        Self::with_screen(bootrom_path, rom_path, screen)
+    }
+
+    fn with_screen(bootrom_path: String, rom_path: String, screen: Screen) -> Self {
+        let mut gb = Self {
             cartridge: Cartridge::new(bootrom_path.clone(), rom_path.clone()),
             cpu: CPU::new(),
             ppu: PPU::new(),
             joypad: Joypad::new(),
             timer: Timer::new(),
-            screen: Screen::new(Self::SCREEN_ROWS, Self::SCREEN_COLUMNS, minifb::Scale::X4),
+            screen,
             apu: APU::new(),
             memory: Memory::new(),
+            debugger: Debugger::new(),
+            cheats: Vec::new(),
+            hooks: Hooks::new(),
+            script: None,
 
             dma_active: false,
 
@@ -186,6 +232,17 @@ impl Gameboy {
             vblank: false,
         };
         gb.cartridge.debug_print();
+
+        let cheat_file = std::path::Path::new(&rom_path).with_extension("cht");
+        if cheat_file.exists() {
+            gb.load_cheats(&cheat_file);
+        }
+        let watch_file = std::path::Path::new(&rom_path).with_extension("wch");
+        if watch_file.exists() {
+            gb.load_watches(&watch_file);
+        } else {
+            gb.set_watch_file(watch_file);
+        }
         gb
     }
 
@@ -199,7 +256,13 @@ impl Gameboy {
         let self_ptr = self as *mut Self;
 
         let mut tile_data_screen: Option<Screen> = None;
-        let mut tile_map_screen: Option<Screen> = None;
+        let mut map_viewer: Option<MapViewer> = None;
+        let mut memory_viewer: Option<MemoryViewer> = None;
+        let mut oam_viewer: Option<OamViewer> = None;
+        let mut io_viewer: Option<IoViewer> = None;
+        let mut event_viewer: Option<EventViewer> = None;
+        let mut heatmap_viewer: Option<HeatmapViewer> = None;
+        let mut watch_viewer: Option<WatchViewer> = None;
         if debug_windows {
             tile_data_screen = Some(Screen::new(
                 Self::TILE_DATA_ROWS,
@@ -207,11 +270,13 @@ impl Gameboy {
                 minifb::Scale::X4,
             ));
 
-            tile_map_screen = Some(Screen::new(
-                Self::TILE_MAP_ROWS,
-                Self::TILE_MAP_COLUMNS,
-                minifb::Scale::X4,
-            ));
+            map_viewer = Some(MapViewer::new());
+            memory_viewer = Some(MemoryViewer::new(self));
+            oam_viewer = Some(OamViewer::new());
+            io_viewer = Some(IoViewer::new());
+            event_viewer = Some(EventViewer::new(self));
+            heatmap_viewer = Some(HeatmapViewer::new(self));
+            watch_viewer = Some(WatchViewer::new());
         }
         let mut debug_counter = 0;
 
@@ -227,13 +292,31 @@ impl Gameboy {
                 self.ppu.tick(self_ptr)?;
                 self.timer.tick(self_ptr)?;
                 self.apu.tick(self_ptr)?;
+                self.cartridge.tick(self_ptr)?;
                 // }
+                self.log_interrupt_requests();
+                if let Some(reason) = self.debugger.take_crash() {
+                    if let Err(err) = self.write_crash_report(&reason) {
+                        log::error!("failed to write crash report: {}", err);
+                    }
+                }
+                if self.debugger.is_break_pending() && self.cpu.is_instruction_boundary() {
+                    self.running = self.debugger_repl();
+                }
+                if self.cpu.is_instruction_boundary() {
+                    self.checkpoint_if_due();
+                }
             }
             if let Some(frame_buffer) = self.ppu.get_frame_buffer() {
                 frame_ready = true;
                 self.screen.set_frame_buffer(frame_buffer);
             }
             if frame_ready {
+                self.apply_cheats();
+                self.debugger.on_frame();
+                self.hooks.on_frame();
+                self.update_watches();
+                self.cartridge.on_frame();
                 // println!("frame ready");
                 if debug_windows {
                     debug_counter += 1;
@@ -244,17 +327,36 @@ impl Gameboy {
                             screen.set_frame_buffer(&self.ppu.get_tile_data_frame_buffer(16));
                             screen.update();
                         }
-                        if let Some(ref mut screen) = tile_map_screen {
-                            screen.set_frame_buffer(&self.ppu.get_window_frame_buffer());
-                            screen.update();
-                        }
                         debug_counter = 0;
                     }
                 }
+                if let Some(ref mut viewer) = map_viewer {
+                    viewer.update(self);
+                }
+                if let Some(ref mut viewer) = memory_viewer {
+                    viewer.update(self);
+                }
+                if let Some(ref mut viewer) = oam_viewer {
+                    viewer.update(self);
+                }
+                if let Some(ref mut viewer) = io_viewer {
+                    viewer.update(self);
+                }
+                if let Some(ref mut viewer) = event_viewer {
+                    viewer.update(self);
+                }
+                if let Some(ref mut viewer) = heatmap_viewer {
+                    viewer.update(self);
+                }
+                if let Some(ref mut viewer) = watch_viewer {
+                    viewer.update(self);
+                }
 
                 (self.running, pause_pressed, fast_forward_pressed) = self.screen.update();
+                self.service_script();
 
                 self.joypad.tick(self_ptr)?;
+                self.record_input();
 
                 if pause_pressed {
                     paused = !paused;
@@ -298,9 +400,46 @@ impl Gameboy {
                 frame_ready = false;
             }
         }
+        self.save_code_data_log();
+        self.save_profile();
+        if let Err(err) = self.cartridge.save_if_dirty() {
+            log::error!("failed to write save file: {}", err);
+        }
         Ok(())
     }
 
+    pub fn enable_debugger(&mut self) {
+        self.enable_time_travel();
+        self.debugger.request_break("debugger started");
+    }
+
+    pub fn set_save_dir(&mut self, dir: std::path::PathBuf) {
+        self.cartridge.set_save_dir(&dir);
+    }
+
+    pub fn set_rtc_host_sync(&mut self, sync: bool) {
+        self.cartridge.set_rtc_host_sync(sync);
+    }
+
+    pub fn set_ir_transport(&mut self, transport: Box<dyn cartridge::infrared::IrTransport>) {
+        self.cartridge.set_ir_transport(transport);
+    }
+
+    pub fn set_history_size(&mut self, size: usize) {
+        self.debugger.set_history_size(size);
+    }
+
+    pub fn set_crash_report_dir(&mut self, dir: std::path::PathBuf) {
+        self.debugger.set_crash_report_dir(dir);
+    }
+
+    /// Called after `run` unwound from a panic, the state is dumped as it was when the panic happened
+    pub fn report_panic(&self, message: String) {
+        if let Err(err) = self.write_crash_report(&CrashReason::Panic(message)) {
+            log::error!("failed to write crash report: {}", err);
+        }
+    }
+
     pub fn switch_speed(&self) {
         panic!("switch speed not implemented");
     }
@@ -309,3 +448,44 @@ impl Gameboy {
         &self.screen.get_keys()
     }
 }
+
+#[cfg(test)]
+impl Gameboy {
+    /// Gameboy without a window running `rom` from $0100, the boot rom only unmaps itself.
+    pub(crate) fn headless(name: &str, rom: &[u8]) -> Self {
+        let dir = std::env::temp_dir().join(format!("gameboy_{}_{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        // the cpu starts at $00FC with ld a,1 and ldh ($50),a
+        let mut boot_rom = vec![0; 0x100];
+        boot_rom[0xFC..].copy_from_slice(&[0x3E, 0x01, 0xE0, 0x50]);
+        let (boot_rom_path, rom_path) = (dir.join("boot.bin"), dir.join("test.gb"));
+        std::fs::write(&boot_rom_path, boot_rom).unwrap();
+        std::fs::write(&rom_path, rom).unwrap();
+        let gb = Self::with_screen(
+            boot_rom_path.to_string_lossy().into_owned(),
+            rom_path.to_string_lossy().into_owned(),
+            Screen::headless(Self::SCREEN_ROWS, Self::SCREEN_COLUMNS),
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+        gb
+    }
+
+    /// Executes whole instructions in the order of the run loop, without the work at the end of a frame.
+    /// Meant for programs that leave the lcd off.
+    pub(crate) unsafe fn run_instructions(&mut self, count: u64) {
+        let self_ptr = self as *mut Self;
+        for _ in 0..count {
+            loop {
+                self.cpu.tick(self_ptr).unwrap();
+                self.ppu.tick(self_ptr).unwrap();
+                self.timer.tick(self_ptr).unwrap();
+                self.apu.tick(self_ptr).unwrap();
+                self.cartridge.tick(self_ptr).unwrap();
+                if self.cpu.is_instruction_boundary() {
+                    break;
+                }
+            }
+            self.checkpoint_if_due();
+        }
+    }
+}
@@ -6,7 +6,11 @@ use crate::bit;
 
 use self::driver::{AudioDriver, AudioQueue};
 
-use super::{memory, GameboyModule};
+use super::{
+    memory,
+    savestate::{SaveState, StateReader, StateWriter},
+    GameboyModule,
+};
 
 mod driver;
 mod noise;
@@ -34,9 +38,8 @@ pub struct APU {
 
     div: u8,
 
-    _stream: OutputStream,
-    _stream_handle: OutputStreamHandle,
-    sink: Sink,
+    // None without an audio device, samples are dropped then
+    output: Option<(OutputStream, OutputStreamHandle, Sink)>,
     audio_queue_sender: mpsc::Sender<AudioQueue>,
 }
 
@@ -92,6 +95,31 @@ impl super::MemoryInterface for APU {
     }
 }
 
+impl SaveState for APU {
+    fn save_state(&self, writer: &mut StateWriter) {
+        self.pulse_sweep.save_state(writer);
+        self.pulse.save_state(writer);
+        self.wave.save_state(writer);
+        self.noise.save_state(writer);
+        writer.write_bool(self.apu_enabled);
+        writer.write_u8(self.get_nr51());
+        writer.write_u8(self.get_nr50());
+        writer.write_u8(self.div);
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), String> {
+        self.pulse_sweep.load_state(reader)?;
+        self.pulse.load_state(reader)?;
+        self.wave.load_state(reader)?;
+        self.noise.load_state(reader)?;
+        self.apu_enabled = reader.read_bool()?;
+        self.set_nr51(reader.read_u8()?);
+        self.set_nr50(reader.read_u8()?);
+        self.div = reader.read_u8()?;
+        Ok(())
+    }
+}
+
 impl APU {
     const ENVELOPE_SWEEP_DIVIDER: u8 = 8;
     const SOUND_LENGTH_DIVIDER: u8 = 2;
@@ -100,9 +128,19 @@ impl APU {
     const CHANNELS: usize = 4;
 
     pub fn new() -> Self {
-        let (_stream, stream_handle) = OutputStream::try_default().unwrap();
         let (tx, rx) = mpsc::channel();
-        let apu = Self {
+        let output = match OutputStream::try_default() {
+            Ok((stream, stream_handle)) => {
+                let sink = Sink::try_new(&stream_handle).unwrap();
+                sink.append(AudioDriver::new(Self::AUDIO_SAMPLING_RATE, 2, rx));
+                Some((stream, stream_handle, sink))
+            }
+            Err(err) => {
+                log::warn!("no audio output: {}", err);
+                None
+            }
+        };
+        Self {
             pulse_sweep: pulse::PulseSweep::new(),
 
             pulse: pulse::Pulse::new(),
@@ -122,13 +160,9 @@ impl APU {
 
             div: 0,
 
-            sink: Sink::try_new(&stream_handle).unwrap(),
-            _stream: _stream,
-            _stream_handle: stream_handle,
+            output,
             audio_queue_sender: tx,
-        };
-        apu.sink.append(AudioDriver::new(Self::AUDIO_SAMPLING_RATE, 2, rx));
-        apu
+        }
     }
 
     pub fn tick_div(&mut self) {
@@ -203,6 +237,19 @@ impl APU {
         self.right_output_volume = value & 0b111;
     }
 
+    /// drops the samples generated since the last sync, e.g. while time travel replays
+    pub fn discard_samples(&mut self) {
+        self.pulse_sweep.reset_samples();
+        self.pulse.reset_samples();
+        self.wave.reset_samples();
+        self.noise.reset_samples();
+    }
+
+    #[cfg(test)]
+    pub(crate) fn pending_samples(&mut self) -> usize {
+        self.wave.get_samples().len() / 2
+    }
+
     pub unsafe fn sync(&mut self, gb_ptr: *mut crate::gameboy::Gameboy, delta_time: u128) {
         let mut queue: VecDeque<f32> = VecDeque::new();
 
@@ -270,12 +317,14 @@ impl APU {
             self.shall_clear_audio_queue
         );
 
-        self.audio_queue_sender
-            .send(AudioQueue {
-                queue,
-                shall_clear_old_samples: self.shall_clear_audio_queue,
-            })
-            .unwrap();
+        if self.output.is_some() {
+            self.audio_queue_sender
+                .send(AudioQueue {
+                    queue,
+                    shall_clear_old_samples: self.shall_clear_audio_queue,
+                })
+                .unwrap();
+        }
         self.pulse_sweep.reset_samples();
         self.pulse.reset_samples();
         self.wave.reset_samples();
@@ -1,6 +1,10 @@
 use crate::{
     bit,
-    gameboy::{memory, GameboyModule, MemoryInterface},
+    gameboy::{
+        memory,
+        savestate::{SaveState, StateReader, StateWriter},
+        GameboyModule, MemoryInterface,
+    },
 };
 
 use super::{APUChannel, APUEnvelope, APU};
@@ -85,6 +89,50 @@ impl MemoryInterface for Noise {
     }
 }
 
+impl SaveState for Noise {
+    fn save_state(&self, writer: &mut StateWriter) {
+        writer.write_bool(self.dac_enabled);
+        writer.write_u8(self.length_timer);
+        writer.write_u8(self.get_nr42());
+        writer.write_u8(self.get_nr43());
+        writer.write_bool(self.shall_trigger);
+        writer.write_bool(self.sound_length_enable);
+        writer.write_u16(self.t_cycles);
+        writer.write_u8(self.timer);
+        writer.write_bool(self.active);
+        writer.write_u16(self.lfsr);
+        writer.write_u8(self.curr_inital_envelope_volume);
+        writer.write_bool(self.curr_envelope_increase);
+        writer.write_u8(self.curr_sweep_pace);
+        writer.write_u8(self.sweep_volume);
+        writer.write_u8(self.envelope_tick);
+        writer.write_u16(self.wave_length_cycles);
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), String> {
+        self.dac_enabled = reader.read_bool()?;
+        self.length_timer = reader.read_u8()?;
+        let nr42 = reader.read_u8()?;
+        self.inital_envelope_volume = nr42 >> 4;
+        self.envelope_increase = bit!(nr42, 3) != 0;
+        self.sweep_pace = nr42 & 0b111;
+        self.set_nr43(reader.read_u8()?);
+        self.shall_trigger = reader.read_bool()?;
+        self.sound_length_enable = reader.read_bool()?;
+        self.t_cycles = reader.read_u16()?;
+        self.timer = reader.read_u8()?;
+        self.active = reader.read_bool()?;
+        self.lfsr = reader.read_u16()?;
+        self.curr_inital_envelope_volume = reader.read_u8()?;
+        self.curr_envelope_increase = reader.read_bool()?;
+        self.curr_sweep_pace = reader.read_u8()?;
+        self.sweep_volume = reader.read_u8()?;
+        self.envelope_tick = reader.read_u8()?;
+        self.wave_length_cycles = reader.read_u16()?;
+        Ok(())
+    }
+}
+
 impl Noise {
     pub fn new() -> Self {
         Self {
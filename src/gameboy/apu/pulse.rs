@@ -3,7 +3,11 @@ use num_traits::FromPrimitive;
 
 use crate::{
     bit,
-    gameboy::{memory, GameboyModule, MemoryInterface},
+    gameboy::{
+        memory,
+        savestate::{SaveState, StateReader, StateWriter},
+        GameboyModule, MemoryInterface,
+    },
 };
 
 use super::{APUChannel, APUEnvelope, APU};
@@ -88,6 +92,55 @@ impl MemoryInterface for Pulse {
     }
 }
 
+impl SaveState for Pulse {
+    fn save_state(&self, writer: &mut StateWriter) {
+        writer.write_bool(self.dac_enabled);
+        writer.write_u8(self.wave_duty as u8);
+        writer.write_u16(self.wave_length);
+        writer.write_bytes(&self.pulse_frame);
+        writer.write_u8(self.length_timer);
+        writer.write_u8(self.inital_envelope_volume);
+        writer.write_bool(self.envelope_increase);
+        writer.write_u8(self.sweep_pace);
+        writer.write_bool(self.shall_trigger);
+        writer.write_bool(self.sound_length_enable);
+        writer.write_u16(self.t_cycles);
+        writer.write_u8(self.timer);
+        writer.write_bool(self.active);
+        writer.write_u8(self.frame_index as u8);
+        writer.write_u8(self.curr_inital_envelope_volume);
+        writer.write_bool(self.curr_envelope_increase);
+        writer.write_u8(self.curr_sweep_pace);
+        writer.write_u8(self.sweep_volume);
+        writer.write_u8(self.envelope_tick);
+        writer.write_u16(self.wave_length_cycles);
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), String> {
+        self.dac_enabled = reader.read_bool()?;
+        self.wave_duty = FromPrimitive::from_u8(reader.read_u8()? & 0b11).expect("couldn't convert wave duty");
+        self.wave_length = reader.read_u16()?;
+        reader.read_bytes(&mut self.pulse_frame)?;
+        self.length_timer = reader.read_u8()?;
+        self.inital_envelope_volume = reader.read_u8()?;
+        self.envelope_increase = reader.read_bool()?;
+        self.sweep_pace = reader.read_u8()?;
+        self.shall_trigger = reader.read_bool()?;
+        self.sound_length_enable = reader.read_bool()?;
+        self.t_cycles = reader.read_u16()?;
+        self.timer = reader.read_u8()?;
+        self.active = reader.read_bool()?;
+        self.frame_index = reader.read_u8()? as usize;
+        self.curr_inital_envelope_volume = reader.read_u8()?;
+        self.curr_envelope_increase = reader.read_bool()?;
+        self.curr_sweep_pace = reader.read_u8()?;
+        self.sweep_volume = reader.read_u8()?;
+        self.envelope_tick = reader.read_u8()?;
+        self.wave_length_cycles = reader.read_u16()?;
+        Ok(())
+    }
+}
+
 impl Pulse {
     const PULSE_FRAME_SIZE: usize = 8;
     pub fn new() -> Self {
@@ -349,6 +402,57 @@ impl MemoryInterface for PulseSweep {
     }
 }
 
+impl SaveState for PulseSweep {
+    fn save_state(&self, writer: &mut StateWriter) {
+        writer.write_u8(self.get_nr10());
+        writer.write_bool(self.dac_enabled);
+        writer.write_u8(self.wave_duty as u8);
+        writer.write_u16(self.wave_length);
+        writer.write_bytes(&self.pulse_frame);
+        writer.write_u8(self.length_timer);
+        writer.write_u8(self.inital_envelope_volume);
+        writer.write_bool(self.envelope_increase);
+        writer.write_u8(self.sweep_pace);
+        writer.write_bool(self.shall_trigger);
+        writer.write_bool(self.sound_length_enable);
+        writer.write_u16(self.t_cycles);
+        writer.write_u8(self.timer);
+        writer.write_bool(self.active);
+        writer.write_u8(self.frame_index as u8);
+        writer.write_u8(self.curr_inital_envelope_volume);
+        writer.write_bool(self.curr_envelope_increase);
+        writer.write_u8(self.curr_sweep_pace);
+        writer.write_u8(self.sweep_volume);
+        writer.write_u8(self.envelope_tick);
+        writer.write_u16(self.wave_length_cycles);
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), String> {
+        self.set_nr10(reader.read_u8()?);
+        self.dac_enabled = reader.read_bool()?;
+        self.wave_duty = FromPrimitive::from_u8(reader.read_u8()? & 0b11).expect("couldn't convert wave duty");
+        self.wave_length = reader.read_u16()?;
+        reader.read_bytes(&mut self.pulse_frame)?;
+        self.length_timer = reader.read_u8()?;
+        self.inital_envelope_volume = reader.read_u8()?;
+        self.envelope_increase = reader.read_bool()?;
+        self.sweep_pace = reader.read_u8()?;
+        self.shall_trigger = reader.read_bool()?;
+        self.sound_length_enable = reader.read_bool()?;
+        self.t_cycles = reader.read_u16()?;
+        self.timer = reader.read_u8()?;
+        self.active = reader.read_bool()?;
+        self.frame_index = reader.read_u8()? as usize;
+        self.curr_inital_envelope_volume = reader.read_u8()?;
+        self.curr_envelope_increase = reader.read_bool()?;
+        self.curr_sweep_pace = reader.read_u8()?;
+        self.sweep_volume = reader.read_u8()?;
+        self.envelope_tick = reader.read_u8()?;
+        self.wave_length_cycles = reader.read_u16()?;
+        Ok(())
+    }
+}
+
 impl PulseSweep {
     const PULSE_SWEEP_FRAME_SIZE: usize = 8;
     pub fn new() -> Self {
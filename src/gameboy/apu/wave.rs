@@ -1,10 +1,13 @@
 use num_derive::FromPrimitive;
 use num_traits::FromPrimitive;
-use rodio::OutputStream;
 
 use crate::{
     bit,
-    gameboy::{memory, GameboyModule, MemoryInterface},
+    gameboy::{
+        memory,
+        savestate::{SaveState, StateReader, StateWriter},
+        GameboyModule, MemoryInterface,
+    },
 };
 
 use super::{APUChannel, APU};
@@ -67,7 +70,7 @@ impl MemoryInterface for Wave {
         } else if addr == memory::apu::NR34 {
             return Some(self.get_nr34());
         } else if addr >= memory::apu::WAVE_PATTERN_RAM.begin && addr <= memory::apu::WAVE_PATTERN_RAM.end {
-            return Some(self.wave_pattern_ram[addr as usize]);
+            return Some(self.wave_pattern_ram[(addr - memory::apu::WAVE_PATTERN_RAM.begin) as usize]);
         }
         return None;
     }
@@ -92,10 +95,47 @@ impl MemoryInterface for Wave {
     }
 }
 
+impl SaveState for Wave {
+    fn save_state(&self, writer: &mut StateWriter) {
+        writer.write_bool(self.dac_enabled);
+        writer.write_u16(self.wave_length);
+        writer.write_u8(self.length_timer);
+        writer.write_u8(self.output_level as u8);
+        writer.write_bool(self.shall_trigger);
+        writer.write_bool(self.sound_length_enable);
+        writer.write_bytes(&self.wave_pattern_ram);
+        writer.write_u16(self.t_cycles);
+        writer.write_u8(self.timer);
+        writer.write_bool(self.active);
+        writer.write_u8(self.frame_index as u8);
+        writer.write_u16(self.wave_length_cycles);
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), String> {
+        self.dac_enabled = reader.read_bool()?;
+        self.wave_length = reader.read_u16()?;
+        self.length_timer = reader.read_u8()?;
+        self.output_level =
+            FromPrimitive::from_u8(reader.read_u8()? & 0b11).expect("couldn't convert wave output level");
+        self.shall_trigger = reader.read_bool()?;
+        self.sound_length_enable = reader.read_bool()?;
+        let mut wave_pattern_ram = [0; memory::apu::WAVE_PATTERN_RAM.size];
+        reader.read_bytes(&mut wave_pattern_ram)?;
+        for (i, value) in wave_pattern_ram.iter().enumerate() {
+            self.set_wave_pattern(memory::apu::WAVE_PATTERN_RAM.begin + i as u16, *value);
+        }
+        self.t_cycles = reader.read_u16()?;
+        self.timer = reader.read_u8()?;
+        self.active = reader.read_bool()?;
+        self.frame_index = reader.read_u8()? as usize;
+        self.wave_length_cycles = reader.read_u16()?;
+        Ok(())
+    }
+}
+
 impl Wave {
     const WAVE_PATTERN_FRAME_SIZE: usize = 32;
     pub fn new() -> Self {
-        let (_stream, _stream_handle) = OutputStream::try_default().unwrap();
         Self {
             dac_enabled: false,
 
@@ -3,6 +3,7 @@ use std::collections::HashMap;
 
 use std::fs::File;
 use std::io::{BufReader, Read};
+use std::path::PathBuf;
 
 use crate::gameboy::memory;
 use crate::utils;
@@ -10,13 +11,29 @@ use crate::utils;
 use num_derive::FromPrimitive;
 use num_traits::FromPrimitive;
 
+use self::huc1::HuC1;
+use self::huc3::HuC3;
+use self::infrared::{Infrared, IrTransport};
 use self::mbc0::MBC0;
 use self::mbc1::MBC1;
+use self::mbc2::MBC2;
+use self::mbc3::MBC3;
+use self::mbc5::MBC5;
+use self::rtc::Rtc;
 
-use super::MemoryInterface;
+use super::savestate::{SaveState, StateReader, StateWriter};
+use super::{Gameboy, GameboyModule, MemoryInterface};
 
+pub mod huc1;
+pub mod huc3;
+pub mod infrared;
 pub mod mbc0;
 pub mod mbc1;
+pub mod mbc2;
+pub mod mbc3;
+pub mod mbc5;
+pub mod rtc;
+mod save_file;
 
 #[derive(Clone, Debug, FromPrimitive)]
 #[allow(non_camel_case_types)]
@@ -25,8 +42,54 @@ enum CartridgeType {
     MBC1 = 0x01,
     MBC1_RAM = 0x02,
     MBC1_RAM_BATTERY = 0x03,
+    MBC2 = 0x05,
+    MBC2_BATTERY = 0x06,
+    MBC3_TIMER_BATTERY = 0x0F,
+    MBC3_TIMER_RAM_BATTERY = 0x10,
+    MBC3 = 0x11,
+    MBC3_RAM = 0x12,
     MBC3_RAM_BATTERY = 0x13,
-    UNKNOWN = 0xFF,
+    MBC5 = 0x19,
+    MBC5_RAM = 0x1A,
+    MBC5_RAM_BATTERY = 0x1B,
+    MBC5_RUMBLE = 0x1C,
+    MBC5_RUMBLE_RAM = 0x1D,
+    MBC5_RUMBLE_RAM_BATTERY = 0x1E,
+    HUC3 = 0xFE,
+    HUC1_RAM_BATTERY = 0xFF,
+}
+
+impl CartridgeType {
+    fn has_battery(&self) -> bool {
+        matches!(
+            self,
+            CartridgeType::MBC1_RAM_BATTERY
+                | CartridgeType::MBC2_BATTERY
+                | CartridgeType::MBC3_TIMER_BATTERY
+                | CartridgeType::MBC3_TIMER_RAM_BATTERY
+                | CartridgeType::MBC3_RAM_BATTERY
+                | CartridgeType::MBC5_RAM_BATTERY
+                | CartridgeType::MBC5_RUMBLE_RAM_BATTERY
+                | CartridgeType::HUC3
+                | CartridgeType::HUC1_RAM_BATTERY
+        )
+    }
+
+    /// whether the cartridge has a clock, which is kept in a footer of the save file
+    fn has_timer(&self) -> bool {
+        matches!(
+            self,
+            CartridgeType::MBC3_TIMER_BATTERY | CartridgeType::MBC3_TIMER_RAM_BATTERY | CartridgeType::HUC3
+        )
+    }
+
+    /// whether bit 3 of the ram bank register drives a motor
+    fn has_rumble(&self) -> bool {
+        matches!(
+            self,
+            CartridgeType::MBC5_RUMBLE | CartridgeType::MBC5_RUMBLE_RAM | CartridgeType::MBC5_RUMBLE_RAM_BATTERY
+        )
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -37,10 +100,33 @@ struct CartridgeHeader {
     rom_banks: usize,
     ram_size: usize,
     ram_banks: usize,
+    // ram as listed in the header, which is what battery saves hold
+    save_size: usize,
+}
+
+/// Game Genie style substitution of a rom byte, applied on every read of `addr`
+/// as long as the original byte matches `compare`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RomPatch {
+    pub addr: u16,
+    pub value: u8,
+    pub compare: Option<u8>,
+}
+
+struct MBC(Box<dyn MBCInterface>, Vec<RomPatch>);
+
+impl MBC {
+    fn patch(&self, addr: u16, value: u8) -> u8 {
+        for patch in self.1.iter() {
+            if patch.addr == addr && patch.compare.is_none_or(|compare| compare == value) {
+                return patch.value;
+            }
+        }
+        value
+    }
 }
 
-struct MBC(Box<dyn MBCInterface>);
-trait MBCInterface {
+trait MBCInterface: SaveState {
     fn read8_rom_bank_0(&self, addr: u16) -> u8;
     fn read8_rom_bank_n(&self, addr: u16) -> u8;
     fn read8_ram_bank_n(&self, addr: u16) -> u8;
@@ -51,17 +137,103 @@ trait MBCInterface {
 
     fn get_rom(&self) -> &Vec<u8>;
 
+    fn get_ram(&self) -> &Vec<u8>;
+
+    fn get_ram_mut(&mut self) -> &mut Vec<u8>;
+
+    /// writes `addr` of the given ram bank without touching the mapper registers, for cheats
+    fn poke_ram(&mut self, bank: usize, addr: u16, value: u8) {
+        let ram = self.get_ram_mut();
+        if ram.is_empty() {
+            return;
+        }
+        let offset = ram_offset(ram, bank, addr);
+        ram[offset] = value;
+    }
+
+    /// rom bank currently mapped into $0000-$3FFF
+    fn selected_rom_bank_0(&self) -> usize {
+        0
+    }
+
+    /// rom bank currently mapped into $4000-$7FFF
+    fn selected_rom_bank(&self) -> usize;
+
+    /// ram bank currently mapped into $A000-$BFFF
+    fn selected_ram_bank(&self) -> usize;
+
+    /// called every t-cycle
+    fn tick(&mut self) {}
+
+    /// whether the rumble motor of the cartridge is running
+    fn rumble(&self) -> bool {
+        false
+    }
+
+    /// real time clock of the cartridge, if it has one
+    fn rtc(&self) -> Option<&Rtc> {
+        None
+    }
+
+    fn rtc_mut(&mut self) -> Option<&mut Rtc> {
+        None
+    }
+
+    /// infrared LED and receiver of the cartridge, if it has them
+    fn infrared_mut(&mut self) -> Option<&mut Infrared> {
+        None
+    }
+
     fn new(rom: Vec<u8>, ram: Vec<u8>) -> Self
     where
         Self: Sized;
 }
 
+/// Bank numbers past the end of the rom lose the bits of the address lines that are not connected.
+/// Roms that are not a power of two in size, like overdumps or homebrew, repeat their banks after the end.
+fn wrap_rom_bank(rom: &[u8], bank: usize) -> usize {
+    let banks = std::cmp::max(rom.len().div_ceil(memory::cartridge::ROM_BANK_N.size), 1);
+    let bank = bank & (banks.next_power_of_two() - 1);
+    if bank < banks {
+        bank
+    } else {
+        bank % banks
+    }
+}
+
+/// offset into the rom of `addr` inside of `bank`
+fn rom_offset(rom: &[u8], bank: usize, addr: u16) -> usize {
+    let offset = wrap_rom_bank(rom, bank) * memory::cartridge::ROM_BANK_N.size
+        + (addr as usize & (memory::cartridge::ROM_BANK_N.size - 1));
+    offset % rom.len()
+}
+
+/// rom for mapper tests whose banks start with their bank number, low byte first
+#[cfg(test)]
+fn numbered_rom(banks: usize) -> Vec<u8> {
+    let mut rom = vec![0; banks * memory::cartridge::ROM_BANK_N.size];
+    for (bank, data) in rom.chunks_mut(memory::cartridge::ROM_BANK_N.size).enumerate() {
+        data[..2].copy_from_slice(&(bank as u16).to_le_bytes());
+    }
+    rom
+}
+
+/// ram bank numbers repeat the banks of the ram, cartridges without ram count as one bank
+fn wrap_ram_bank(ram: &[u8], bank: usize) -> usize {
+    bank % std::cmp::max(ram.len() / memory::cartridge::EXTERNAL_RAM.size, 1)
+}
+
+/// offset into the ram of `addr` inside of `bank`, smaller rams than a bank repeat within it
+fn ram_offset(ram: &[u8], bank: usize, addr: u16) -> usize {
+    ((addr - memory::cartridge::EXTERNAL_RAM.begin) as usize + memory::cartridge::EXTERNAL_RAM.size * bank) % ram.len()
+}
+
 impl MemoryInterface for MBC {
     fn read8(&self, addr: u16) -> Option<u8> {
         if addr >= memory::cartridge::ROM_BANK_0.begin && addr <= memory::cartridge::ROM_BANK_0.end {
-            return Some(self.0.read8_rom_bank_0(addr));
+            return Some(self.patch(addr, self.0.read8_rom_bank_0(addr)));
         } else if addr >= memory::cartridge::ROM_BANK_N.begin && addr <= memory::cartridge::ROM_BANK_N.end {
-            return Some(self.0.read8_rom_bank_n(addr));
+            return Some(self.patch(addr, self.0.read8_rom_bank_n(addr)));
         } else if addr >= memory::cartridge::EXTERNAL_RAM.begin && addr <= memory::cartridge::EXTERNAL_RAM.end {
             return Some(self.0.read8_ram_bank_n(addr));
         } else {
@@ -120,23 +292,41 @@ impl std::convert::From<&Vec<u8>> for CartridgeHeader {
 
         let mut header: CartridgeHeader = CartridgeHeader {
             title: "".to_string(),
-            cartridge_type: CartridgeType::UNKNOWN,
+            cartridge_type: CartridgeType::ROM_ONLY,
             rom_size: 0,
             rom_banks: 0,
             ram_size: 0,
             ram_banks: 0,
+            save_size: 0,
         };
         header.title =
             String::from(std::str::from_utf8(&rom[0x134..=0x143]).unwrap_or("failed to parse cartridge title"));
         header.cartridge_type =
             FromPrimitive::from_u8(rom[0x147]).expect(format!("cartridge type not supported {}", rom[0x147]).as_str());
-        header.rom_size = ROM_SIZE_MAP[&rom[0x148]].0;
+        header.rom_size = match ROM_SIZE_MAP.get(&rom[0x148]) {
+            Some((size, _)) => *size,
+            None => {
+                log::warn!("unknown rom size {:#04X}, using the file size", rom[0x148]);
+                rom.len().next_multiple_of(memory::cartridge::ROM_BANK_N.size)
+            }
+        };
         header.rom_banks = header.rom_size / 0x4000;
-        header.ram_size = std::cmp::max(RAM_SIZE_MAP[&rom[0x149]].0, 0x2000);
+        if header.rom_size != rom.len() {
+            log::warn!(
+                "header lists a {} byte rom but the file has {} bytes",
+                header.rom_size,
+                rom.len()
+            );
+        }
+        header.save_size = RAM_SIZE_MAP.get(&rom[0x149]).map_or(0, |(size, _)| *size);
+        // cartridges without ram leave $A000-$BFFF open
+        header.ram_size = header.save_size;
         header.ram_banks = header.ram_size / 0x2000;
-
-        if header.rom_size >= 0x200000 {
-            panic!("2MiB rom cartridges not supported yet");
+        if let CartridgeType::MBC2 | CartridgeType::MBC2_BATTERY = header.cartridge_type {
+            // the ram is part of the mapper, the header lists none
+            header.ram_size = MBC2::RAM_SIZE;
+            header.ram_banks = 1;
+            header.save_size = MBC2::RAM_SIZE;
         }
 
         header
@@ -148,6 +338,13 @@ pub struct Cartridge {
     mbc: MBC,
     boot_rom: Vec<u8>,
     boot_flag: u8,
+
+    // only set for cartridges with a battery
+    save_path: Option<PathBuf>,
+    ram_dirty: bool,
+    frames_since_save: u32,
+    // an existing save that could not be read is never overwritten
+    load_failed: bool,
 }
 
 impl MemoryInterface for Cartridge {
@@ -173,6 +370,9 @@ impl MemoryInterface for Cartridge {
             }
         }
         if let Some(_res) = self.mbc.write8(addr, value) {
+            if addr >= memory::cartridge::EXTERNAL_RAM.begin && addr <= memory::cartridge::EXTERNAL_RAM.end {
+                self.ram_dirty = true;
+            }
         } else if addr == memory::cartridge::BOOTROM_FLAG {
             log::warn!("bootrom flag set to {:#04X}", value);
             self.boot_flag = value;
@@ -183,25 +383,74 @@ impl MemoryInterface for Cartridge {
     }
 }
 
+impl GameboyModule for Cartridge {
+    unsafe fn tick(&mut self, _gb_ptr: *mut Gameboy) -> Result<u32, std::fmt::Error> {
+        self.mbc.0.tick();
+        Ok(0)
+    }
+}
+
+impl SaveState for Cartridge {
+    fn save_state(&self, writer: &mut StateWriter) {
+        writer.write_u8(self.boot_flag);
+        self.mbc.0.save_state(writer);
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), String> {
+        self.boot_flag = reader.read_u8()?;
+        self.mbc.0.load_state(reader)?;
+        self.ram_dirty = true;
+        Ok(())
+    }
+}
+
 impl Cartridge {
     pub fn new(bootrom_path: String, rom_path: String) -> Self {
-        let rom = Self::load_rom(rom_path);
+        let mut rom = Self::load_rom(rom_path.clone());
         let header = CartridgeHeader::from(&rom);
+        // mappers can rely on whole banks and on the 32 KiB mapped without banking
+        let padded_size = std::cmp::max(rom.len().next_multiple_of(memory::cartridge::ROM_BANK_N.size), 0x8000);
+        rom.resize(padded_size, 0xFF);
         let ram = vec![0; header.ram_size];
+        let has_timer = header.cartridge_type.has_timer();
+        let has_rumble = header.cartridge_type.has_rumble();
 
-        let mem = Cartridge {
+        let mut mem = Cartridge {
             header: header.clone(),
             boot_rom: Self::load_boot_rom(bootrom_path),
             boot_flag: 0,
+            save_path: header
+                .cartridge_type
+                .has_battery()
+                .then(|| PathBuf::from(&rom_path).with_extension("sav")),
+            ram_dirty: false,
+            frames_since_save: 0,
+            load_failed: false,
             mbc: match header.cartridge_type {
-                CartridgeType::ROM_ONLY => MBC(Box::new(MBC0::new(rom, ram))),
-                CartridgeType::MBC1 => MBC(Box::new(MBC1::new(rom, ram))),
-                CartridgeType::MBC1_RAM => MBC(Box::new(MBC1::new(rom, ram))),
-                CartridgeType::MBC1_RAM_BATTERY => MBC(Box::new(MBC1::new(rom, ram))),
-                CartridgeType::MBC3_RAM_BATTERY => MBC(Box::new(MBC1::new(rom, ram))),
-                CartridgeType::UNKNOWN => panic!("cartridge type is 0xFF"),
+                CartridgeType::ROM_ONLY => MBC(Box::new(MBC0::new(rom, ram)), Vec::new()),
+                CartridgeType::MBC1 => MBC(Box::new(MBC1::new(rom, ram)), Vec::new()),
+                CartridgeType::MBC1_RAM => MBC(Box::new(MBC1::new(rom, ram)), Vec::new()),
+                CartridgeType::MBC1_RAM_BATTERY => MBC(Box::new(MBC1::new(rom, ram)), Vec::new()),
+                CartridgeType::MBC2 => MBC(Box::new(MBC2::new(rom, ram)), Vec::new()),
+                CartridgeType::MBC2_BATTERY => MBC(Box::new(MBC2::new(rom, ram)), Vec::new()),
+                CartridgeType::MBC3_TIMER_BATTERY => MBC(Box::new(MBC3::with_rtc(rom, ram, has_timer)), Vec::new()),
+                CartridgeType::MBC3_TIMER_RAM_BATTERY => MBC(Box::new(MBC3::with_rtc(rom, ram, has_timer)), Vec::new()),
+                CartridgeType::MBC3 => MBC(Box::new(MBC3::with_rtc(rom, ram, has_timer)), Vec::new()),
+                CartridgeType::MBC3_RAM => MBC(Box::new(MBC3::with_rtc(rom, ram, has_timer)), Vec::new()),
+                CartridgeType::MBC3_RAM_BATTERY => MBC(Box::new(MBC3::with_rtc(rom, ram, has_timer)), Vec::new()),
+                CartridgeType::MBC5 => MBC(Box::new(MBC5::with_rumble(rom, ram, has_rumble)), Vec::new()),
+                CartridgeType::MBC5_RAM => MBC(Box::new(MBC5::with_rumble(rom, ram, has_rumble)), Vec::new()),
+                CartridgeType::MBC5_RAM_BATTERY => MBC(Box::new(MBC5::with_rumble(rom, ram, has_rumble)), Vec::new()),
+                CartridgeType::MBC5_RUMBLE => MBC(Box::new(MBC5::with_rumble(rom, ram, has_rumble)), Vec::new()),
+                CartridgeType::MBC5_RUMBLE_RAM => MBC(Box::new(MBC5::with_rumble(rom, ram, has_rumble)), Vec::new()),
+                CartridgeType::MBC5_RUMBLE_RAM_BATTERY => {
+                    MBC(Box::new(MBC5::with_rumble(rom, ram, has_rumble)), Vec::new())
+                }
+                CartridgeType::HUC3 => MBC(Box::new(HuC3::new(rom, ram)), Vec::new()),
+                CartridgeType::HUC1_RAM_BATTERY => MBC(Box::new(HuC1::new(rom, ram)), Vec::new()),
             },
         };
+        mem.load_save();
 
         mem
     }
@@ -220,10 +469,6 @@ impl Cartridge {
         buffer
     }
 
-    fn _load_ram(_rom_path: String) -> Vec<u8> {
-        Vec::new()
-    }
-
     fn load_boot_rom(bootrom_path: String) -> Vec<u8> {
         let f = File::open(bootrom_path).unwrap();
         let mut reader = BufReader::new(f);
@@ -237,15 +482,122 @@ impl Cartridge {
         buffer
     }
 
+    pub fn title(&self) -> &str {
+        &self.header.title
+    }
+
+    /// rom bank visible at the given address, the boot rom counts as bank 0
+    pub fn rom_bank(&self, addr: u16) -> usize {
+        if addr >= memory::cartridge::ROM_BANK_N.begin && addr <= memory::cartridge::ROM_BANK_N.end {
+            self.mbc.0.selected_rom_bank()
+        } else if self.boot_flag == 0 && addr <= memory::cartridge::BOOTROM.end {
+            0
+        } else if addr <= memory::cartridge::ROM_BANK_0.end {
+            self.mbc.0.selected_rom_bank_0()
+        } else {
+            0
+        }
+    }
+
+    pub fn ram_bank(&self) -> usize {
+        self.mbc.0.selected_ram_bank()
+    }
+
+    /// writes to a ram bank that does not have to be mapped, see `MBCInterface::poke_ram`
+    pub fn poke_ram(&mut self, bank: usize, addr: u16, value: u8) {
+        self.mbc.0.poke_ram(bank, addr, value);
+        self.ram_dirty = true;
+    }
+
+    pub fn rumble(&self) -> bool {
+        self.mbc.0.rumble()
+    }
+
+    /// lets the real time clock follow the host clock instead of counting emulated time
+    pub fn set_rtc_host_sync(&mut self, sync: bool) {
+        match self.mbc.0.rtc_mut() {
+            Some(rtc) => rtc.set_host_sync(sync),
+            None => log::warn!("cartridge has no real time clock"),
+        }
+    }
+
+    /// connects the infrared LED and receiver, e.g. to another emulator instance
+    pub fn set_ir_transport(&mut self, transport: Box<dyn IrTransport>) {
+        match self.mbc.0.infrared_mut() {
+            Some(infrared) => infrared.set_transport(transport),
+            None => log::warn!("cartridge has no infrared port"),
+        }
+    }
+
+    /// replaces the active Game Genie patches
+    pub fn set_rom_patches(&mut self, patches: Vec<RomPatch>) {
+        self.mbc.1 = patches;
+    }
+
+    pub fn rom_size(&self) -> usize {
+        self.mbc.0.get_rom().len()
+    }
+
+    /// offset into the rom file of the byte visible at the given address, None outside of rom or for the boot rom
+    pub fn rom_offset(&self, addr: u16) -> Option<usize> {
+        if self.boot_flag == 0 && addr <= memory::cartridge::BOOTROM.end {
+            return None;
+        }
+        if addr > memory::cartridge::ROM_BANK_N.end {
+            return None;
+        }
+        let offset = self.rom_bank(addr) * memory::cartridge::ROM_BANK_N.size
+            + (addr & (memory::cartridge::ROM_BANK_N.size - 1) as u16) as usize;
+        Some(offset % self.rom_size())
+    }
+
+    /// byte at `addr` as seen with `bank` mapped, None outside of rom or for the boot rom
+    pub fn read_rom_bank(&self, bank: usize, addr: u16) -> Option<u8> {
+        if (self.boot_flag == 0 && addr <= memory::cartridge::BOOTROM.end) || addr > memory::cartridge::ROM_BANK_N.end {
+            return None;
+        }
+        Some(self.mbc.0.get_rom()[rom_offset(self.mbc.0.get_rom(), bank, addr)])
+    }
+
     pub fn debug_print(&self) {
         let mut print_str = String::new();
 
         use std::fmt::Write;
         writeln!(print_str, "Title:\t{}", self.header.title).unwrap();
         writeln!(print_str, "Cartridge Type: {:?}", self.header.cartridge_type).unwrap();
-        writeln!(print_str, "ROM Size: {}", ROM_SIZE_MAP[&self.mbc.0.get_rom()[0x148]].1).unwrap();
-        writeln!(print_str, "RAM Size: {}", RAM_SIZE_MAP[&self.mbc.0.get_rom()[0x149]].1).unwrap();
+        let rom = self.mbc.0.get_rom();
+        writeln!(
+            print_str,
+            "ROM Size: {}",
+            ROM_SIZE_MAP.get(&rom[0x148]).map_or("unknown", |(_, name)| name)
+        )
+        .unwrap();
+        writeln!(
+            print_str,
+            "RAM Size: {}",
+            RAM_SIZE_MAP.get(&rom[0x149]).map_or("unknown", |(_, name)| name)
+        )
+        .unwrap();
 
         println!("{}", print_str);
     }
 }
+
+#[test]
+fn rom_bank_wrap_test() {
+    let mut rom = vec![0; 0x800000];
+    rom[0x147] = CartridgeType::MBC5 as u8;
+    rom[0x148] = 0x08;
+    let header = CartridgeHeader::from(&rom);
+    assert_eq!((header.rom_size, header.rom_banks), (0x800000, 512));
+    assert_eq!((header.ram_size, header.ram_banks), (0, 0));
+    assert_eq!(wrap_rom_bank(&rom, 511), 511);
+    assert_eq!(wrap_rom_bank(&rom, 512), 0);
+
+    // 3 banks are mirrored like 4 banks with the last one repeating the first
+    let rom = vec![0; 3 * 0x4000];
+    assert_eq!(wrap_rom_bank(&rom, 2), 2);
+    assert_eq!(wrap_rom_bank(&rom, 3), 0);
+    assert_eq!(wrap_rom_bank(&rom, 6), 2);
+    assert_eq!(rom_offset(&rom, 7, 0x4001), 0x0001);
+}
@@ -0,0 +1,184 @@
+use crate::gameboy::savestate::{SaveState, StateReader, StateWriter};
+
+use super::{infrared::Infrared, ram_offset, rom_offset, wrap_ram_bank, wrap_rom_bank, MBCInterface};
+
+/// Hudson HuC1 with up to 64 rom banks and 4 ram banks. Instead of enabling ram the first register
+/// switches $A000-$BFFF between ram and the infrared LED and receiver.
+pub struct HuC1 {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    infrared: Infrared,
+
+    ir_mode: bool,
+    selected_rom_bank: u8,
+    selected_ram_bank: u8,
+}
+
+impl HuC1 {
+    const IR_MODE: u8 = 0x0E;
+}
+
+impl MBCInterface for HuC1 {
+    fn read8_rom_bank_0(&self, addr: u16) -> u8 {
+        self.rom[rom_offset(&self.rom, 0, addr)]
+    }
+
+    fn read8_rom_bank_n(&self, addr: u16) -> u8 {
+        self.rom[rom_offset(&self.rom, self.selected_rom_bank(), addr)]
+    }
+
+    fn read8_ram_bank_n(&self, addr: u16) -> u8 {
+        if self.ir_mode {
+            self.infrared.read()
+        } else if self.ram.is_empty() {
+            0xFF
+        } else {
+            self.ram[ram_offset(&self.ram, self.selected_ram_bank(), addr)]
+        }
+    }
+
+    fn write8_rom_bank_0(&mut self, addr: u16, value: u8) {
+        if addr >= write::IR_SELECT.begin && addr <= write::IR_SELECT.end {
+            self.ir_mode = value & 0x0F == Self::IR_MODE;
+            log::debug!("huc1 infrared mapped: {}", self.ir_mode);
+        } else if addr >= write::ROM_BANK_NUMBER.begin && addr <= write::ROM_BANK_NUMBER.end {
+            self.selected_rom_bank = std::cmp::max(value & 0x3F, 1);
+        }
+    }
+
+    fn write8_rom_bank_n(&mut self, addr: u16, value: u8) {
+        if addr >= write::RAM_BANK_NUMBER.begin && addr <= write::RAM_BANK_NUMBER.end {
+            self.selected_ram_bank = value & 0x03;
+        }
+    }
+
+    fn write8_ram_bank_n(&mut self, addr: u16, value: u8) {
+        if self.ir_mode {
+            self.infrared.write(value);
+        } else if !self.ram.is_empty() {
+            let offset = ram_offset(&self.ram, self.selected_ram_bank(), addr);
+            self.ram[offset] = value;
+        }
+    }
+
+    fn get_rom(&self) -> &Vec<u8> {
+        &self.rom
+    }
+
+    fn get_ram(&self) -> &Vec<u8> {
+        &self.ram
+    }
+
+    fn get_ram_mut(&mut self) -> &mut Vec<u8> {
+        &mut self.ram
+    }
+
+    fn selected_rom_bank(&self) -> usize {
+        wrap_rom_bank(&self.rom, self.selected_rom_bank as usize)
+    }
+
+    fn selected_ram_bank(&self) -> usize {
+        wrap_ram_bank(&self.ram, self.selected_ram_bank as usize)
+    }
+
+    fn infrared_mut(&mut self) -> Option<&mut Infrared> {
+        Some(&mut self.infrared)
+    }
+
+    fn new(rom: Vec<u8>, ram: Vec<u8>) -> Self
+    where
+        Self: Sized,
+    {
+        Self {
+            rom,
+            ram,
+            infrared: Infrared::new(),
+            ir_mode: false,
+            selected_rom_bank: 1,
+            selected_ram_bank: 0,
+        }
+    }
+}
+
+impl SaveState for HuC1 {
+    fn save_state(&self, writer: &mut StateWriter) {
+        writer.write_vec(&self.ram);
+        writer.write_bool(self.ir_mode);
+        writer.write_bool(self.infrared.led());
+        writer.write_u8(self.selected_rom_bank);
+        writer.write_u8(self.selected_ram_bank);
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), String> {
+        self.ram = reader.read_vec()?;
+        self.ir_mode = reader.read_bool()?;
+        self.infrared.set_led(reader.read_bool()?);
+        self.selected_rom_bank = reader.read_u8()?;
+        self.selected_ram_bank = reader.read_u8()?;
+        Ok(())
+    }
+}
+
+mod write {
+    use crate::gameboy::memory::MemoryRange;
+
+    pub const IR_SELECT: MemoryRange = MemoryRange {
+        begin: 0x0000,
+        end: 0x1FFF,
+        size: 0x2000,
+    };
+
+    pub const ROM_BANK_NUMBER: MemoryRange = MemoryRange {
+        begin: 0x2000,
+        end: 0x3FFF,
+        size: 0x2000,
+    };
+
+    pub const RAM_BANK_NUMBER: MemoryRange = MemoryRange {
+        begin: 0x4000,
+        end: 0x5FFF,
+        size: 0x2000,
+    };
+}
+
+#[test]
+fn huc1_test() {
+    use super::infrared::IrTransport;
+    use std::{cell::Cell, rc::Rc};
+
+    // both ends of the link see the same light
+    struct Loopback(Rc<Cell<bool>>);
+    impl IrTransport for Loopback {
+        fn send(&mut self, on: bool) {
+            self.0.set(on);
+        }
+
+        fn receive(&self) -> bool {
+            self.0.get()
+        }
+    }
+
+    let mut mbc = HuC1::new(super::numbered_rom(64), vec![0; 0x8000]);
+    mbc.write8_rom_bank_0(0x2000, 0x3F);
+    assert_eq!(mbc.read8_rom_bank_n(0x4000), 0x3F);
+    mbc.write8_rom_bank_0(0x2000, 0x00);
+    assert_eq!(mbc.read8_rom_bank_n(0x4000), 1);
+
+    mbc.write8_rom_bank_n(0x4000, 0x03);
+    mbc.write8_ram_bank_n(0xA000, 0x42);
+    assert_eq!(mbc.ram[0x6000], 0x42);
+
+    let light = Rc::new(Cell::new(false));
+    mbc.infrared_mut()
+        .unwrap()
+        .set_transport(Box::new(Loopback(light.clone())));
+    mbc.write8_rom_bank_0(0x0000, 0x0E);
+    assert_eq!(mbc.read8_ram_bank_n(0xA000), 0xC0);
+    mbc.write8_ram_bank_n(0xA000, 0x01);
+    assert!(light.get());
+    assert_eq!(mbc.read8_ram_bank_n(0xA000), 0xC1);
+    assert_eq!(mbc.ram[0x6000], 0x42);
+
+    mbc.write8_rom_bank_0(0x0000, 0x00);
+    assert_eq!(mbc.read8_ram_bank_n(0xA000), 0x42);
+}
@@ -0,0 +1,335 @@
+use crate::gameboy::savestate::{SaveState, StateReader, StateWriter};
+
+use super::{infrared::Infrared, ram_offset, rom_offset, rtc::Rtc, wrap_ram_bank, wrap_rom_bank, MBCInterface};
+
+/// Hudson HuC3 with up to 128 rom banks, 4 ram banks, an infrared LED and receiver and a clock chip.
+/// The first register selects what $A000-$BFFF maps, the clock is driven with commands of one nibble
+/// and an argument nibble written in command mode, answers are read in response mode.
+pub struct HuC3 {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    rtc: Rtc,
+    infrared: Infrared,
+
+    mode: u8,
+    selected_rom_bank: u8,
+    selected_ram_bank: u8,
+    // nibble address of the clock chip, 0-2 hold the minute of the day and 3-6 the day counter
+    address: u8,
+    memory: [u8; 0x100],
+    response: u8,
+    // argument of the last extended command, which reports the status and starts the tone generator
+    extended: u8,
+}
+
+impl HuC3 {
+    const MODE_RAM_READ: u8 = 0x00;
+    const MODE_RAM: u8 = 0x0A;
+    const MODE_COMMAND: u8 = 0x0B;
+    const MODE_RESPONSE: u8 = 0x0C;
+    const MODE_SEMAPHORE: u8 = 0x0D;
+    const MODE_IR: u8 = 0x0E;
+
+    const COMMAND_READ: u8 = 0x1;
+    const COMMAND_WRITE: u8 = 0x2;
+    const COMMAND_WRITE_INCREMENT: u8 = 0x3;
+    const COMMAND_ADDRESS_LOW: u8 = 0x4;
+    const COMMAND_ADDRESS_HIGH: u8 = 0x5;
+    const COMMAND_EXTENDED: u8 = 0x6;
+
+    const EXTENDED_STATUS: u8 = 0x2;
+    const EXTENDED_TONE: u8 = 0xE;
+
+    // nibbles of the minute of the day and of the day counter
+    const CLOCK_NIBBLES: u8 = 7;
+
+    fn clock_nibble(&mut self, address: u8) -> u8 {
+        let (minutes, days) = self.rtc.time();
+        if address < 3 {
+            (minutes >> (address * 4)) as u8 & 0x0F
+        } else {
+            (days >> ((address - 3) * 4)) as u8 & 0x0F
+        }
+    }
+
+    fn set_clock_nibble(&mut self, address: u8, value: u8) {
+        let (mut minutes, mut days) = self.rtc.time();
+        if address < 3 {
+            let shift = address * 4;
+            minutes = minutes & !(0x0F << shift) | (value as u16) << shift;
+        } else {
+            let shift = (address - 3) * 4;
+            days = days & !(0x0F << shift) | (value as u16) << shift;
+        }
+        self.rtc.set_time(minutes, days);
+    }
+
+    fn execute(&mut self, value: u8) {
+        let argument = value & 0x0F;
+        match (value >> 4) & 0x07 {
+            Self::COMMAND_READ => {
+                self.response = if self.address < Self::CLOCK_NIBBLES {
+                    self.clock_nibble(self.address)
+                } else {
+                    self.memory[self.address as usize]
+                };
+                self.address = self.address.wrapping_add(1);
+            }
+            command @ (Self::COMMAND_WRITE | Self::COMMAND_WRITE_INCREMENT) => {
+                if self.address < Self::CLOCK_NIBBLES {
+                    self.set_clock_nibble(self.address, argument);
+                } else {
+                    self.memory[self.address as usize] = argument;
+                }
+                if command == Self::COMMAND_WRITE_INCREMENT {
+                    self.address = self.address.wrapping_add(1);
+                }
+            }
+            Self::COMMAND_ADDRESS_LOW => self.address = self.address & 0xF0 | argument,
+            Self::COMMAND_ADDRESS_HIGH => self.address = self.address & 0x0F | argument << 4,
+            Self::COMMAND_EXTENDED => {
+                if (argument == Self::EXTENDED_TONE) != self.tone() {
+                    log::debug!("huc3 tone generator on: {}", argument == Self::EXTENDED_TONE);
+                }
+                self.extended = argument;
+            }
+            command => log::debug!("huc3 clock command {:#03X} not supported", command),
+        }
+    }
+
+    /// whether the tone generator is playing
+    pub fn tone(&self) -> bool {
+        self.extended == Self::EXTENDED_TONE
+    }
+}
+
+impl MBCInterface for HuC3 {
+    fn read8_rom_bank_0(&self, addr: u16) -> u8 {
+        self.rom[rom_offset(&self.rom, 0, addr)]
+    }
+
+    fn read8_rom_bank_n(&self, addr: u16) -> u8 {
+        self.rom[rom_offset(&self.rom, self.selected_rom_bank(), addr)]
+    }
+
+    fn read8_ram_bank_n(&self, addr: u16) -> u8 {
+        match self.mode {
+            Self::MODE_RAM_READ | Self::MODE_RAM if !self.ram.is_empty() => {
+                self.ram[ram_offset(&self.ram, self.selected_ram_bank(), addr)]
+            }
+            Self::MODE_RESPONSE if self.extended == Self::EXTENDED_STATUS => 0x01,
+            Self::MODE_RESPONSE => self.response,
+            // the clock is always done with the last command
+            Self::MODE_SEMAPHORE => 0x01,
+            Self::MODE_IR => self.infrared.read(),
+            _ => 0xFF,
+        }
+    }
+
+    fn write8_rom_bank_0(&mut self, addr: u16, value: u8) {
+        if addr >= write::MODE_SELECT.begin && addr <= write::MODE_SELECT.end {
+            self.mode = value & 0x0F;
+            log::debug!("huc3 mode {:#03X}", self.mode);
+        } else if addr >= write::ROM_BANK_NUMBER.begin && addr <= write::ROM_BANK_NUMBER.end {
+            self.selected_rom_bank = std::cmp::max(value & 0x7F, 1);
+        }
+    }
+
+    fn write8_rom_bank_n(&mut self, addr: u16, value: u8) {
+        if addr >= write::RAM_BANK_NUMBER.begin && addr <= write::RAM_BANK_NUMBER.end {
+            self.selected_ram_bank = value & 0x03;
+        }
+    }
+
+    fn write8_ram_bank_n(&mut self, addr: u16, value: u8) {
+        match self.mode {
+            Self::MODE_RAM if !self.ram.is_empty() => {
+                let offset = ram_offset(&self.ram, self.selected_ram_bank(), addr);
+                self.ram[offset] = value;
+            }
+            Self::MODE_COMMAND => self.execute(value),
+            Self::MODE_IR => self.infrared.write(value),
+            _ => {}
+        }
+    }
+
+    fn get_rom(&self) -> &Vec<u8> {
+        &self.rom
+    }
+
+    fn get_ram(&self) -> &Vec<u8> {
+        &self.ram
+    }
+
+    fn get_ram_mut(&mut self) -> &mut Vec<u8> {
+        &mut self.ram
+    }
+
+    fn selected_rom_bank(&self) -> usize {
+        wrap_rom_bank(&self.rom, self.selected_rom_bank as usize)
+    }
+
+    fn selected_ram_bank(&self) -> usize {
+        wrap_ram_bank(&self.ram, self.selected_ram_bank as usize)
+    }
+
+    fn tick(&mut self) {
+        self.rtc.tick();
+    }
+
+    fn rtc(&self) -> Option<&Rtc> {
+        Some(&self.rtc)
+    }
+
+    fn rtc_mut(&mut self) -> Option<&mut Rtc> {
+        Some(&mut self.rtc)
+    }
+
+    fn infrared_mut(&mut self) -> Option<&mut Infrared> {
+        Some(&mut self.infrared)
+    }
+
+    fn new(rom: Vec<u8>, ram: Vec<u8>) -> Self
+    where
+        Self: Sized,
+    {
+        Self {
+            rom,
+            ram,
+            rtc: Rtc::with_day_bits(16),
+            infrared: Infrared::new(),
+            mode: Self::MODE_RAM_READ,
+            selected_rom_bank: 1,
+            selected_ram_bank: 0,
+            address: 0,
+            memory: [0; 0x100],
+            response: 0,
+            extended: 0,
+        }
+    }
+}
+
+impl SaveState for HuC3 {
+    fn save_state(&self, writer: &mut StateWriter) {
+        writer.write_vec(&self.ram);
+        self.rtc.save_state(writer);
+        writer.write_bool(self.infrared.led());
+        writer.write_u8(self.mode);
+        writer.write_u8(self.selected_rom_bank);
+        writer.write_u8(self.selected_ram_bank);
+        writer.write_u8(self.address);
+        writer.write_bytes(&self.memory);
+        writer.write_u8(self.response);
+        writer.write_u8(self.extended);
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), String> {
+        self.ram = reader.read_vec()?;
+        self.rtc.load_state(reader)?;
+        self.infrared.set_led(reader.read_bool()?);
+        self.mode = reader.read_u8()?;
+        self.selected_rom_bank = reader.read_u8()?;
+        self.selected_ram_bank = reader.read_u8()?;
+        self.address = reader.read_u8()?;
+        reader.read_bytes(&mut self.memory)?;
+        self.response = reader.read_u8()?;
+        self.extended = reader.read_u8()?;
+        Ok(())
+    }
+}
+
+mod write {
+    use crate::gameboy::memory::MemoryRange;
+
+    pub const MODE_SELECT: MemoryRange = MemoryRange {
+        begin: 0x0000,
+        end: 0x1FFF,
+        size: 0x2000,
+    };
+
+    pub const ROM_BANK_NUMBER: MemoryRange = MemoryRange {
+        begin: 0x2000,
+        end: 0x3FFF,
+        size: 0x2000,
+    };
+
+    pub const RAM_BANK_NUMBER: MemoryRange = MemoryRange {
+        begin: 0x4000,
+        end: 0x5FFF,
+        size: 0x2000,
+    };
+}
+
+#[test]
+fn huc3_test() {
+    let mut mbc = HuC3::new(super::numbered_rom(128), vec![0; 0x8000]);
+    mbc.write8_rom_bank_0(0x2000, 0x7F);
+    assert_eq!(mbc.read8_rom_bank_n(0x4000), 0x7F);
+
+    // ram is read only until it is selected for writing
+    mbc.write8_rom_bank_n(0x4000, 0x01);
+    mbc.write8_ram_bank_n(0xA000, 0x42);
+    assert_eq!(mbc.read8_ram_bank_n(0xA000), 0x00);
+    mbc.write8_rom_bank_0(0x0000, 0x0A);
+    mbc.write8_ram_bank_n(0xA000, 0x42);
+    assert_eq!(mbc.ram[0x2000], 0x42);
+
+    // 1 day and 90 minutes, written nibble by nibble from address 0
+    mbc.write8_rom_bank_0(0x0000, 0x0B);
+    for command in [0x40, 0x50, 0x3A, 0x35, 0x30, 0x31, 0x30, 0x30, 0x30] {
+        mbc.write8_ram_bank_n(0xA000, command);
+    }
+    mbc.rtc.advance(60);
+    assert_eq!(mbc.rtc.time(), (91, 1));
+
+    // the day counter has all 16 bits of nibbles 3-6
+    let mut huc3 = HuC3::new(vec![0; 0x8000], Vec::new());
+    huc3.write8_rom_bank_0(0x0000, 0x0B);
+    for command in [0x43, 0x50, 0x34, 0x33, 0x32, 0x31] {
+        huc3.write8_ram_bank_n(0xA000, command);
+    }
+    assert_eq!(huc3.rtc.time(), (0, 0x1234));
+    huc3.write8_ram_bank_n(0xA000, 0x43);
+    let mut days = Vec::new();
+    for _ in 0..4 {
+        huc3.write8_rom_bank_0(0x0000, 0x0B);
+        huc3.write8_ram_bank_n(0xA000, 0x10);
+        huc3.write8_rom_bank_0(0x0000, 0x0C);
+        days.push(huc3.read8_ram_bank_n(0xA000));
+    }
+    assert_eq!(days, [0x04, 0x03, 0x02, 0x01]);
+    let mut restored = Rtc::with_day_bits(16);
+    restored.load_footer(&huc3.rtc.footer(0), 0).unwrap();
+    assert_eq!(restored.time(), (0, 0x1234));
+    mbc.write8_ram_bank_n(0xA000, 0x40);
+    let mut nibbles = Vec::new();
+    for _ in 0..4 {
+        mbc.write8_rom_bank_0(0x0000, 0x0B);
+        mbc.write8_ram_bank_n(0xA000, 0x10);
+        mbc.write8_rom_bank_0(0x0000, 0x0C);
+        nibbles.push(mbc.read8_ram_bank_n(0xA000));
+    }
+    assert_eq!(nibbles, [0x0B, 0x05, 0x00, 0x01]);
+
+    // nibbles past the clock keep what was written
+    mbc.write8_rom_bank_0(0x0000, 0x0B);
+    for command in [0x48, 0x55, 0x37, 0x48] {
+        mbc.write8_ram_bank_n(0xA000, command);
+    }
+    mbc.write8_ram_bank_n(0xA000, 0x10);
+    mbc.write8_rom_bank_0(0x0000, 0x0C);
+    assert_eq!(mbc.read8_ram_bank_n(0xA000), 0x07);
+
+    mbc.write8_rom_bank_0(0x0000, 0x0B);
+    mbc.write8_ram_bank_n(0xA000, 0x62);
+    mbc.write8_rom_bank_0(0x0000, 0x0C);
+    assert_eq!(mbc.read8_ram_bank_n(0xA000), 0x01);
+    mbc.write8_rom_bank_0(0x0000, 0x0B);
+    mbc.write8_ram_bank_n(0xA000, 0x6E);
+    assert!(mbc.tone());
+
+    mbc.write8_rom_bank_0(0x0000, 0x0E);
+    assert_eq!(mbc.read8_ram_bank_n(0xA000), 0xC0);
+    mbc.write8_ram_bank_n(0xA000, 0x01);
+    assert!(mbc.infrared.led());
+}
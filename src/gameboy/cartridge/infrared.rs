@@ -0,0 +1,193 @@
+use std::{
+    cell::Cell,
+    io,
+    net::{SocketAddr, UdpSocket},
+};
+
+/// Carries the light of the infrared LED of a cartridge to the receiver of another one
+pub trait IrTransport {
+    /// called when the LED is switched on or off
+    fn send(&mut self, on: bool);
+
+    /// whether the receiver sees light at the moment
+    fn receive(&self) -> bool;
+}
+
+/// nothing on the other side, the receiver stays dark
+pub struct NoIrTransport;
+
+impl IrTransport for NoIrTransport {
+    fn send(&mut self, _on: bool) {}
+
+    fn receive(&self) -> bool {
+        false
+    }
+}
+
+/// Sends every change of the LED as a single byte datagram to another emulator instance,
+/// the receiver shows the last state that arrived from there.
+pub struct UdpIrTransport {
+    socket: UdpSocket,
+    remote_led: Cell<bool>,
+}
+
+impl UdpIrTransport {
+    pub fn new(local: SocketAddr, peer: SocketAddr) -> io::Result<Self> {
+        let socket = UdpSocket::bind(local)?;
+        socket.connect(peer)?;
+        socket.set_nonblocking(true)?;
+        Ok(Self {
+            socket,
+            remote_led: Cell::new(false),
+        })
+    }
+}
+
+impl IrTransport for UdpIrTransport {
+    fn send(&mut self, on: bool) {
+        // a refused earlier datagram, because the peer was not started yet, is reported by the next send
+        let mut result = self.socket.send(&[on as u8]);
+        if matches!(&result, Err(err) if err.kind() == io::ErrorKind::ConnectionRefused) {
+            result = self.socket.send(&[on as u8]);
+        }
+        // nobody listening on the other side is not an error, the light just goes nowhere
+        if let Err(err) = result {
+            log::trace!("failed to send ir signal: {}", err);
+        }
+    }
+
+    fn receive(&self) -> bool {
+        let mut buffer = [0; 1];
+        loop {
+            match self.socket.recv(&mut buffer) {
+                Ok(1) => self.remote_led.set(buffer[0] != 0),
+                Ok(_) => {}
+                Err(err) if err.kind() == io::ErrorKind::ConnectionRefused => {}
+                Err(_) => break,
+            }
+        }
+        self.remote_led.get()
+    }
+}
+
+/// IR LED and receiver of HuC1 and HuC3 cartridges
+pub struct Infrared {
+    transport: Box<dyn IrTransport>,
+    led: bool,
+}
+
+impl Infrared {
+    pub fn new() -> Self {
+        Self {
+            transport: Box::new(NoIrTransport),
+            led: false,
+        }
+    }
+
+    pub fn set_transport(&mut self, transport: Box<dyn IrTransport>) {
+        self.transport = transport;
+        self.transport.send(self.led);
+    }
+
+    pub fn led(&self) -> bool {
+        self.led
+    }
+
+    pub fn set_led(&mut self, on: bool) {
+        if on != self.led {
+            self.led = on;
+            self.transport.send(on);
+        }
+    }
+
+    /// $C1 while light reaches the receiver, $C0 otherwise
+    pub fn read(&self) -> u8 {
+        0xC0 | self.transport.receive() as u8
+    }
+
+    /// bit 0 switches the LED
+    pub fn write(&mut self, value: u8) {
+        self.set_led(value & 0x01 != 0);
+    }
+}
+
+impl Default for Infrared {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[test]
+fn infrared_test() {
+    use std::{cell::RefCell, rc::Rc};
+
+    // remembers what was sent and lets the test decide what the receiver sees
+    struct Recorder {
+        sent: Rc<RefCell<Vec<bool>>>,
+        light: Rc<Cell<bool>>,
+    }
+    impl IrTransport for Recorder {
+        fn send(&mut self, on: bool) {
+            self.sent.borrow_mut().push(on);
+        }
+
+        fn receive(&self) -> bool {
+            self.light.get()
+        }
+    }
+
+    let sent = Rc::new(RefCell::new(Vec::new()));
+    let light = Rc::new(Cell::new(false));
+    let mut infrared = Infrared::new();
+    infrared.write(0x01);
+    // a new transport gets the current state of the LED
+    infrared.set_transport(Box::new(Recorder {
+        sent: sent.clone(),
+        light: light.clone(),
+    }));
+    assert_eq!(*sent.borrow(), [true]);
+
+    // only changes are sent, bits above bit 0 are ignored
+    infrared.write(0xFF);
+    infrared.write(0xFE);
+    infrared.write(0x00);
+    assert_eq!(*sent.borrow(), [true, false]);
+    assert!(!infrared.led());
+
+    assert_eq!(infrared.read(), 0xC0);
+    light.set(true);
+    assert_eq!(infrared.read(), 0xC1);
+}
+
+// binds real sockets on localhost and waits for the datagrams, run with `cargo test -- --ignored`
+#[test]
+#[ignore]
+fn udp_ir_transport_test() {
+    let any_port: SocketAddr = "127.0.0.1:0".parse().unwrap();
+    let probe_a = UdpSocket::bind(any_port).unwrap();
+    let probe_b = UdpSocket::bind(any_port).unwrap();
+    let (addr_a, addr_b) = (probe_a.local_addr().unwrap(), probe_b.local_addr().unwrap());
+    drop((probe_a, probe_b));
+
+    let mut a = Infrared::new();
+    a.set_transport(Box::new(UdpIrTransport::new(addr_a, addr_b).unwrap()));
+    let mut b = Infrared::new();
+    b.set_transport(Box::new(UdpIrTransport::new(addr_b, addr_a).unwrap()));
+    assert_eq!(b.read(), 0xC0);
+
+    let wait_for = |infrared: &Infrared, value: u8| {
+        for _ in 0..100 {
+            if infrared.read() == value {
+                return true;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        false
+    };
+    a.write(0x01);
+    assert!(wait_for(&b, 0xC1));
+    a.write(0x00);
+    assert!(wait_for(&b, 0xC0));
+    b.write(0x01);
+    assert!(wait_for(&a, 0xC1));
+}
@@ -1,4 +1,7 @@
-
+use crate::gameboy::{
+    memory,
+    savestate::{SaveState, StateReader, StateWriter},
+};
 
 pub struct MBC0 {
     rom: Vec<u8>,
@@ -18,7 +21,10 @@ impl super::MBCInterface for MBC0 {
 
     fn read8_ram_bank_n(&self, addr: u16) -> u8 {
         log::trace!("mbc0 ram read");
-        self.ram[addr as usize]
+        if self.ram.is_empty() {
+            return 0xFF;
+        }
+        self.ram[(addr - memory::cartridge::EXTERNAL_RAM.begin) as usize % self.ram.len()]
     }
 
     fn write8_rom_bank_0(self: &mut MBC0, addr: u16, value: u8) {
@@ -31,13 +37,33 @@ impl super::MBCInterface for MBC0 {
 
     fn write8_ram_bank_n(self: &mut MBC0, addr: u16, value: u8) {
         log::trace!("mbc0 ram write");
-        self.ram[addr as usize] = value;
+        if self.ram.is_empty() {
+            return;
+        }
+        let offset = (addr - memory::cartridge::EXTERNAL_RAM.begin) as usize % self.ram.len();
+        self.ram[offset] = value;
     }
 
     fn get_rom(&self) -> &Vec<u8> {
         &self.rom
     }
 
+    fn get_ram(&self) -> &Vec<u8> {
+        &self.ram
+    }
+
+    fn get_ram_mut(&mut self) -> &mut Vec<u8> {
+        &mut self.ram
+    }
+
+    fn selected_rom_bank(&self) -> usize {
+        1
+    }
+
+    fn selected_ram_bank(&self) -> usize {
+        0
+    }
+
     fn new(rom: Vec<u8>, ram: Vec<u8>) -> Self
     where
         Self: Sized,
@@ -45,3 +71,14 @@ impl super::MBCInterface for MBC0 {
         Self { rom, ram }
     }
 }
+
+impl SaveState for MBC0 {
+    fn save_state(&self, writer: &mut StateWriter) {
+        writer.write_vec(&self.ram);
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), String> {
+        self.ram = reader.read_vec()?;
+        Ok(())
+    }
+}
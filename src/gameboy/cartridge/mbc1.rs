@@ -1,86 +1,157 @@
-use crate::gameboy::memory;
+use crate::gameboy::savestate::{SaveState, StateReader, StateWriter};
 
+use super::{ram_offset, rom_offset, wrap_ram_bank, wrap_rom_bank, MBCInterface};
+
+/// MBC1 with up to 2 MiB rom and 32 KiB ram. The 2 bit register at $4000-$5FFF holds the upper bits of the rom bank,
+/// in banking mode 1 it also selects the ram bank and the bank mapped into $0000-$3FFF.
+/// Multicarts (MBC1M) wire only 4 bits of the lower rom bank register, so the 2 bit register selects one of four 256 KiB games.
 pub struct MBC1 {
     rom: Vec<u8>,
     ram: Vec<u8>,
 
     banking_mode: u8,
     ram_enable: bool,
-    selected_rom_bank: u16,
-    selected_ram_bank: u8,
+    // 5 bit register at $2000-$3FFF, never 0
+    rom_bank_low: u8,
+    // 2 bit register at $4000-$5FFF
+    bank_high: u8,
+    multicart: bool,
+}
+
+impl MBC1 {
+    /// multicarts are 1 MiB roms that hold another game with its own nintendo logo at bank $10
+    fn is_multicart(rom: &[u8]) -> bool {
+        const LOGO: std::ops::Range<usize> = 0x0104..0x0134;
+        const GAME_SIZE: usize = 0x40000;
+        rom.len() == 0x100000 && rom[LOGO] == rom[GAME_SIZE + LOGO.start..GAME_SIZE + LOGO.end]
+    }
+
+    fn bank_high_shift(&self) -> u8 {
+        if self.multicart {
+            4
+        } else {
+            5
+        }
+    }
 }
 
-impl super::MBCInterface for MBC1 {
+impl MBCInterface for MBC1 {
     fn read8_rom_bank_0(&self, addr: u16) -> u8 {
-        self.rom[addr as usize]
+        self.rom[rom_offset(&self.rom, self.selected_rom_bank_0(), addr)]
     }
 
     fn read8_rom_bank_n(&self, addr: u16) -> u8 {
-        self.rom[(addr - memory::cartridge::ROM_BANK_N.begin) as usize
-            + (memory::cartridge::ROM_BANK_N.size * self.selected_rom_bank as usize)]
+        self.rom[rom_offset(&self.rom, self.selected_rom_bank(), addr)]
     }
 
     fn read8_ram_bank_n(&self, addr: u16) -> u8 {
-        self.ram[(addr - memory::cartridge::EXTERNAL_RAM.begin) as usize
-            + (memory::cartridge::EXTERNAL_RAM.size * self.selected_ram_bank as usize)]
+        if !self.ram_enable || self.ram.is_empty() {
+            return 0xFF;
+        }
+        self.ram[ram_offset(&self.ram, self.selected_ram_bank(), addr)]
     }
 
     fn write8_rom_bank_0(&mut self, addr: u16, value: u8) {
         if addr >= write::RAM_ENABLE.begin && addr <= write::RAM_ENABLE.end {
-            if value == 0x0A {
-                self.ram_enable = true;
-                log::debug!("ram in mbc1 enabled");
-            } else {
-                self.ram_enable = false;
-                log::debug!("ram in mbc1 disabled");
-            }
+            self.ram_enable = value & 0x0F == 0x0A;
+            log::debug!("ram in mbc1 enabled: {}", self.ram_enable);
         } else if addr >= write::ROM_BANK_NUMBER.begin && addr <= write::ROM_BANK_NUMBER.end {
-            self.selected_rom_bank = (value & 0x1F) as u16;
-            if self.selected_rom_bank == 0 {
-                self.selected_rom_bank = 1;
-            }
-            if self.banking_mode != 0 {
-                panic!("advanced banking mode not supported");
-            }
+            // bank 0 is turned into 1 before the unwired bits of multicarts are dropped
+            self.rom_bank_low = std::cmp::max(value & 0x1F, 1);
         }
     }
 
     fn write8_rom_bank_n(&mut self, addr: u16, value: u8) {
         if addr >= write::RAM_BANK_NUMBER.begin && addr <= write::RAM_BANK_NUMBER.end {
-            self.selected_ram_bank = std::cmp::min((self.ram.len() / 0x2000 - 1) as u8, value & 0x11);
-            log::debug!("select ram bank: {}", self.selected_ram_bank);
+            self.bank_high = value & 0x03;
+            log::debug!("select upper bank bits: {}", self.bank_high);
         } else if addr >= write::BANKING_MODE_SELECT.begin && addr <= write::BANKING_MODE_SELECT.end {
-            self.banking_mode = value;
-            if self.banking_mode != 0 {
-                log::error!("advanced banking mode not supported");
-            }
+            self.banking_mode = value & 0x01;
         }
     }
 
     fn write8_ram_bank_n(&mut self, addr: u16, value: u8) {
-        self.ram[(addr - memory::cartridge::EXTERNAL_RAM.begin) as usize
-            + (memory::cartridge::EXTERNAL_RAM.size * self.selected_ram_bank as usize)] = value;
+        if !self.ram_enable || self.ram.is_empty() {
+            return;
+        }
+        let offset = ram_offset(&self.ram, self.selected_ram_bank(), addr);
+        self.ram[offset] = value;
     }
 
     fn get_rom(&self) -> &Vec<u8> {
         &self.rom
     }
 
+    fn get_ram(&self) -> &Vec<u8> {
+        &self.ram
+    }
+
+    fn get_ram_mut(&mut self) -> &mut Vec<u8> {
+        &mut self.ram
+    }
+
+    fn selected_rom_bank_0(&self) -> usize {
+        if self.banking_mode == 0 {
+            return 0;
+        }
+        wrap_rom_bank(&self.rom, (self.bank_high << self.bank_high_shift()) as usize)
+    }
+
+    fn selected_rom_bank(&self) -> usize {
+        let low = if self.multicart {
+            self.rom_bank_low & 0x0F
+        } else {
+            self.rom_bank_low
+        };
+        wrap_rom_bank(&self.rom, (self.bank_high << self.bank_high_shift() | low) as usize)
+    }
+
+    fn selected_ram_bank(&self) -> usize {
+        if self.banking_mode == 0 {
+            return 0;
+        }
+        wrap_ram_bank(&self.ram, self.bank_high as usize)
+    }
+
     fn new(rom: Vec<u8>, ram: Vec<u8>) -> Self
     where
         Self: Sized,
     {
+        let multicart = Self::is_multicart(&rom);
+        if multicart {
+            log::info!("mbc1 multicart detected");
+        }
         Self {
             rom,
             ram,
             banking_mode: 0,
             ram_enable: false,
-            selected_rom_bank: 1,
-            selected_ram_bank: 0,
+            rom_bank_low: 1,
+            bank_high: 0,
+            multicart,
         }
     }
 }
 
+impl SaveState for MBC1 {
+    fn save_state(&self, writer: &mut StateWriter) {
+        writer.write_vec(&self.ram);
+        writer.write_u8(self.banking_mode);
+        writer.write_bool(self.ram_enable);
+        writer.write_u8(self.rom_bank_low);
+        writer.write_u8(self.bank_high);
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), String> {
+        self.ram = reader.read_vec()?;
+        self.banking_mode = reader.read_u8()?;
+        self.ram_enable = reader.read_bool()?;
+        self.rom_bank_low = reader.read_u8()?;
+        self.bank_high = reader.read_u8()?;
+        Ok(())
+    }
+}
+
 mod read {
     use crate::gameboy::memory::MemoryRange;
 
@@ -130,3 +201,101 @@ mod write {
         size: 0x2000,
     };
 }
+
+#[test]
+fn mbc1_test() {
+    use super::numbered_rom;
+
+    let mut mbc = MBC1::new(numbered_rom(128), vec![0; 0x8000]);
+    assert!(!mbc.multicart);
+    assert_eq!(mbc.read8_rom_bank_n(0x4000), 1);
+    mbc.write8_rom_bank_0(0x2000, 0x00);
+    assert_eq!(mbc.read8_rom_bank_n(0x4000), 1);
+    mbc.write8_rom_bank_0(0x2000, 0x1F);
+    mbc.write8_rom_bank_n(0x4000, 0x03);
+    assert_eq!(mbc.read8_rom_bank_n(0x4000), 0x7F);
+    assert_eq!(mbc.read8_rom_bank_0(0x0000), 0);
+    // mode 1 maps the upper bits into $0000-$3FFF and selects the ram bank
+    mbc.write8_rom_bank_n(0x6000, 0x01);
+    assert_eq!(mbc.read8_rom_bank_0(0x0000), 0x60);
+    assert_eq!(mbc.selected_ram_bank(), 3);
+
+    // ram only responds while enabled
+    mbc.write8_ram_bank_n(0xA000, 0x42);
+    assert_eq!(mbc.read8_ram_bank_n(0xA000), 0xFF);
+    mbc.write8_rom_bank_0(0x0000, 0x0A);
+    mbc.write8_ram_bank_n(0xA000, 0x42);
+    assert_eq!(mbc.read8_ram_bank_n(0xA000), 0x42);
+    assert_eq!(mbc.ram[0x6000], 0x42);
+    mbc.write8_rom_bank_n(0x6000, 0x00);
+    assert_eq!(mbc.read8_ram_bank_n(0xA000), 0x00);
+
+    // cheats write into a bank without touching the banking registers
+    mbc.poke_ram(2, 0xA001, 0x99);
+    assert_eq!(mbc.ram[0x4001], 0x99);
+    assert_eq!(
+        (mbc.bank_high, mbc.banking_mode, mbc.read8_rom_bank_n(0x4000)),
+        (3, 0, 0x7F)
+    );
+
+    // without ram the bus stays open even while ram is enabled
+    let mut mbc = MBC1::new(numbered_rom(8), Vec::new());
+    mbc.write8_rom_bank_0(0x0000, 0x0A);
+    mbc.write8_ram_bank_n(0xA000, 0x42);
+    assert_eq!(mbc.read8_ram_bank_n(0xA000), 0xFF);
+
+    // banks above the rom size wrap around
+    let mut mbc = MBC1::new(numbered_rom(8), vec![0; 0x2000]);
+    mbc.write8_rom_bank_0(0x2000, 0x09);
+    assert_eq!(mbc.read8_rom_bank_n(0x4000), 1);
+
+    // multicarts use 4 bits of the lower register
+    let mut rom = numbered_rom(64);
+    for game in 0..4 {
+        rom[game * 0x40000 + 0x104..game * 0x40000 + 0x134].fill(0xCE);
+    }
+    let mut mbc = MBC1::new(rom, vec![0; 0x2000]);
+    assert!(mbc.multicart);
+    mbc.write8_rom_bank_n(0x4000, 0x02);
+    mbc.write8_rom_bank_0(0x2000, 0x13);
+    assert_eq!(mbc.read8_rom_bank_n(0x4000), 0x23);
+    mbc.write8_rom_bank_0(0x2000, 0x10);
+    assert_eq!(mbc.read8_rom_bank_n(0x4000), 0x20);
+    mbc.write8_rom_bank_n(0x6000, 0x01);
+    assert_eq!(mbc.read8_rom_bank_0(0x0000), 0x20);
+}
+
+/// Runs the `emulator-only/mbc1` roms of the mooneye test suite, which are not part of the repository:
+/// `MOONEYE_MBC1_DIR=<dir> cargo test mooneye_mbc1 -- --ignored`
+#[test]
+#[ignore]
+fn mooneye_mbc1_test() {
+    use crate::gameboy::Gameboy;
+
+    let dir = std::env::var("MOONEYE_MBC1_DIR").expect("MOONEYE_MBC1_DIR is not set");
+    let mut roms: Vec<_> = std::fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "gb"))
+        .collect();
+    roms.sort();
+    assert!(!roms.is_empty());
+    let mut failed = Vec::new();
+    for path in roms {
+        let mut gb = Gameboy::headless("mooneye_mbc1", &std::fs::read(&path).unwrap());
+        // the tests end with ld b,b and the fibonacci numbers in the registers if they passed
+        let mut passed = false;
+        for _ in 0..10_000_000 {
+            unsafe { gb.run_instructions(1) };
+            if gb.read8_unlocked(gb.cpu.pc) == 0x40 {
+                let cpu = &gb.cpu;
+                passed = [cpu.b, cpu.c, cpu.d, cpu.e, cpu.h, cpu.l] == [3, 5, 8, 13, 21, 34];
+                break;
+            }
+        }
+        if !passed {
+            failed.push(path.display().to_string());
+        }
+    }
+    assert!(failed.is_empty(), "failed: {:?}", failed);
+}
@@ -0,0 +1,142 @@
+use crate::gameboy::{
+    memory,
+    savestate::{SaveState, StateReader, StateWriter},
+};
+
+use super::{rom_offset, wrap_rom_bank, MBCInterface};
+
+/// MBC2 with up to 16 rom banks and 512 half bytes of ram built into the mapper.
+/// Bit 8 of the address decides whether a write to $0000-$3FFF enables ram or selects the rom bank.
+pub struct MBC2 {
+    rom: Vec<u8>,
+    // only the lower nibble of each byte is stored
+    ram: Vec<u8>,
+
+    ram_enable: bool,
+    selected_rom_bank: u8,
+}
+
+impl MBC2 {
+    pub const RAM_SIZE: usize = 0x200;
+
+    /// the 512 half bytes are echoed through all of $A000-$BFFF
+    fn ram_offset(addr: u16) -> usize {
+        (addr - memory::cartridge::EXTERNAL_RAM.begin) as usize % Self::RAM_SIZE
+    }
+}
+
+impl MBCInterface for MBC2 {
+    fn read8_rom_bank_0(&self, addr: u16) -> u8 {
+        self.rom[rom_offset(&self.rom, 0, addr)]
+    }
+
+    fn read8_rom_bank_n(&self, addr: u16) -> u8 {
+        self.rom[rom_offset(&self.rom, self.selected_rom_bank(), addr)]
+    }
+
+    fn read8_ram_bank_n(&self, addr: u16) -> u8 {
+        if !self.ram_enable {
+            return 0xFF;
+        }
+        // the upper nibble is not connected and reads as 1s
+        0xF0 | self.ram[Self::ram_offset(addr)]
+    }
+
+    fn write8_rom_bank_0(&mut self, addr: u16, value: u8) {
+        if addr & write::REGISTER_SELECT_BIT == 0 {
+            self.ram_enable = value & 0x0F == 0x0A;
+            log::debug!("ram in mbc2 enabled: {}", self.ram_enable);
+        } else {
+            self.selected_rom_bank = std::cmp::max(value & 0x0F, 1);
+        }
+    }
+
+    fn write8_rom_bank_n(&mut self, addr: u16, value: u8) {
+        log::debug!("mbc2 has no register at {:#06X}, value {:#04X}", addr, value);
+    }
+
+    fn write8_ram_bank_n(&mut self, addr: u16, value: u8) {
+        if self.ram_enable {
+            self.ram[Self::ram_offset(addr)] = value & 0x0F;
+        }
+    }
+
+    fn get_rom(&self) -> &Vec<u8> {
+        &self.rom
+    }
+
+    fn get_ram(&self) -> &Vec<u8> {
+        &self.ram
+    }
+
+    fn get_ram_mut(&mut self) -> &mut Vec<u8> {
+        &mut self.ram
+    }
+
+    fn poke_ram(&mut self, _bank: usize, addr: u16, value: u8) {
+        self.ram[Self::ram_offset(addr)] = value & 0x0F;
+    }
+
+    fn selected_rom_bank(&self) -> usize {
+        wrap_rom_bank(&self.rom, self.selected_rom_bank as usize)
+    }
+
+    fn selected_ram_bank(&self) -> usize {
+        0
+    }
+
+    fn new(rom: Vec<u8>, mut ram: Vec<u8>) -> Self
+    where
+        Self: Sized,
+    {
+        ram.resize(Self::RAM_SIZE, 0);
+        Self {
+            rom,
+            ram,
+            ram_enable: false,
+            selected_rom_bank: 1,
+        }
+    }
+}
+
+impl SaveState for MBC2 {
+    fn save_state(&self, writer: &mut StateWriter) {
+        writer.write_vec(&self.ram);
+        writer.write_bool(self.ram_enable);
+        writer.write_u8(self.selected_rom_bank);
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), String> {
+        self.ram = reader.read_vec()?;
+        self.ram_enable = reader.read_bool()?;
+        self.selected_rom_bank = reader.read_u8()?;
+        Ok(())
+    }
+}
+
+mod write {
+    /// writes to $0000-$3FFF with this address bit set select the rom bank, without it they enable ram
+    pub const REGISTER_SELECT_BIT: u16 = 0x0100;
+}
+
+#[test]
+fn mbc2_test() {
+    let mut mbc = MBC2::new(super::numbered_rom(16), Vec::new());
+    assert_eq!(mbc.read8_rom_bank_n(0x4000), 1);
+    mbc.write8_rom_bank_0(0x2100, 0x0F);
+    assert_eq!(mbc.read8_rom_bank_n(0x4000), 0x0F);
+    mbc.write8_rom_bank_0(0x0100, 0x10);
+    assert_eq!(mbc.read8_rom_bank_n(0x4000), 1);
+    // without bit 8 the same region enables ram
+    mbc.write8_rom_bank_0(0x2000, 0x05);
+    assert_eq!(mbc.read8_rom_bank_n(0x4000), 1);
+
+    assert_eq!(mbc.read8_ram_bank_n(0xA000), 0xFF);
+    mbc.write8_rom_bank_0(0x0000, 0x0A);
+    mbc.write8_ram_bank_n(0xA001, 0x5C);
+    assert_eq!(mbc.read8_ram_bank_n(0xA001), 0xFC);
+    assert_eq!(mbc.read8_ram_bank_n(0xA201), 0xFC);
+    assert_eq!(mbc.read8_ram_bank_n(0xBE01), 0xFC);
+    mbc.write8_rom_bank_0(0x3E00, 0x00);
+    assert_eq!(mbc.read8_ram_bank_n(0xA001), 0xFF);
+}
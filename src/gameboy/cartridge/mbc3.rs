@@ -0,0 +1,227 @@
+use crate::gameboy::savestate::{SaveState, StateReader, StateWriter};
+
+use super::{ram_offset, rom_offset, rtc::Rtc, wrap_ram_bank, wrap_rom_bank, MBCInterface};
+
+/// MBC3 with up to 128 rom banks, 4 ram banks and on timer cartridges a real time clock whose registers are mapped
+/// into $A000-$BFFF instead of a ram bank.
+pub struct MBC3 {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    rtc: Option<Rtc>,
+
+    ram_enable: bool,
+    selected_rom_bank: u8,
+    // ram bank 0-3 or rtc register $08-$0C
+    selected_ram_bank: u8,
+}
+
+impl MBC3 {
+    /// the clock is only there on the timer cartridge types, see `CartridgeType::has_timer`
+    pub fn with_rtc(rom: Vec<u8>, ram: Vec<u8>, has_rtc: bool) -> Self {
+        Self {
+            rtc: has_rtc.then(Rtc::new),
+            ..Self::new(rom, ram)
+        }
+    }
+
+    fn rtc_selected(&self) -> bool {
+        self.selected_ram_bank >= Rtc::SECONDS
+    }
+}
+
+impl MBCInterface for MBC3 {
+    fn read8_rom_bank_0(&self, addr: u16) -> u8 {
+        self.rom[rom_offset(&self.rom, 0, addr)]
+    }
+
+    fn read8_rom_bank_n(&self, addr: u16) -> u8 {
+        self.rom[rom_offset(&self.rom, self.selected_rom_bank(), addr)]
+    }
+
+    fn read8_ram_bank_n(&self, addr: u16) -> u8 {
+        if !self.ram_enable {
+            0xFF
+        } else if self.rtc_selected() {
+            // open bus without a clock
+            self.rtc.as_ref().map_or(0xFF, |rtc| rtc.read(self.selected_ram_bank))
+        } else if self.ram.is_empty() {
+            0xFF
+        } else {
+            self.ram[ram_offset(&self.ram, self.selected_ram_bank(), addr)]
+        }
+    }
+
+    fn write8_rom_bank_0(&mut self, addr: u16, value: u8) {
+        if addr >= write::RAM_TIMER_ENABLE.begin && addr <= write::RAM_TIMER_ENABLE.end {
+            self.ram_enable = value & 0x0F == 0x0A;
+            log::debug!("ram and timer in mbc3 enabled: {}", self.ram_enable);
+        } else if addr >= write::ROM_BANK_NUMBER.begin && addr <= write::ROM_BANK_NUMBER.end {
+            self.selected_rom_bank = std::cmp::max(value & 0x7F, 1);
+        }
+    }
+
+    fn write8_rom_bank_n(&mut self, addr: u16, value: u8) {
+        if addr >= write::RAM_BANK_RTC_SELECT.begin && addr <= write::RAM_BANK_RTC_SELECT.end {
+            match value {
+                0x00..=0x03 | Rtc::SECONDS..=Rtc::DAY_HIGH => self.selected_ram_bank = value,
+                _ => log::debug!("mbc3 has no ram bank or rtc register {:#04X}", value),
+            }
+        } else if addr >= write::LATCH_CLOCK_DATA.begin && addr <= write::LATCH_CLOCK_DATA.end {
+            if let Some(rtc) = self.rtc.as_mut() {
+                rtc.write_latch(value);
+            }
+        }
+    }
+
+    fn write8_ram_bank_n(&mut self, addr: u16, value: u8) {
+        if !self.ram_enable {
+            return;
+        }
+        if self.rtc_selected() {
+            if let Some(rtc) = self.rtc.as_mut() {
+                rtc.write(self.selected_ram_bank, value);
+            }
+        } else if !self.ram.is_empty() {
+            let offset = ram_offset(&self.ram, self.selected_ram_bank(), addr);
+            self.ram[offset] = value;
+        }
+    }
+
+    fn get_rom(&self) -> &Vec<u8> {
+        &self.rom
+    }
+
+    fn get_ram(&self) -> &Vec<u8> {
+        &self.ram
+    }
+
+    fn get_ram_mut(&mut self) -> &mut Vec<u8> {
+        &mut self.ram
+    }
+
+    fn selected_rom_bank(&self) -> usize {
+        wrap_rom_bank(&self.rom, self.selected_rom_bank as usize)
+    }
+
+    fn selected_ram_bank(&self) -> usize {
+        if self.rtc_selected() {
+            return 0;
+        }
+        wrap_ram_bank(&self.ram, self.selected_ram_bank as usize)
+    }
+
+    fn tick(&mut self) {
+        if let Some(rtc) = self.rtc.as_mut() {
+            rtc.tick();
+        }
+    }
+
+    fn rtc(&self) -> Option<&Rtc> {
+        self.rtc.as_ref()
+    }
+
+    fn rtc_mut(&mut self) -> Option<&mut Rtc> {
+        self.rtc.as_mut()
+    }
+
+    fn new(rom: Vec<u8>, ram: Vec<u8>) -> Self
+    where
+        Self: Sized,
+    {
+        Self {
+            rom,
+            ram,
+            rtc: None,
+            ram_enable: false,
+            selected_rom_bank: 1,
+            selected_ram_bank: 0,
+        }
+    }
+}
+
+impl SaveState for MBC3 {
+    fn save_state(&self, writer: &mut StateWriter) {
+        writer.write_vec(&self.ram);
+        if let Some(rtc) = self.rtc.as_ref() {
+            rtc.save_state(writer);
+        }
+        writer.write_bool(self.ram_enable);
+        writer.write_u8(self.selected_rom_bank);
+        writer.write_u8(self.selected_ram_bank);
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), String> {
+        self.ram = reader.read_vec()?;
+        if let Some(rtc) = self.rtc.as_mut() {
+            rtc.load_state(reader)?;
+        }
+        self.ram_enable = reader.read_bool()?;
+        self.selected_rom_bank = reader.read_u8()?;
+        self.selected_ram_bank = reader.read_u8()?;
+        Ok(())
+    }
+}
+
+mod write {
+    use crate::gameboy::memory::MemoryRange;
+
+    pub const RAM_TIMER_ENABLE: MemoryRange = MemoryRange {
+        begin: 0x0000,
+        end: 0x1FFF,
+        size: 0x2000,
+    };
+
+    pub const ROM_BANK_NUMBER: MemoryRange = MemoryRange {
+        begin: 0x2000,
+        end: 0x3FFF,
+        size: 0x2000,
+    };
+
+    pub const RAM_BANK_RTC_SELECT: MemoryRange = MemoryRange {
+        begin: 0x4000,
+        end: 0x5FFF,
+        size: 0x2000,
+    };
+
+    pub const LATCH_CLOCK_DATA: MemoryRange = MemoryRange {
+        begin: 0x6000,
+        end: 0x7FFF,
+        size: 0x2000,
+    };
+}
+
+#[test]
+fn mbc3_test() {
+    let rom = super::numbered_rom(128);
+    let mut mbc = MBC3::with_rtc(rom.clone(), vec![0; 0x8000], true);
+    mbc.write8_rom_bank_0(0x2000, 0x45);
+    assert_eq!(mbc.read8_rom_bank_n(0x4000), 0x45);
+    mbc.write8_rom_bank_0(0x2000, 0x80);
+    assert_eq!(mbc.read8_rom_bank_n(0x4000), 1);
+
+    mbc.write8_rom_bank_0(0x0000, 0x0A);
+    mbc.write8_rom_bank_n(0x4000, 0x02);
+    mbc.write8_ram_bank_n(0xA000, 0x42);
+    assert_eq!(mbc.ram[0x4000], 0x42);
+
+    mbc.write8_rom_bank_n(0x4000, Rtc::MINUTES);
+    mbc.write8_ram_bank_n(0xA000, 30);
+    mbc.write8_rom_bank_n(0x6000, 0x00);
+    mbc.write8_rom_bank_n(0x6000, 0x01);
+    assert_eq!(mbc.read8_ram_bank_n(0xA000), 30);
+    mbc.write8_rom_bank_n(0x4000, 0x02);
+    assert_eq!(mbc.read8_ram_bank_n(0xA000), 0x42);
+
+    mbc.write8_rom_bank_0(0x0000, 0x00);
+    assert_eq!(mbc.read8_ram_bank_n(0xA000), 0xFF);
+
+    // without a timer the clock registers are open bus
+    let mut mbc = MBC3::new(rom, vec![0; 0x8000]);
+    assert!(mbc.rtc().is_none());
+    mbc.write8_rom_bank_0(0x0000, 0x0A);
+    mbc.write8_rom_bank_n(0x4000, Rtc::MINUTES);
+    mbc.write8_ram_bank_n(0xA000, 30);
+    mbc.write8_rom_bank_n(0x6000, 0x00);
+    mbc.write8_rom_bank_n(0x6000, 0x01);
+    assert_eq!(mbc.read8_ram_bank_n(0xA000), 0xFF);
+}
@@ -0,0 +1,184 @@
+use crate::gameboy::savestate::{SaveState, StateReader, StateWriter};
+
+use super::{ram_offset, rom_offset, wrap_ram_bank, wrap_rom_bank, MBCInterface};
+
+/// MBC5 with a 9 bit rom bank number, where bank 0 can be mapped into $4000-$7FFF as well, and 16 ram banks.
+/// On rumble cartridges bit 3 of the ram bank register drives the motor instead of selecting a bank.
+pub struct MBC5 {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    has_rumble: bool,
+
+    ram_enable: bool,
+    selected_rom_bank: u16,
+    selected_ram_bank: u8,
+    rumble: bool,
+}
+
+impl MBC5 {
+    const RUMBLE_BIT: u8 = 0x08;
+
+    /// the motor is only there on the rumble cartridge types, see `CartridgeType::has_rumble`
+    pub fn with_rumble(rom: Vec<u8>, ram: Vec<u8>, has_rumble: bool) -> Self {
+        Self {
+            has_rumble,
+            ..Self::new(rom, ram)
+        }
+    }
+}
+
+impl MBCInterface for MBC5 {
+    fn read8_rom_bank_0(&self, addr: u16) -> u8 {
+        self.rom[rom_offset(&self.rom, 0, addr)]
+    }
+
+    fn read8_rom_bank_n(&self, addr: u16) -> u8 {
+        self.rom[rom_offset(&self.rom, self.selected_rom_bank(), addr)]
+    }
+
+    fn read8_ram_bank_n(&self, addr: u16) -> u8 {
+        if !self.ram_enable || self.ram.is_empty() {
+            return 0xFF;
+        }
+        self.ram[ram_offset(&self.ram, self.selected_ram_bank(), addr)]
+    }
+
+    fn write8_rom_bank_0(&mut self, addr: u16, value: u8) {
+        if addr >= write::RAM_ENABLE.begin && addr <= write::RAM_ENABLE.end {
+            self.ram_enable = value & 0x0F == 0x0A;
+            log::debug!("ram in mbc5 enabled: {}", self.ram_enable);
+        } else if addr >= write::ROM_BANK_LOW.begin && addr <= write::ROM_BANK_LOW.end {
+            self.selected_rom_bank = self.selected_rom_bank & 0x100 | value as u16;
+        } else if addr >= write::ROM_BANK_HIGH.begin && addr <= write::ROM_BANK_HIGH.end {
+            self.selected_rom_bank = self.selected_rom_bank & 0xFF | (value as u16 & 0x01) << 8;
+        }
+    }
+
+    fn write8_rom_bank_n(&mut self, addr: u16, value: u8) {
+        if addr >= write::RAM_BANK_NUMBER.begin && addr <= write::RAM_BANK_NUMBER.end {
+            if self.has_rumble {
+                self.rumble = value & Self::RUMBLE_BIT != 0;
+                self.selected_ram_bank = value & 0x07;
+            } else {
+                self.selected_ram_bank = value & 0x0F;
+            }
+        }
+    }
+
+    fn write8_ram_bank_n(&mut self, addr: u16, value: u8) {
+        if !self.ram_enable || self.ram.is_empty() {
+            return;
+        }
+        let offset = ram_offset(&self.ram, self.selected_ram_bank(), addr);
+        self.ram[offset] = value;
+    }
+
+    fn get_rom(&self) -> &Vec<u8> {
+        &self.rom
+    }
+
+    fn get_ram(&self) -> &Vec<u8> {
+        &self.ram
+    }
+
+    fn get_ram_mut(&mut self) -> &mut Vec<u8> {
+        &mut self.ram
+    }
+
+    fn selected_rom_bank(&self) -> usize {
+        wrap_rom_bank(&self.rom, self.selected_rom_bank as usize)
+    }
+
+    fn selected_ram_bank(&self) -> usize {
+        wrap_ram_bank(&self.ram, self.selected_ram_bank as usize)
+    }
+
+    fn rumble(&self) -> bool {
+        self.rumble
+    }
+
+    fn new(rom: Vec<u8>, ram: Vec<u8>) -> Self
+    where
+        Self: Sized,
+    {
+        Self {
+            rom,
+            ram,
+            has_rumble: false,
+            ram_enable: false,
+            selected_rom_bank: 1,
+            selected_ram_bank: 0,
+            rumble: false,
+        }
+    }
+}
+
+impl SaveState for MBC5 {
+    fn save_state(&self, writer: &mut StateWriter) {
+        writer.write_vec(&self.ram);
+        writer.write_bool(self.ram_enable);
+        writer.write_u16(self.selected_rom_bank);
+        writer.write_u8(self.selected_ram_bank);
+        writer.write_bool(self.rumble);
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), String> {
+        self.ram = reader.read_vec()?;
+        self.ram_enable = reader.read_bool()?;
+        self.selected_rom_bank = reader.read_u16()?;
+        self.selected_ram_bank = reader.read_u8()?;
+        self.rumble = reader.read_bool()?;
+        Ok(())
+    }
+}
+
+mod write {
+    use crate::gameboy::memory::MemoryRange;
+
+    pub const RAM_ENABLE: MemoryRange = MemoryRange {
+        begin: 0x0000,
+        end: 0x1FFF,
+        size: 0x2000,
+    };
+
+    pub const ROM_BANK_LOW: MemoryRange = MemoryRange {
+        begin: 0x2000,
+        end: 0x2FFF,
+        size: 0x1000,
+    };
+
+    pub const ROM_BANK_HIGH: MemoryRange = MemoryRange {
+        begin: 0x3000,
+        end: 0x3FFF,
+        size: 0x1000,
+    };
+
+    pub const RAM_BANK_NUMBER: MemoryRange = MemoryRange {
+        begin: 0x4000,
+        end: 0x5FFF,
+        size: 0x2000,
+    };
+}
+
+#[test]
+fn mbc5_test() {
+    let mut mbc = MBC5::with_rumble(super::numbered_rom(512), vec![0; 0x8000], true);
+    mbc.write8_rom_bank_0(0x2000, 0x00);
+    assert_eq!(mbc.selected_rom_bank(), 0);
+    mbc.write8_rom_bank_0(0x2000, 0x23);
+    mbc.write8_rom_bank_0(0x3000, 0x01);
+    assert_eq!(
+        [mbc.read8_rom_bank_n(0x4000), mbc.read8_rom_bank_n(0x4001)],
+        [0x23, 0x01]
+    );
+
+    mbc.write8_rom_bank_0(0x0000, 0x0A);
+    mbc.write8_rom_bank_n(0x4000, 0x0B);
+    assert!(mbc.rumble());
+    assert_eq!(mbc.selected_ram_bank(), 3);
+    mbc.write8_ram_bank_n(0xA000, 0x42);
+    assert_eq!(mbc.ram[0x6000], 0x42);
+    mbc.write8_rom_bank_n(0x4000, 0x03);
+    assert!(!mbc.rumble());
+    assert_eq!(mbc.read8_ram_bank_n(0xA000), 0x42);
+}
@@ -0,0 +1,336 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::gameboy::savestate::{SaveState, StateReader, StateWriter};
+
+/// Real time clock of MBC3 and HuC3 cartridges. It counts t-cycles of the emulated cpu, so it runs with
+/// emulation speed, unless it follows the host clock, which is only looked at when the game latches the time.
+pub struct Rtc {
+    seconds: u8,
+    minutes: u8,
+    hours: u8,
+    // 9 bits on the MBC3, 16 bits on the HuC3
+    days: u16,
+    day_mask: u16,
+    halt: bool,
+    carry: bool,
+    // what the game reads, copied from the counters by the latch sequence
+    latched: [u8; 5],
+    latch_armed: bool,
+    cycles: u32,
+    // last time the counters were brought up to the host clock, None while counting emulated time
+    host_sync: Option<SystemTime>,
+}
+
+impl Rtc {
+    const CYCLES_PER_SECOND: u32 = 4194304;
+    pub const SECONDS: u8 = 0x08;
+    pub const MINUTES: u8 = 0x09;
+    pub const HOURS: u8 = 0x0A;
+    pub const DAY_LOW: u8 = 0x0B;
+    pub const DAY_HIGH: u8 = 0x0C;
+    /// size of the clock footer VBA-M and BGB append to save files
+    pub const FOOTER_SIZE: usize = 48;
+    // older versions write the timestamp with 32 bits
+    const SHORT_FOOTER_SIZE: usize = 44;
+
+    pub fn new() -> Self {
+        Self::with_day_bits(9)
+    }
+
+    /// a clock whose day counter wraps after `bits` bits instead of the 9 of the MBC3
+    pub fn with_day_bits(bits: u32) -> Self {
+        Self {
+            seconds: 0,
+            minutes: 0,
+            hours: 0,
+            days: 0,
+            day_mask: (1u32 << bits).wrapping_sub(1) as u16,
+            halt: false,
+            carry: false,
+            latched: [0; 5],
+            latch_armed: false,
+            cycles: 0,
+            host_sync: None,
+        }
+    }
+
+    pub fn set_host_sync(&mut self, sync: bool) {
+        self.host_sync = sync.then(SystemTime::now);
+    }
+
+    /// called every t-cycle
+    pub fn tick(&mut self) {
+        if self.halt || self.host_sync.is_some() {
+            return;
+        }
+        self.cycles += 1;
+        if self.cycles >= Self::CYCLES_PER_SECOND {
+            self.cycles = 0;
+            self.tick_second();
+        }
+    }
+
+    /// counts like the hardware, out of range values count up to the register width before they wrap without carry
+    fn tick_second(&mut self) {
+        self.seconds = (self.seconds + 1) & 0x3F;
+        if self.seconds != 60 {
+            return;
+        }
+        self.seconds = 0;
+        self.minutes = (self.minutes + 1) & 0x3F;
+        if self.minutes != 60 {
+            return;
+        }
+        self.minutes = 0;
+        self.hours = (self.hours + 1) & 0x1F;
+        if self.hours != 24 {
+            return;
+        }
+        self.hours = 0;
+        self.days = self.days.wrapping_add(1) & self.day_mask;
+        if self.days == 0 {
+            self.carry = true;
+        }
+    }
+
+    /// moves the clock forward as if it ran for the given seconds
+    pub fn advance(&mut self, mut seconds: u64) {
+        if self.halt {
+            return;
+        }
+        // out of range values are stepped until they wrapped
+        while seconds > 0 && (self.seconds >= 60 || self.minutes >= 60 || self.hours >= 24) {
+            self.tick_second();
+            seconds -= 1;
+        }
+        let total = self.seconds as u64
+            + self.minutes as u64 * 60
+            + self.hours as u64 * 3600
+            + self.days as u64 * 86400
+            + seconds;
+        let days = total / 86400;
+        let day_count = self.day_mask as u64 + 1;
+        if days >= day_count {
+            self.carry = true;
+        }
+        self.days = (days % day_count) as u16;
+        self.hours = (total % 86400 / 3600) as u8;
+        self.minutes = (total % 3600 / 60) as u8;
+        self.seconds = (total % 60) as u8;
+    }
+
+    fn sync_to_host(&mut self) {
+        let last_sync = match self.host_sync {
+            Some(last_sync) => last_sync,
+            None => return,
+        };
+        let elapsed = SystemTime::now()
+            .duration_since(last_sync)
+            .map_or(0, |elapsed| elapsed.as_secs());
+        self.advance(elapsed);
+        self.host_sync = Some(last_sync + std::time::Duration::from_secs(elapsed));
+    }
+
+    /// writing 0 and then 1 copies the counters into the registers the game reads
+    pub fn write_latch(&mut self, value: u8) {
+        if self.latch_armed && value == 0x01 {
+            self.sync_to_host();
+            self.latched = self.registers();
+        }
+        self.latch_armed = value == 0x00;
+    }
+
+    /// live counters as minute of the day and day, for the HuC3 which has no latch
+    pub fn time(&mut self) -> (u16, u16) {
+        self.sync_to_host();
+        (self.hours as u16 * 60 + self.minutes as u16, self.days)
+    }
+
+    pub fn set_time(&mut self, minute_of_day: u16, days: u16) {
+        self.sync_to_host();
+        self.seconds = 0;
+        self.cycles = 0;
+        self.minutes = (minute_of_day % 60) as u8;
+        self.hours = (minute_of_day / 60 % 24) as u8;
+        self.days = days & self.day_mask;
+    }
+
+    fn registers(&self) -> [u8; 5] {
+        [
+            self.seconds,
+            self.minutes,
+            self.hours,
+            self.days as u8,
+            (self.days >> 8) as u8 & 0x01 | (self.halt as u8) << 6 | (self.carry as u8) << 7,
+        ]
+    }
+
+    pub fn read(&self, register: u8) -> u8 {
+        match register {
+            Self::SECONDS..=Self::DAY_HIGH => self.latched[(register - Self::SECONDS) as usize],
+            _ => 0xFF,
+        }
+    }
+
+    pub fn write(&mut self, register: u8, value: u8) {
+        self.sync_to_host();
+        match register {
+            Self::SECONDS => {
+                self.seconds = value & 0x3F;
+                self.cycles = 0;
+            }
+            Self::MINUTES => self.minutes = value & 0x3F,
+            Self::HOURS => self.hours = value & 0x1F,
+            Self::DAY_LOW => self.days = self.days & 0x100 | value as u16,
+            Self::DAY_HIGH => {
+                self.days = self.days & 0xFF | (value as u16 & 0x01) << 8;
+                self.halt = value & 0x40 != 0;
+                self.carry = value & 0x80 != 0;
+            }
+            _ => return,
+        }
+        self.latched[(register - Self::SECONDS) as usize] = self.registers()[(register - Self::SECONDS) as usize];
+    }
+}
+
+impl Rtc {
+    pub fn unix_time() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_secs())
+    }
+
+    /// The counters and the latched registers as 32 bit little endian values followed by the unix time of the save,
+    /// the layout VBA-M and BGB use after the ram in save files. Day bits above the 9 of the MBC3 go into the
+    /// otherwise unused upper bytes of the day high register.
+    pub fn footer(&self, timestamp: u64) -> Vec<u8> {
+        let mut footer = Vec::with_capacity(Self::FOOTER_SIZE);
+        for (i, register) in self.registers().iter().chain(self.latched.iter()).enumerate() {
+            let mut value = *register as u32;
+            if i == 4 {
+                value |= (self.days as u32 >> 9) << 8;
+            }
+            footer.extend_from_slice(&value.to_le_bytes());
+        }
+        footer.extend_from_slice(&timestamp.to_le_bytes());
+        footer
+    }
+
+    pub fn is_footer(data: &[u8]) -> bool {
+        data.len() == Self::FOOTER_SIZE || data.len() == Self::SHORT_FOOTER_SIZE
+    }
+
+    /// restores the clock from a save file footer and lets it run for the time that passed since the save
+    pub fn load_footer(&mut self, footer: &[u8], now: u64) -> Result<(), String> {
+        if !Self::is_footer(footer) {
+            return Err(format!("rtc footer has {} bytes", footer.len()));
+        }
+        let wide_value = |i: usize| u32::from_le_bytes(footer[i * 4..i * 4 + 4].try_into().unwrap());
+        let value = |i: usize| wide_value(i) as u8;
+        let timestamp = if footer.len() == Self::FOOTER_SIZE {
+            u64::from_le_bytes(footer[40..48].try_into().unwrap())
+        } else {
+            u32::from_le_bytes(footer[40..44].try_into().unwrap()) as u64
+        };
+        self.seconds = value(0) & 0x3F;
+        self.minutes = value(1) & 0x3F;
+        self.hours = value(2) & 0x1F;
+        self.days =
+            (value(3) as u32 | (value(4) as u32 & 0x01) << 8 | (wide_value(4) >> 8) << 9) as u16 & self.day_mask;
+        self.halt = value(4) & 0x40 != 0;
+        self.carry = value(4) & 0x80 != 0;
+        for i in 0..self.latched.len() {
+            self.latched[i] = value(5 + i);
+        }
+        self.cycles = 0;
+        self.advance(now.saturating_sub(timestamp));
+        if self.host_sync.is_some() {
+            self.host_sync = Some(SystemTime::now());
+        }
+        Ok(())
+    }
+}
+
+impl Default for Rtc {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SaveState for Rtc {
+    fn save_state(&self, writer: &mut StateWriter) {
+        writer.write_bytes(&self.registers());
+        writer.write_bytes(&self.latched);
+        writer.write_bool(self.latch_armed);
+        writer.write_u32(self.cycles);
+        writer.write_u16(self.days);
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), String> {
+        let mut registers = [0; 5];
+        reader.read_bytes(&mut registers)?;
+        [self.seconds, self.minutes, self.hours] = [registers[0], registers[1], registers[2]];
+        self.days = registers[3] as u16 | (registers[4] as u16 & 0x01) << 8;
+        self.halt = registers[4] & 0x40 != 0;
+        self.carry = registers[4] & 0x80 != 0;
+        reader.read_bytes(&mut self.latched)?;
+        self.latch_armed = reader.read_bool()?;
+        self.cycles = reader.read_u32()?;
+        self.days = reader.read_u16()? & self.day_mask;
+        if self.host_sync.is_some() {
+            self.host_sync = Some(SystemTime::now());
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn rtc_test() {
+    let mut rtc = Rtc::new();
+    rtc.write(Rtc::SECONDS, 59);
+    rtc.write(Rtc::MINUTES, 59);
+    rtc.write(Rtc::HOURS, 23);
+    rtc.write(Rtc::DAY_LOW, 0xFF);
+    rtc.write(Rtc::DAY_HIGH, 0x01);
+    for _ in 0..Rtc::CYCLES_PER_SECOND {
+        rtc.tick();
+    }
+    // nothing is visible before the latch
+    assert_eq!(rtc.read(Rtc::SECONDS), 59);
+    rtc.write_latch(0x00);
+    rtc.write_latch(0x01);
+    assert_eq!(
+        [Rtc::SECONDS, Rtc::MINUTES, Rtc::HOURS, Rtc::DAY_LOW, Rtc::DAY_HIGH].map(|register| rtc.read(register)),
+        [0, 0, 0, 0, 0x80]
+    );
+
+    // halted clocks stand still
+    rtc.write(Rtc::DAY_HIGH, 0x40);
+    rtc.advance(100);
+    rtc.write(Rtc::DAY_HIGH, 0x00);
+    rtc.advance(3 * 86400 + 3661);
+    rtc.write_latch(0x00);
+    rtc.write_latch(0x01);
+    assert_eq!(
+        [Rtc::SECONDS, Rtc::MINUTES, Rtc::HOURS, Rtc::DAY_LOW].map(|register| rtc.read(register)),
+        [1, 1, 1, 3]
+    );
+
+    // out of range seconds wrap to 0 at 64 without counting a minute
+    rtc.write(Rtc::SECONDS, 62);
+    rtc.advance(3);
+    rtc.write_latch(0x00);
+    rtc.write_latch(0x01);
+    assert_eq!(rtc.read(Rtc::SECONDS), 1);
+    assert_eq!(rtc.read(Rtc::MINUTES), 1);
+
+    // saved an hour ago
+    let footer = rtc.footer(1_000_000);
+    assert_eq!(footer.len(), Rtc::FOOTER_SIZE);
+    assert_eq!(&footer[4..8], &[1, 0, 0, 0]);
+    let mut loaded = Rtc::new();
+    loaded.load_footer(&footer, 1_000_000 + 3600).unwrap();
+    assert_eq!(loaded.read(Rtc::MINUTES), 1);
+    assert_eq!(loaded.registers()[..4], [1, 1, 2, 3]);
+    assert!(loaded.load_footer(&footer[..40], 0).is_err());
+}
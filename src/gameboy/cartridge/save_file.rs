@@ -0,0 +1,185 @@
+use std::{
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use super::{rtc::Rtc, Cartridge};
+
+impl Cartridge {
+    // about 5 seconds after the first write that was not saved yet
+    const AUTOSAVE_FRAMES: u32 = 300;
+
+    /// Battery backed ram is saved as `<rom name>.sav` in the directory instead of next to the rom,
+    /// an existing save there replaces the one loaded on start.
+    pub fn set_save_dir(&mut self, dir: &Path) {
+        let file_name = match self.save_path.as_ref().and_then(|path| path.file_name()) {
+            Some(file_name) => file_name.to_owned(),
+            None => return,
+        };
+        self.save_path = Some(dir.join(file_name));
+        self.load_save();
+    }
+
+    pub fn save_path(&self) -> Option<&PathBuf> {
+        self.save_path.as_ref()
+    }
+
+    pub(super) fn load_save(&mut self) {
+        let path = match self.save_path.as_ref() {
+            Some(path) => path,
+            None => return,
+        };
+        self.load_failed = false;
+        match fs::read(path) {
+            Ok(data) => {
+                let save_size = std::cmp::min(data.len(), self.header.save_size);
+                let (ram_data, footer) = data.split_at(save_size);
+                let ram = self.mbc.0.get_ram_mut();
+                let size = std::cmp::min(ram_data.len(), ram.len());
+                ram[..size].copy_from_slice(&ram_data[..size]);
+                if let Some(rtc) = self.mbc.0.rtc_mut() {
+                    if let Err(err) = rtc.load_footer(footer, Rtc::unix_time()) {
+                        log::warn!("no clock in {}: {}", path.display(), err);
+                    }
+                }
+                log::info!("loaded {}", path.display());
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+            Err(err) => {
+                log::error!("failed to load {}, it will not be overwritten: {}", path.display(), err);
+                self.load_failed = true;
+            }
+        }
+    }
+
+    /// the ram as listed in the header followed by the clock, so saves are exchangeable with other emulators
+    fn save_data(&self) -> Vec<u8> {
+        let ram = self.mbc.0.get_ram();
+        let mut data = ram[..std::cmp::min(self.header.save_size, ram.len())].to_vec();
+        if let Some(rtc) = self.mbc.0.rtc() {
+            data.extend_from_slice(&rtc.footer(Rtc::unix_time()));
+        }
+        data
+    }
+
+    /// Writes the battery backed ram. The save is written to a temporary file first and then renamed,
+    /// so a crash while writing leaves the previous save intact.
+    pub fn save(&mut self) -> io::Result<()> {
+        let path = match self.save_path.as_ref() {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        if self.load_failed {
+            return Err(io::Error::other(format!(
+                "{} could not be loaded, not overwriting it",
+                path.display()
+            )));
+        }
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+        let temp_path = path.with_extension("sav.tmp");
+        let mut file = fs::File::create(&temp_path)?;
+        file.write_all(&self.save_data())?;
+        // the data has to be on disk before the rename replaces the previous save
+        file.sync_all()?;
+        fs::rename(&temp_path, path)?;
+        self.ram_dirty = false;
+        self.frames_since_save = 0;
+        Ok(())
+    }
+
+    /// saves when the ram was written since the last save, e.g. on exit
+    pub fn save_if_dirty(&mut self) -> io::Result<()> {
+        if !self.ram_dirty {
+            return Ok(());
+        }
+        self.save()
+    }
+
+    /// saves once written ram stayed unsaved for a while
+    pub fn on_frame(&mut self) {
+        if !self.ram_dirty || self.save_path.is_none() || self.load_failed {
+            return;
+        }
+        self.frames_since_save += 1;
+        if self.frames_since_save >= Self::AUTOSAVE_FRAMES {
+            if let Err(err) = self.save() {
+                log::error!("failed to write save file: {}", err);
+                self.frames_since_save = 0;
+            }
+        }
+    }
+}
+
+#[test]
+fn save_file_test() {
+    use crate::gameboy::MemoryInterface;
+
+    let dir = std::env::temp_dir().join(format!("gameboy_save_file_test_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let mut rom = vec![0; 0x8000];
+    rom[0x147] = 0x03; // MBC1+RAM+BATTERY
+    rom[0x149] = 0x02; // 8 KiB
+    let rom_path = dir.join("game.gb");
+    fs::write(&rom_path, &rom).unwrap();
+    let boot_rom_path = dir.join("boot.bin");
+    fs::write(&boot_rom_path, [0; 0x100]).unwrap();
+    let new_cartridge = || {
+        Cartridge::new(
+            boot_rom_path.to_string_lossy().into_owned(),
+            rom_path.to_string_lossy().into_owned(),
+        )
+    };
+
+    let mut cartridge = new_cartridge();
+    assert_eq!(cartridge.save_path(), Some(&dir.join("game.sav")));
+    cartridge.write8(0x0000, 0x0A);
+    cartridge.write8(0xA010, 0x42);
+    assert!(cartridge.ram_dirty);
+    cartridge.save().unwrap();
+    assert!(!cartridge.ram_dirty);
+    assert_eq!(fs::read(dir.join("game.sav")).unwrap().len(), 0x2000);
+    assert!(!dir.join("game.sav.tmp").exists());
+
+    let mut cartridge = new_cartridge();
+    cartridge.write8(0x0000, 0x0A);
+    assert_eq!(cartridge.read8(0xA010), Some(0x42));
+
+    // a save directory without a save keeps what was loaded
+    cartridge.set_save_dir(&dir.join("saves"));
+    assert_eq!(cartridge.read8(0xA010), Some(0x42));
+    cartridge.save().unwrap();
+    assert!(dir.join("saves").join("game.sav").exists());
+
+    // only cartridges with a timer get the clock footer
+    for (cartridge_type, size) in [(0x13, 0x2000), (0x10, 0x2000 + Rtc::FOOTER_SIZE)] {
+        rom[0x147] = cartridge_type;
+        fs::write(&rom_path, &rom).unwrap();
+        let mut cartridge = new_cartridge();
+        cartridge.save().unwrap();
+        assert_eq!(fs::read(dir.join("game.sav")).unwrap().len(), size);
+    }
+
+    // an unreadable save is kept, a directory can not be read as a file
+    let save_path = dir.join("game.sav");
+    fs::remove_file(&save_path).unwrap();
+    fs::create_dir(&save_path).unwrap();
+    let mut cartridge = new_cartridge();
+    assert!(cartridge.load_failed);
+    cartridge.write8(0x0000, 0x0A);
+    cartridge.write8(0xA010, 0x42);
+    assert!(cartridge.save_if_dirty().is_err());
+    cartridge.on_frame();
+    assert_eq!(cartridge.frames_since_save, 0);
+    assert!(save_path.is_dir());
+    fs::remove_dir(&save_path).unwrap();
+
+    // nothing is written without a write to the ram
+    let mut cartridge = new_cartridge();
+    cartridge.save_if_dirty().unwrap();
+    assert!(!save_path.exists());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
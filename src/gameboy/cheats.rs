@@ -0,0 +1,218 @@
+use std::{fmt, fs, path::Path};
+
+use super::{cartridge::RomPatch, memory, Gameboy};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CheatCode {
+    /// `ttvvllhh`, written through the bus once per frame.
+    /// Type 00 and 01 write to whatever is mapped at the address, type 8n writes into bank n of the
+    /// banked ram the address belongs to (external ram at $A000-$BFFF, work ram at $D000-$DFFF)
+    GameShark { bank: Option<u8>, addr: u16, value: u8 },
+    /// `ABC-DEF-GHI` or `ABC-DEF`, substitutes rom reads in the mbc
+    GameGenie(RomPatch),
+}
+
+impl std::str::FromStr for CheatCode {
+    type Err = String;
+
+    fn from_str(code: &str) -> Result<Self, Self::Err> {
+        let digits: Vec<u8> = code
+            .chars()
+            .filter(|c| *c != '-')
+            .map(|c| c.to_digit(16).map(|digit| digit as u8))
+            .collect::<Option<_>>()
+            .ok_or_else(|| format!("invalid cheat code '{}'", code))?;
+        let byte = |i: usize| digits[i] << 4 | digits[i + 1];
+        match digits.len() {
+            8 if !code.contains('-') => {
+                let bank = match byte(0) {
+                    0x00 | 0x01 => None,
+                    code_type @ 0x80..=0x8F => Some(code_type & 0x0F),
+                    code_type => {
+                        return Err(format!(
+                            "unsupported GameShark code type {:02X} in '{}'",
+                            code_type, code
+                        ))
+                    }
+                };
+                Ok(CheatCode::GameShark {
+                    bank,
+                    value: byte(2),
+                    addr: (byte(6) as u16) << 8 | byte(4) as u16,
+                })
+            }
+            6 | 9 => {
+                let addr = ((digits[5] ^ 0xF) as u16) << 12
+                    | (digits[2] as u16) << 8
+                    | (digits[3] as u16) << 4
+                    | digits[4] as u16;
+                let compare = (digits.len() == 9).then(|| (digits[6] << 4 | digits[8]).rotate_right(2) ^ 0xBA);
+                Ok(CheatCode::GameGenie(RomPatch {
+                    addr,
+                    value: byte(0),
+                    compare,
+                }))
+            }
+            _ => Err(format!("invalid cheat code '{}'", code)),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Cheat {
+    pub code: String,
+    pub description: String,
+    pub enabled: bool,
+    pub kind: CheatCode,
+}
+
+impl fmt::Display for Cheat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "[{}] {:<11} {}",
+            if self.enabled { "x" } else { " " },
+            self.code,
+            self.description
+        )
+    }
+}
+
+/// One cheat per line as `<code> [description]`, a `-` in front of the code loads it disabled.
+/// Empty lines and lines starting with `#` are ignored.
+pub fn parse_cheat_file(text: &str) -> Result<Vec<Cheat>, String> {
+    let mut cheats = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (code, description) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let (code, enabled) = match code.strip_prefix('-') {
+            Some(code) => (code, false),
+            None => (code, true),
+        };
+        let kind = code.parse().map_err(|err| format!("line {}: {}", i + 1, err))?;
+        cheats.push(Cheat {
+            code: code.to_uppercase(),
+            description: description.trim().to_string(),
+            enabled,
+            kind,
+        });
+    }
+    Ok(cheats)
+}
+
+impl Gameboy {
+    pub fn load_cheats(&mut self, path: &Path) {
+        let cheats = fs::read_to_string(path)
+            .map_err(|err| err.to_string())
+            .and_then(|text| parse_cheat_file(&text));
+        match cheats {
+            Ok(cheats) => {
+                log::info!("loaded {} cheats from {}", cheats.len(), path.display());
+                self.cheats = cheats;
+                self.update_rom_patches();
+            }
+            Err(err) => log::error!("failed to load cheats from {}: {}", path.display(), err),
+        }
+    }
+
+    pub fn cheats(&self) -> &[Cheat] {
+        &self.cheats
+    }
+
+    /// returns false if there is no cheat with the given index
+    pub fn set_cheat_enabled(&mut self, index: usize, enabled: bool) -> bool {
+        match self.cheats.get_mut(index) {
+            Some(cheat) => {
+                cheat.enabled = enabled;
+                self.update_rom_patches();
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn update_rom_patches(&mut self) {
+        let patches = self
+            .cheats
+            .iter()
+            .filter(|cheat| cheat.enabled)
+            .filter_map(|cheat| match cheat.kind {
+                CheatCode::GameGenie(patch) => Some(patch),
+                _ => None,
+            })
+            .collect();
+        self.cartridge.set_rom_patches(patches);
+    }
+
+    /// called once per frame like the vblank handler of a real GameShark
+    pub(super) fn apply_cheats(&mut self) {
+        for i in 0..self.cheats.len() {
+            let cheat = &self.cheats[i];
+            if !cheat.enabled {
+                continue;
+            }
+            if let CheatCode::GameShark { bank, addr, value } = cheat.kind {
+                self.write_game_shark(bank, addr, value);
+            }
+        }
+    }
+
+    fn write_game_shark(&mut self, bank: Option<u8>, addr: u16, value: u8) {
+        let external_ram = addr >= memory::cartridge::EXTERNAL_RAM.begin && addr <= memory::cartridge::EXTERNAL_RAM.end;
+        match bank {
+            // the bank is written directly, switching banks through the mapper would change its other registers too
+            Some(bank) if external_ram => self.cartridge.poke_ram(bank as usize, addr, value),
+            // without cgb wram banking only bank 1 is mapped at $D000-$DFFF
+            Some(bank) if addr >= memory::WRAM.begin + 0x1000 && addr <= memory::WRAM.end && bank > 1 => {}
+            _ => self.write8(addr, value),
+        }
+    }
+}
+
+#[test]
+fn cheat_code_test() {
+    assert_eq!(
+        "01FF10C1".parse::<CheatCode>(),
+        Ok(CheatCode::GameShark {
+            bank: None,
+            addr: 0xC110,
+            value: 0xFF
+        })
+    );
+    assert_eq!(
+        "82630AA0".parse::<CheatCode>(),
+        Ok(CheatCode::GameShark {
+            bank: Some(2),
+            addr: 0xA00A,
+            value: 0x63
+        })
+    );
+    assert_eq!(
+        "01A-B4E-E6E".parse::<CheatCode>(),
+        Ok(CheatCode::GameGenie(RomPatch {
+            addr: 0x1AB4,
+            value: 0x01,
+            compare: Some(0x01)
+        }))
+    );
+    assert_eq!(
+        "C3A-B4E".parse::<CheatCode>(),
+        Ok(CheatCode::GameGenie(RomPatch {
+            addr: 0x1AB4,
+            value: 0xC3,
+            compare: None
+        }))
+    );
+    assert!("01FF10C".parse::<CheatCode>().is_err());
+    assert!("47FF10C1".parse::<CheatCode>().is_err());
+
+    let cheats = parse_cheat_file("# infinite lives\n01FF10C1 lives\n\n-01A-B4E-E6E jump\n").unwrap();
+    assert_eq!(cheats.len(), 2);
+    assert_eq!(cheats[0].description, "lives");
+    assert!(cheats[0].enabled);
+    assert!(!cheats[1].enabled);
+    assert!(parse_cheat_file("01FF10C1\nnope").is_err());
+}
@@ -2,7 +2,11 @@ use std::fmt;
 
 use self::instructions::InterruptRegister;
 
-use super::{memory, Gameboy, GameboyModule};
+use super::{
+    memory,
+    savestate::{SaveState, StateReader, StateWriter},
+    Gameboy, GameboyModule,
+};
 
 #[allow(non_snake_case)]
 mod instructions;
@@ -110,6 +114,44 @@ impl super::MemoryInterface for CPU {
     }
 }
 
+impl SaveState for CPU {
+    fn save_state(&self, writer: &mut StateWriter) {
+        for reg in [self.a, self.b, self.c, self.d, self.e, self.f, self.h, self.l] {
+            writer.write_u8(reg);
+        }
+        writer.write_u16(self.pc);
+        writer.write_u16(self.sp);
+        writer.write_u8(self.ie_register.clone().into());
+        writer.write_u8(self.if_register.clone().into());
+        writer.write_bool(self.halted);
+        writer.write_bool(self.interrupt_master_enable);
+        writer.write_u16(self.t_cycles);
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), String> {
+        for reg in [
+            &mut self.a,
+            &mut self.b,
+            &mut self.c,
+            &mut self.d,
+            &mut self.e,
+            &mut self.f,
+            &mut self.h,
+            &mut self.l,
+        ] {
+            *reg = reader.read_u8()?;
+        }
+        self.pc = reader.read_u16()?;
+        self.sp = reader.read_u16()?;
+        self.ie_register = reader.read_u8()?.into();
+        self.if_register = reader.read_u8()?.into();
+        self.halted = reader.read_bool()?;
+        self.interrupt_master_enable = reader.read_bool()?;
+        self.t_cycles = reader.read_u16()?;
+        Ok(())
+    }
+}
+
 impl CPU {
     fn check_halted(&mut self) -> bool {
         if !self.halted {
@@ -133,14 +175,41 @@ impl CPU {
                 return Ok(self.t_cycles as u32);
             }
             instructions::handle_int(self, gb);
+            if gb.debugger.is_break_pending() {
+                // hand over to the debugger before the interrupt handler executes
+                return Ok(self.t_cycles as u32);
+            }
+            let bank = gb.cartridge.rom_bank(self.pc);
+            let opcode = gb.read8_unlocked(self.pc);
+            gb.debugger_on_instruction(self, bank, opcode);
+            gb.hooks.on_instruction(self);
+            gb.cdl_log_instruction(self.pc, opcode);
+            gb.heatmap_log_instruction(self.pc, opcode);
+            let pc = self.pc;
             let cycles;
             (self.pc, cycles) = instructions::execute_instruction(self, gb);
             self.t_cycles = cycles + 8;
+            let next_bank = gb.cartridge.rom_bank(self.pc);
+            gb.debugger
+                .on_instruction_executed(self, bank, pc, opcode, self.t_cycles as u64, next_bank);
         }
         self.t_cycles -= 1;
         Ok(self.t_cycles as u32)
     }
 
+    /// human readable instruction for an opcode, the second byte is only used for the 0xCB prefix
+    pub fn mnemonic(opcode: u8, cb_opcode: u8) -> &'static str {
+        instructions::mnemonic(opcode, cb_opcode)
+    }
+
+    pub fn instruction_length(opcode: u8) -> u16 {
+        instructions::instruction_length(opcode)
+    }
+
+    pub fn is_instruction_boundary(&self) -> bool {
+        self.t_cycles == 0
+    }
+
     pub fn new() -> Self {
         Self {
             a: 0x00,
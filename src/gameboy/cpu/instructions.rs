@@ -1,6 +1,6 @@
 use crate::{
     bit,
-    gameboy::{cpu::Flag, Gameboy},
+    gameboy::{cpu::Flag, debugger::DebugEvent, Gameboy},
 };
 
 use super::CPU;
@@ -230,6 +230,9 @@ fn execute_int(cpu: &mut CPU, address: u16, gb: &mut Gameboy) {
     log::info!("handle interrupt addr {:#06X}", address);
     _push(cpu, cpu.pc, gb);
     cpu.pc = address;
+    gb.debugger.on_interrupt_dispatched(address, cpu.sp);
+    gb.hooks.on_interrupt(address);
+    gb.debugger.on_event(DebugEvent::Interrupt { vector: address });
 }
 
 pub fn execute_instruction_extension(cpu: &mut CPU, gb: &mut Gameboy) -> (u16, u16) {
@@ -862,6 +865,28 @@ pub fn execute_instruction(cpu: &mut CPU, gb: &mut Gameboy) -> (u16, u16) {
     new_pc
 }
 
+pub fn mnemonic(opcode: u8, cb_opcode: u8) -> &'static str {
+    if opcode == 0xCB {
+        CPU_COMMANDS[cb_opcode as usize + 0x100]
+    } else {
+        CPU_COMMANDS[opcode as usize]
+    }
+}
+
+/// number of bytes the instruction occupies including operands
+pub fn instruction_length(opcode: u8) -> u16 {
+    let mnemonic = CPU_COMMANDS[opcode as usize];
+    if opcode == 0xCB || opcode == 0x10 {
+        2
+    } else if mnemonic.contains("d16") || mnemonic.contains("a16") {
+        3
+    } else if mnemonic.contains("d8") || mnemonic.contains("a8") || mnemonic.contains("r8") {
+        2
+    } else {
+        1
+    }
+}
+
 #[inline(always)]
 fn get_imm16(cpu: &CPU, gb: &Gameboy) -> u16 {
     let low = gb.read8(cpu.pc.wrapping_add(1));
@@ -0,0 +1,65 @@
+mod event_viewer;
+mod heatmap_viewer;
+mod io_viewer;
+mod map_viewer;
+mod memory_viewer;
+mod oam_viewer;
+mod watch_viewer;
+
+pub use self::event_viewer::EventViewer;
+pub use self::heatmap_viewer::HeatmapViewer;
+pub use self::io_viewer::IoViewer;
+pub use self::map_viewer::MapViewer;
+pub use self::memory_viewer::MemoryViewer;
+pub use self::oam_viewer::OamViewer;
+pub use self::watch_viewer::WatchViewer;
+
+// colors shared by the debug windows
+const BACKGROUND: u32 = 0x00101418;
+const TEXT: u32 = 0x00C8C8C8;
+const TEXT_DIM: u32 = 0x00707880;
+const ACCENT: u32 = 0x0060A0FF;
+const HIGHLIGHT: u32 = 0x00FF5050;
+const SELECTION: u32 = 0x00305080;
+
+/// maps number and a-f keys to their hex value
+fn hex_digit(key: minifb::Key) -> Option<u8> {
+    use minifb::Key;
+    match key {
+        Key::Key0 | Key::NumPad0 => Some(0x0),
+        Key::Key1 | Key::NumPad1 => Some(0x1),
+        Key::Key2 | Key::NumPad2 => Some(0x2),
+        Key::Key3 | Key::NumPad3 => Some(0x3),
+        Key::Key4 | Key::NumPad4 => Some(0x4),
+        Key::Key5 | Key::NumPad5 => Some(0x5),
+        Key::Key6 | Key::NumPad6 => Some(0x6),
+        Key::Key7 | Key::NumPad7 => Some(0x7),
+        Key::Key8 | Key::NumPad8 => Some(0x8),
+        Key::Key9 | Key::NumPad9 => Some(0x9),
+        Key::A => Some(0xA),
+        Key::B => Some(0xB),
+        Key::C => Some(0xC),
+        Key::D => Some(0xD),
+        Key::E => Some(0xE),
+        Key::F => Some(0xF),
+        _ => None,
+    }
+}
+
+/// linear mix between two rgb colors, t = 0 gives `from` and t = 1 gives `to`
+fn blend(from: u32, to: u32, t: f32) -> u32 {
+    let t = t.clamp(0., 1.);
+    let channel = |shift: u32| {
+        let a = ((from >> shift) & 0xFF) as f32;
+        let b = ((to >> shift) & 0xFF) as f32;
+        ((a + (b - a) * t) as u32) << shift
+    };
+    channel(16) | channel(8) | channel(0)
+}
+
+#[test]
+fn blend_test() {
+    assert_eq!(blend(0x00000000, 0x00FF8040, 0.), 0x00000000);
+    assert_eq!(blend(0x00000000, 0x00FF8040, 1.), 0x00FF8040);
+    assert_eq!(blend(0x00000000, 0x00FF8040, 0.5), 0x007F4020);
+}
@@ -0,0 +1,196 @@
+use std::path::PathBuf;
+
+use minifb::Key;
+
+use crate::{
+    gameboy::{
+        debugger::{IoEvent, IoEventKind},
+        memory,
+        ppu::PPU,
+        Gameboy,
+    },
+    screen::{canvas::Canvas, Screen},
+};
+
+use super::{blend, ACCENT, BACKGROUND, HIGHLIGHT, SELECTION, TEXT, TEXT_DIM};
+
+/// IO writes and interrupt requests of the last frame plotted at the dot and line they happened on,
+/// one pixel per dot over all 154 lines. The background shows the nominal ppu modes of each line.
+/// Hovering the plot lists the events close to the mouse, `S` writes the frame to `events_<frame>.csv`.
+pub struct EventViewer {
+    screen: Screen,
+    canvas: Canvas,
+}
+
+impl EventViewer {
+    const MARGIN: usize = 4;
+    const LINE_HEIGHT: usize = Canvas::CHAR_HEIGHT + 2;
+    const PLOT_WIDTH: usize = PPU::DOTS_PER_LINE as usize;
+    const PLOT_HEIGHT: usize = PPU::LINES_PER_FRAME as usize;
+    const PLOT_Y: usize = Self::MARGIN + Self::LINE_HEIGHT + 2;
+    const INFO_LINES: usize = 5;
+    const WIDTH: usize = Self::PLOT_WIDTH + 2 * Self::MARGIN;
+    const HEIGHT: usize =
+        Self::PLOT_Y + Self::PLOT_HEIGHT + 2 + (Self::INFO_LINES + 1) * Self::LINE_HEIGHT + Self::MARGIN;
+    // nominal mode boundaries, mode 3 gets longer with scrolling, the window and objects
+    const OAM_SEARCH_DOTS: usize = 80;
+    const TRANSFER_DOTS: usize = 172;
+    const VISIBLE_LINES: usize = 144;
+    // events within this many dots of the mouse are listed
+    const HOVER_DISTANCE: usize = 4;
+
+    const PPU_COLOR: u32 = ACCENT;
+    const SOUND_COLOR: u32 = 0x0060D080;
+    const TIMER_COLOR: u32 = 0x00E0C040;
+    const INTERRUPT_COLOR: u32 = HIGHLIGHT;
+    const OTHER_COLOR: u32 = TEXT;
+
+    pub fn new(gb: &mut Gameboy) -> Self {
+        gb.debugger.log_events();
+        let mut screen = Screen::new(Self::HEIGHT, Self::WIDTH, minifb::Scale::X2);
+        screen.set_title("Events");
+        Self {
+            screen,
+            canvas: Canvas::new(Self::WIDTH, Self::HEIGHT),
+        }
+    }
+
+    pub fn update(&mut self, gb: &Gameboy) {
+        for key in self.screen.get_keys_pressed() {
+            if key == Key::S {
+                self.save(gb);
+            }
+        }
+        self.draw(gb);
+        self.screen.set_frame_buffer(self.canvas.buffer());
+        self.screen.update();
+    }
+
+    fn save(&self, gb: &Gameboy) {
+        let frame = gb.debugger.event_log().map_or(0, |event_log| event_log.frame());
+        let path = PathBuf::from(format!("events_{}.csv", frame));
+        match gb.save_event_log(&path) {
+            Ok(()) => log::info!("events written to {}", path.display()),
+            Err(err) => log::error!("failed to write {}: {}", path.display(), err),
+        }
+    }
+
+    fn event_color(kind: &IoEventKind) -> u32 {
+        match *kind {
+            IoEventKind::InterruptRequest { .. } => Self::INTERRUPT_COLOR,
+            IoEventKind::Write { addr, .. } => {
+                if addr == memory::interrupt::IF || addr == memory::interrupt::IE {
+                    Self::INTERRUPT_COLOR
+                } else if addr >= memory::ppu::LCDC && addr <= memory::ppu::WX {
+                    Self::PPU_COLOR
+                // the memory map names $FF10, the first sound register, NR11
+                } else if addr >= memory::apu::NR11 && addr <= memory::apu::WAVE_PATTERN_RAM.end {
+                    Self::SOUND_COLOR
+                } else if addr >= memory::timer::DIV && addr <= memory::timer::TAC {
+                    Self::TIMER_COLOR
+                } else {
+                    Self::OTHER_COLOR
+                }
+            }
+        }
+    }
+
+    fn mode_color(dot: usize, line: usize) -> u32 {
+        let tint = if line >= Self::VISIBLE_LINES {
+            TEXT_DIM
+        } else if dot < Self::OAM_SEARCH_DOTS {
+            Self::TIMER_COLOR
+        } else if dot < Self::OAM_SEARCH_DOTS + Self::TRANSFER_DOTS {
+            SELECTION
+        } else {
+            BACKGROUND
+        };
+        blend(BACKGROUND, tint, 0.25)
+    }
+
+    fn hovered(&self) -> Option<(usize, usize)> {
+        let (x, y) = self.screen.get_mouse_pos()?;
+        let dot = x.checked_sub(Self::MARGIN)?;
+        let line = y.checked_sub(Self::PLOT_Y)?;
+        (dot < Self::PLOT_WIDTH && line < Self::PLOT_HEIGHT).then_some((dot, line))
+    }
+
+    fn draw(&mut self, gb: &Gameboy) {
+        self.canvas.clear(BACKGROUND);
+        let event_log = match gb.debugger.event_log() {
+            Some(event_log) => event_log,
+            None => return,
+        };
+        let events = event_log.last_frame();
+
+        self.canvas.draw_text(
+            Self::MARGIN,
+            Self::MARGIN,
+            &format!("frame {}  {} events  (s saves csv)", event_log.frame(), events.len()),
+            TEXT,
+        );
+
+        for line in 0..Self::PLOT_HEIGHT {
+            for dot in 0..Self::PLOT_WIDTH {
+                self.canvas
+                    .set_pixel(Self::MARGIN + dot, Self::PLOT_Y + line, Self::mode_color(dot, line));
+            }
+        }
+        let hovered = self.hovered();
+        if let Some((dot, line)) = hovered {
+            for x in 0..Self::PLOT_WIDTH {
+                self.canvas.set_pixel(Self::MARGIN + x, Self::PLOT_Y + line, SELECTION);
+            }
+            for y in 0..Self::PLOT_HEIGHT {
+                self.canvas.set_pixel(Self::MARGIN + dot, Self::PLOT_Y + y, SELECTION);
+            }
+        }
+        for event in events.iter() {
+            self.canvas.set_pixel(
+                Self::MARGIN + event.dot as usize,
+                Self::PLOT_Y + event.ly as usize,
+                Self::event_color(&event.kind),
+            );
+        }
+
+        let mut y = Self::PLOT_Y + Self::PLOT_HEIGHT + 2;
+        let mut x = Self::MARGIN;
+        for (name, color) in [
+            ("ppu", Self::PPU_COLOR),
+            ("sound", Self::SOUND_COLOR),
+            ("timer", Self::TIMER_COLOR),
+            ("interrupt", Self::INTERRUPT_COLOR),
+            ("other", Self::OTHER_COLOR),
+        ] {
+            x = self.canvas.draw_text(x, y, name, color) + Canvas::CHAR_WIDTH;
+        }
+        y += Self::LINE_HEIGHT;
+
+        let (dot, line) = match hovered {
+            Some(position) => position,
+            None => {
+                self.canvas
+                    .draw_text(Self::MARGIN, y, "hover the plot to list events", TEXT_DIM);
+                return;
+            }
+        };
+        let nearby: Vec<&IoEvent> = events
+            .iter()
+            .filter(|event| event.ly as usize == line && (event.dot as usize).abs_diff(dot) <= Self::HOVER_DISTANCE)
+            .collect();
+        self.canvas.draw_text(
+            Self::MARGIN,
+            y,
+            &format!("LY {:>3} dot {:>3}: {} events nearby", line, dot, nearby.len()),
+            TEXT_DIM,
+        );
+        for (i, event) in nearby.iter().take(Self::INFO_LINES - 1).enumerate() {
+            self.canvas.draw_text(
+                Self::MARGIN,
+                y + (i + 1) * Self::LINE_HEIGHT,
+                &event.to_string(),
+                Self::event_color(&event.kind),
+            );
+        }
+    }
+}
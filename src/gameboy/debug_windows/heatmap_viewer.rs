@@ -0,0 +1,186 @@
+use minifb::Key;
+
+use crate::{
+    gameboy::{debugger::Heat, memory, Gameboy},
+    screen::{canvas::Canvas, Screen},
+};
+
+use super::{BACKGROUND, TEXT, TEXT_DIM};
+
+#[derive(Clone, Copy, PartialEq)]
+enum HeatmapView {
+    AddressSpace,
+    RomBank(usize),
+    RamBank(usize),
+}
+
+/// Reads, writes and executes as a decaying heatmap, one pixel per byte of the address space
+/// or of a single rom or external ram bank. Reads are green, writes red and executes blue.
+/// `A` shows the address space, `R` and `E` a rom and an external ram bank, left and right switch the bank.
+pub struct HeatmapViewer {
+    screen: Screen,
+    canvas: Canvas,
+    view: HeatmapView,
+}
+
+impl HeatmapViewer {
+    const MARGIN: usize = 4;
+    const LINE_HEIGHT: usize = Canvas::CHAR_HEIGHT + 2;
+    const LABEL_WIDTH: usize = 6 * Canvas::CHAR_WIDTH;
+    const PLOT_X: usize = Self::MARGIN + Self::LABEL_WIDTH;
+    const PLOT_Y: usize = Self::MARGIN + Self::LINE_HEIGHT + 2;
+    const PLOT_SIZE: usize = 256;
+    const WIDTH: usize = Self::PLOT_X + Self::PLOT_SIZE + Self::MARGIN;
+    const HEIGHT: usize = Self::PLOT_Y + Self::PLOT_SIZE + 2 + 2 * Self::LINE_HEIGHT + Self::MARGIN;
+    // a bank is drawn with 128 bytes per row, each byte as a 2x2 block
+    const BANK_ROW_BYTES: usize = 128;
+    const BANK_ZOOM: usize = 2;
+    const LABEL_ROWS: usize = 32;
+    const EMPTY: u32 = 0x00181E24;
+
+    pub fn new(gb: &mut Gameboy) -> Self {
+        gb.enable_heatmap();
+        let mut screen = Screen::new(Self::HEIGHT, Self::WIDTH, minifb::Scale::X2);
+        screen.set_title("Heatmap");
+        Self {
+            screen,
+            canvas: Canvas::new(Self::WIDTH, Self::HEIGHT),
+            view: HeatmapView::AddressSpace,
+        }
+    }
+
+    pub fn update(&mut self, gb: &Gameboy) {
+        let (rom_banks, ram_banks) = match gb.debugger.heatmap().as_ref() {
+            Some(heatmap) => (
+                heatmap.rom().len().div_ceil(memory::cartridge::ROM_BANK_N.size),
+                heatmap.ram().len() / memory::cartridge::EXTERNAL_RAM.size,
+            ),
+            None => (0, 0),
+        };
+        for key in self.screen.get_keys_pressed() {
+            self.view = match (key, self.view) {
+                (Key::A, _) => HeatmapView::AddressSpace,
+                (Key::R, _) => HeatmapView::RomBank(gb.cartridge.rom_bank(memory::cartridge::ROM_BANK_N.begin)),
+                (Key::E, _) => HeatmapView::RamBank(gb.cartridge.ram_bank()),
+                (Key::Left, HeatmapView::RomBank(bank)) => HeatmapView::RomBank(bank.saturating_sub(1)),
+                (Key::Right, HeatmapView::RomBank(bank)) => {
+                    HeatmapView::RomBank(std::cmp::min(bank + 1, rom_banks.saturating_sub(1)))
+                }
+                (Key::Left, HeatmapView::RamBank(bank)) => HeatmapView::RamBank(bank.saturating_sub(1)),
+                (Key::Right, HeatmapView::RamBank(bank)) => {
+                    HeatmapView::RamBank(std::cmp::min(bank + 1, ram_banks.saturating_sub(1)))
+                }
+                (_, view) => view,
+            };
+        }
+        self.draw(gb);
+        self.screen.set_frame_buffer(self.canvas.buffer());
+        self.screen.update();
+    }
+
+    fn color(heat: &Heat) -> u32 {
+        let [read, write, execute] = *heat;
+        let channel = |heat: u8, shift: u32| std::cmp::max(heat as u32, (Self::EMPTY >> shift) & 0xFF) << shift;
+        channel(write, 16) | channel(read, 8) | channel(execute, 0)
+    }
+
+    /// (bytes per row, zoom, address of the first byte, title)
+    fn layout(&self) -> (usize, usize, usize, String) {
+        match self.view {
+            HeatmapView::AddressSpace => (Self::PLOT_SIZE, 1, 0, "address space".to_string()),
+            HeatmapView::RomBank(bank) => (
+                Self::BANK_ROW_BYTES,
+                Self::BANK_ZOOM,
+                if bank == 0 {
+                    0
+                } else {
+                    memory::cartridge::ROM_BANK_N.begin as usize
+                },
+                format!("rom bank {:#04X}", bank),
+            ),
+            HeatmapView::RamBank(bank) => (
+                Self::BANK_ROW_BYTES,
+                Self::BANK_ZOOM,
+                memory::cartridge::EXTERNAL_RAM.begin as usize,
+                format!("ram bank {:#04X}", bank),
+            ),
+        }
+    }
+
+    fn draw(&mut self, gb: &Gameboy) {
+        self.canvas.clear(BACKGROUND);
+        let heatmap = gb.debugger.heatmap();
+        let heatmap = match heatmap.as_ref() {
+            Some(heatmap) => heatmap,
+            None => return,
+        };
+        let heat = match self.view {
+            HeatmapView::AddressSpace => heatmap.address_space(),
+            HeatmapView::RomBank(bank) => {
+                let size = memory::cartridge::ROM_BANK_N.size;
+                heatmap.rom().get(bank * size..(bank + 1) * size).unwrap_or(&[])
+            }
+            HeatmapView::RamBank(bank) => {
+                let size = memory::cartridge::EXTERNAL_RAM.size;
+                heatmap.ram().get(bank * size..(bank + 1) * size).unwrap_or(&[])
+            }
+        };
+        let (row_bytes, zoom, base, title) = self.layout();
+        self.canvas.draw_text(
+            Self::MARGIN,
+            Self::MARGIN,
+            &format!("{:<14} a: all  r: rom  e: ram  arrows: bank", title),
+            TEXT,
+        );
+        if heat.is_empty() {
+            self.canvas
+                .draw_text(Self::PLOT_X, Self::PLOT_Y, "bank not accessed yet", TEXT_DIM);
+            return;
+        }
+
+        for (i, byte) in heat.iter().enumerate() {
+            self.canvas.fill_rect(
+                Self::PLOT_X + (i % row_bytes) * zoom,
+                Self::PLOT_Y + (i / row_bytes) * zoom,
+                zoom,
+                zoom,
+                Self::color(byte),
+            );
+        }
+        let rows = heat.len() / row_bytes;
+        for row in (0..rows).step_by(Self::LABEL_ROWS / zoom) {
+            self.canvas.draw_text(
+                Self::MARGIN,
+                Self::PLOT_Y + row * zoom,
+                &format!("${:04X}", base + row * row_bytes),
+                TEXT_DIM,
+            );
+        }
+
+        let y = Self::PLOT_Y + Self::PLOT_SIZE + 2;
+        let mut x = Self::PLOT_X;
+        for (name, color) in [("read", 0x0000FF00), ("write", 0x00FF0000), ("execute", 0x004060FF)] {
+            x = self.canvas.draw_text(x, y, name, color) + Canvas::CHAR_WIDTH;
+        }
+        let hovered = self.screen.get_mouse_pos().and_then(|(x, y)| {
+            let column = x.checked_sub(Self::PLOT_X)? / zoom;
+            let row = y.checked_sub(Self::PLOT_Y)? / zoom;
+            (column < row_bytes && row < rows).then_some(row * row_bytes + column)
+        });
+        if let Some(index) = hovered {
+            let [read, write, execute] = heat[index];
+            self.canvas.draw_text(
+                Self::PLOT_X,
+                y + Self::LINE_HEIGHT,
+                &format!(
+                    "${:04X}  read {:>3}  write {:>3}  execute {:>3}",
+                    base + index,
+                    read,
+                    write,
+                    execute
+                ),
+                TEXT,
+            );
+        }
+    }
+}
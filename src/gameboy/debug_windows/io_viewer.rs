@@ -0,0 +1,480 @@
+use crate::{
+    gameboy::{memory, ppu::palette::PaletteData, Gameboy},
+    screen::{canvas::Canvas, MonochromeColor, Screen},
+};
+
+use super::{ACCENT, BACKGROUND, TEXT, TEXT_DIM};
+
+/// Decoded view of the video, timer, interrupt and sound registers, refreshed once per frame.
+/// Flags that are set are drawn bright, cleared flags are dimmed.
+pub struct IoViewer {
+    screen: Screen,
+    canvas: Canvas,
+}
+
+impl IoViewer {
+    const MARGIN: usize = 4;
+    const LINE_HEIGHT: usize = Canvas::CHAR_HEIGHT + 2;
+    const ROWS: usize = 26;
+    const LEFT_COLUMNS: usize = 44;
+    const RIGHT_COLUMNS: usize = 52;
+    const INDENT: usize = 2 * Canvas::CHAR_WIDTH;
+    const WIDTH: usize = (Self::LEFT_COLUMNS + Self::RIGHT_COLUMNS) * Canvas::CHAR_WIDTH + 2 * Self::MARGIN;
+    const HEIGHT: usize = Self::ROWS * Self::LINE_HEIGHT + 2 * Self::MARGIN;
+    const CPU_CLOCK: u32 = 4194304;
+
+    pub fn new() -> Self {
+        let mut screen = Screen::new(Self::HEIGHT, Self::WIDTH, minifb::Scale::X2);
+        screen.set_title("IO registers");
+        Self {
+            screen,
+            canvas: Canvas::new(Self::WIDTH, Self::HEIGHT),
+        }
+    }
+
+    pub fn update(&mut self, gb: &Gameboy) {
+        self.canvas.clear(BACKGROUND);
+        self.draw_video(gb, Self::MARGIN);
+        self.draw_sound(gb, Self::MARGIN + Self::LEFT_COLUMNS * Canvas::CHAR_WIDTH);
+        self.screen.set_frame_buffer(self.canvas.buffer());
+        self.screen.update();
+    }
+
+    fn row_y(row: usize) -> usize {
+        Self::MARGIN + row * Self::LINE_HEIGHT
+    }
+
+    /// draws the flag names separated by a space, returns the x position after the last one
+    fn draw_flags(&mut self, x: usize, y: usize, flags: &[(&str, bool)]) -> usize {
+        let mut x = x;
+        for (name, set) in flags {
+            x = self.canvas.draw_text(x, y, name, if *set { TEXT } else { TEXT_DIM }) + Canvas::CHAR_WIDTH;
+        }
+        x
+    }
+
+    fn draw_palette(&mut self, x: usize, y: usize, name: &str, palette: &PaletteData) {
+        let value = u8::from(palette.clone());
+        let x = self
+            .canvas
+            .draw_text(x, y, &format!("{:<4} ${:02X}", name, value), ACCENT);
+        for (i, color_id) in palette.color_map.iter().enumerate() {
+            let swatch_x = x + Canvas::CHAR_WIDTH + i * 2 * Canvas::CHAR_WIDTH;
+            let color = match color_id {
+                0 => MonochromeColor::White as u32,
+                1 => MonochromeColor::LightGray as u32,
+                2 => MonochromeColor::DarkGray as u32,
+                _ => MonochromeColor::Black as u32,
+            };
+            self.canvas
+                .fill_rect(swatch_x, y - 1, 2 * Canvas::CHAR_WIDTH - 2, Canvas::CHAR_HEIGHT, color);
+            self.canvas.draw_rect(
+                swatch_x,
+                y - 1,
+                2 * Canvas::CHAR_WIDTH - 2,
+                Canvas::CHAR_HEIGHT,
+                TEXT_DIM,
+            );
+        }
+    }
+
+    fn draw_video(&mut self, gb: &Gameboy, x: usize) {
+        let lcdc = gb.ppu.get_lcdc().clone();
+        let stat = gb.ppu.get_stat().clone();
+        let map_area = |area_9c00: bool| if area_9c00 { "$9C00" } else { "$9800" };
+
+        let mut row = 0;
+        let y = Self::row_y(row);
+        let end = self
+            .canvas
+            .draw_text(x, y, &format!("LCDC ${:02X}", u8::from(lcdc.clone())), ACCENT);
+        self.draw_flags(
+            end + Canvas::CHAR_WIDTH,
+            y,
+            &[
+                ("lcd", lcdc.lcd_ppu_enable),
+                ("bg/win", lcdc.bg_and_window_enable),
+                ("window", lcdc.window_enable),
+                ("obj", lcdc.obj_enable),
+            ],
+        );
+        row += 1;
+        self.canvas.draw_text(
+            x + Self::INDENT,
+            Self::row_y(row),
+            &format!(
+                "obj 8x{}  bg map {}  win map {}",
+                if lcdc.obj_size { 16 } else { 8 },
+                map_area(lcdc.bg_tile_map_area),
+                map_area(lcdc.window_tile_map_area)
+            ),
+            TEXT,
+        );
+        row += 1;
+        self.canvas.draw_text(
+            x + Self::INDENT,
+            Self::row_y(row),
+            &format!(
+                "tile data {}",
+                if lcdc.bg_and_window_tile_data_area {
+                    "$8000"
+                } else {
+                    "$8800"
+                }
+            ),
+            TEXT,
+        );
+
+        row += 1;
+        let y = Self::row_y(row);
+        let end = self
+            .canvas
+            .draw_text(x, y, &format!("STAT ${:02X}", u8::from(stat.clone())), ACCENT);
+        self.canvas.draw_text(
+            end + Canvas::CHAR_WIDTH,
+            y,
+            &format!("mode {} {:?}", stat.mode_flag as u8, stat.mode_flag),
+            TEXT,
+        );
+        row += 1;
+        let end = self.draw_flags(x + Self::INDENT, Self::row_y(row), &[("LYC=LY", stat.lyc_flag)]);
+        let end = self
+            .canvas
+            .draw_text(end + Canvas::CHAR_WIDTH, Self::row_y(row), "int:", TEXT_DIM);
+        self.draw_flags(
+            end + Canvas::CHAR_WIDTH,
+            Self::row_y(row),
+            &[
+                ("lyc", stat.lyc_interrupt_enable),
+                ("oam", stat.mode2_oam_interrupt_enable),
+                ("vblank", stat.mode1_vblank_interrupt_enable),
+                ("hblank", stat.mode0_hblank_interrupt_enable),
+            ],
+        );
+
+        let (scx, scy) = gb.ppu.get_scroll();
+        let (wx, wy) = gb.ppu.get_window_position();
+        row += 1;
+        self.canvas.draw_text(
+            x,
+            Self::row_y(row),
+            &format!("LY {:>3}  LYC {:>3}", gb.ppu.get_ly(), gb.ppu.get_lyc()),
+            TEXT,
+        );
+        row += 1;
+        self.canvas.draw_text(
+            x,
+            Self::row_y(row),
+            &format!("SCX {:>3}  SCY {:>3}  WX {:>3}  WY {:>3}", scx, scy, wx, wy),
+            TEXT,
+        );
+
+        let (bgp, obp0, obp1) = gb.ppu.get_palettes();
+        row += 2;
+        self.draw_palette(x, Self::row_y(row), "BGP", bgp);
+        row += 1;
+        self.draw_palette(x, Self::row_y(row), "OBP0", obp0);
+        row += 1;
+        self.draw_palette(x, Self::row_y(row), "OBP1", obp1);
+
+        let tac = gb.timer.get_tac().clone();
+        row += 2;
+        self.canvas.draw_text(
+            x,
+            Self::row_y(row),
+            &format!(
+                "DIV ${:02X}  TIMA ${:02X}  TMA ${:02X}",
+                gb.read8_unlocked(memory::timer::DIV),
+                gb.read8_unlocked(memory::timer::TIMA),
+                gb.read8_unlocked(memory::timer::TMA)
+            ),
+            TEXT,
+        );
+        row += 1;
+        let y = Self::row_y(row);
+        let end = self
+            .canvas
+            .draw_text(x, y, &format!("TAC  ${:02X}", u8::from(tac.clone())), ACCENT);
+        let end = self.draw_flags(end + Canvas::CHAR_WIDTH, y, &[("enabled", tac.enable)]);
+        self.canvas
+            .draw_text(end, y, &format!("{} Hz", Self::CPU_CLOCK / tac.prescaler as u32), TEXT);
+
+        row += 2;
+        let y = Self::row_y(row);
+        let end = self.canvas.draw_text(x, y, "IME", ACCENT);
+        self.draw_flags(
+            end + Canvas::CHAR_WIDTH,
+            y,
+            &[("enabled", gb.cpu.interrupt_master_enable)],
+        );
+        for (name, register) in [("IE", &gb.cpu.ie_register), ("IF", &gb.cpu.if_register)] {
+            row += 1;
+            let y = Self::row_y(row);
+            let end = self.canvas.draw_text(
+                x,
+                y,
+                &format!("{:<4} ${:02X}", name, u8::from(register.clone())),
+                ACCENT,
+            );
+            self.draw_flags(
+                end + Canvas::CHAR_WIDTH,
+                y,
+                &[
+                    ("vblank", register.vblank),
+                    ("stat", register.lcd_stat),
+                    ("timer", register.timer),
+                    ("serial", register.serial),
+                    ("joypad", register.joypad),
+                ],
+            );
+        }
+    }
+
+    fn draw_sound(&mut self, gb: &Gameboy, x: usize) {
+        let read = |addr: u16| gb.read8_unlocked(addr);
+        let nr52 = read(memory::apu::NR52);
+        let nr51 = read(memory::apu::NR51);
+        let nr50 = read(memory::apu::NR50);
+
+        let mut row = 0;
+        let y = Self::row_y(row);
+        let end = self.canvas.draw_text(x, y, &format!("NR52 ${:02X}", nr52), ACCENT);
+        self.draw_flags(
+            end + Canvas::CHAR_WIDTH,
+            y,
+            &[
+                ("apu", nr52 & 0x80 != 0),
+                ("ch1", nr52 & 0x01 != 0),
+                ("ch2", nr52 & 0x02 != 0),
+                ("ch3", nr52 & 0x04 != 0),
+                ("ch4", nr52 & 0x08 != 0),
+            ],
+        );
+        row += 1;
+        let y = Self::row_y(row);
+        let end = self.canvas.draw_text(x, y, &format!("NR50 ${:02X}", nr50), ACCENT);
+        self.canvas.draw_text(
+            end + Canvas::CHAR_WIDTH,
+            y,
+            &format!("volume left {}  right {}", (nr50 >> 4) & 0b111, nr50 & 0b111),
+            TEXT,
+        );
+        row += 1;
+        let y = Self::row_y(row);
+        let end = self.canvas.draw_text(x, y, &format!("NR51 ${:02X}", nr51), ACCENT);
+        let end = self.canvas.draw_text(end + Canvas::CHAR_WIDTH, y, "left", TEXT_DIM);
+        let end = self.draw_flags(
+            end + Canvas::CHAR_WIDTH,
+            y,
+            &[
+                ("1", nr51 & 0x10 != 0),
+                ("2", nr51 & 0x20 != 0),
+                ("3", nr51 & 0x40 != 0),
+                ("4", nr51 & 0x80 != 0),
+            ],
+        );
+        let end = self.canvas.draw_text(end + Canvas::CHAR_WIDTH, y, "right", TEXT_DIM);
+        self.draw_flags(
+            end + Canvas::CHAR_WIDTH,
+            y,
+            &[
+                ("1", nr51 & 0x01 != 0),
+                ("2", nr51 & 0x02 != 0),
+                ("3", nr51 & 0x04 != 0),
+                ("4", nr51 & 0x08 != 0),
+            ],
+        );
+
+        // channel 1 and 2
+        let pulse_channels = [
+            (
+                "CH1 pulse with sweep",
+                Some(memory::apu::NR10),
+                [
+                    memory::apu::NR11,
+                    memory::apu::NR12,
+                    memory::apu::NR13,
+                    memory::apu::NR14,
+                ],
+                "NR1",
+            ),
+            (
+                "CH2 pulse",
+                None,
+                [
+                    memory::apu::NR21,
+                    memory::apu::NR22,
+                    memory::apu::NR23,
+                    memory::apu::NR24,
+                ],
+                "NR2",
+            ),
+        ];
+        for (title, sweep, registers, prefix) in pulse_channels {
+            row += 2;
+            self.canvas.draw_text(x, Self::row_y(row), title, ACCENT);
+            if let Some(addr) = sweep {
+                let value = read(addr);
+                row += 1;
+                self.draw_register(
+                    x,
+                    row,
+                    &format!("{}0", prefix),
+                    value,
+                    &format!(
+                        "sweep pace {} {} step {}",
+                        (value >> 4) & 0b111,
+                        if value & 0x08 != 0 { "down" } else { "up" },
+                        value & 0b111
+                    ),
+                );
+            }
+            let [length, envelope, period_low, period_high] = registers.map(read);
+            row += 1;
+            self.draw_register(
+                x,
+                row,
+                &format!("{}1", prefix),
+                length,
+                &format!("duty {}  length {}", Self::duty(length), length & 0b11_1111),
+            );
+            row += 1;
+            self.draw_register(x, row, &format!("{}2", prefix), envelope, &Self::envelope(envelope));
+            row += 1;
+            self.draw_period(x, row, prefix, period_low, period_high, Self::pulse_frequency);
+        }
+
+        row += 2;
+        self.canvas.draw_text(x, Self::row_y(row), "CH3 wave", ACCENT);
+        let nr30 = read(memory::apu::NR30);
+        row += 1;
+        let end = self.draw_register(x, row, "NR30", nr30, "");
+        self.draw_flags(end, Self::row_y(row), &[("dac", nr30 & 0x80 != 0)]);
+        let nr31 = read(memory::apu::NR31);
+        row += 1;
+        self.draw_register(x, row, "NR31", nr31, &format!("length {}", nr31));
+        let nr32 = read(memory::apu::NR32);
+        row += 1;
+        self.draw_register(
+            x,
+            row,
+            "NR32",
+            nr32,
+            &format!(
+                "volume {}",
+                match (nr32 >> 5) & 0b11 {
+                    0 => "mute",
+                    1 => "100%",
+                    2 => "50%",
+                    _ => "25%",
+                }
+            ),
+        );
+        row += 1;
+        self.draw_period(
+            x,
+            row,
+            "NR3",
+            read(memory::apu::NR33),
+            read(memory::apu::NR34),
+            Self::wave_frequency,
+        );
+
+        row += 2;
+        self.canvas.draw_text(x, Self::row_y(row), "CH4 noise", ACCENT);
+        let nr41 = read(memory::apu::NR41);
+        row += 1;
+        self.draw_register(x, row, "NR41", nr41, &format!("length {}", nr41 & 0b11_1111));
+        let nr42 = read(memory::apu::NR42);
+        row += 1;
+        self.draw_register(x, row, "NR42", nr42, &Self::envelope(nr42));
+        let nr43 = read(memory::apu::NR43);
+        row += 1;
+        self.draw_register(
+            x,
+            row,
+            "NR43",
+            nr43,
+            &format!(
+                "shift {}  {} bit  divider {}  {} Hz",
+                nr43 >> 4,
+                if nr43 & 0x08 != 0 { 7 } else { 15 },
+                nr43 & 0b111,
+                Self::noise_frequency(nr43)
+            ),
+        );
+        let nr44 = read(memory::apu::NR44);
+        row += 1;
+        let end = self.draw_register(x, row, "NR44", nr44, "");
+        self.draw_flags(end, Self::row_y(row), &[("length", nr44 & 0x40 != 0)]);
+    }
+
+    /// indented register name and value followed by its decoded fields,
+    /// returns the x position after the text
+    fn draw_register(&mut self, x: usize, row: usize, name: &str, value: u8, decoded: &str) -> usize {
+        let y = Self::row_y(row);
+        let end = self
+            .canvas
+            .draw_text(x + Self::INDENT, y, &format!("{} ${:02X}", name, value), TEXT_DIM);
+        self.canvas.draw_text(end + Canvas::CHAR_WIDTH, y, decoded, TEXT) + Canvas::CHAR_WIDTH
+    }
+
+    /// NRx3 and NRx4 share the 11 bit period
+    fn draw_period(&mut self, x: usize, row: usize, prefix: &str, low: u8, high: u8, frequency: fn(u16) -> u32) {
+        let period = ((high as u16 & 0b111) << 8) | low as u16;
+        let end = self.draw_register(
+            x,
+            row,
+            &format!("{}3/4 ${:02X}", prefix, low),
+            high,
+            &format!("period ${:03X}  {} Hz", period, frequency(period)),
+        );
+        self.draw_flags(end, Self::row_y(row), &[("length", high & 0x40 != 0)]);
+    }
+
+    fn duty(nrx1: u8) -> &'static str {
+        ["12.5%", "25%", "50%", "75%"][(nrx1 >> 6) as usize]
+    }
+
+    fn envelope(nrx2: u8) -> String {
+        format!(
+            "volume {} {} pace {}",
+            nrx2 >> 4,
+            if nrx2 & 0x08 != 0 { "up" } else { "down" },
+            nrx2 & 0b111
+        )
+    }
+
+    fn pulse_frequency(period: u16) -> u32 {
+        131072 / (2048 - period as u32)
+    }
+
+    fn wave_frequency(period: u16) -> u32 {
+        65536 / (2048 - period as u32)
+    }
+
+    /// lfsr clock, a divider of 0 counts as 0.5
+    fn noise_frequency(nr43: u8) -> u32 {
+        let divider = match nr43 & 0b111 {
+            0 => 1,
+            divider => 2 * divider as u32,
+        };
+        (524288 / divider) >> (nr43 >> 4)
+    }
+}
+
+impl Default for IoViewer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[test]
+fn io_decode_test() {
+    assert_eq!(IoViewer::duty(0x80), "50%");
+    assert_eq!(IoViewer::envelope(0xF3), "volume 15 down pace 3");
+    assert_eq!(IoViewer::pulse_frequency(0x783), 1048);
+    assert_eq!(IoViewer::wave_frequency(0x7FF), 65536);
+    assert_eq!(IoViewer::noise_frequency(0x00), 524288);
+    assert_eq!(IoViewer::noise_frequency(0x21), 65536);
+}
@@ -0,0 +1,247 @@
+use minifb::Key;
+
+use crate::{
+    gameboy::{memory, Gameboy},
+    screen::{canvas::Canvas, Screen},
+};
+
+use super::{ACCENT, BACKGROUND, HIGHLIGHT, TEXT, TEXT_DIM};
+
+#[derive(Clone, Copy, PartialEq)]
+enum MapSelection {
+    /// map selected by LCDC bit 3
+    Background,
+    /// map selected by LCDC bit 6
+    Window,
+    Fixed9800,
+    Fixed9C00,
+}
+
+/// 32x32 tile map with the visible screen area drawn on top.
+/// `B` and `W` follow the background and window map selected in LCDC, `M` switches between the
+/// two maps directly and `T` between the two tile data areas, escape follows LCDC again.
+pub struct MapViewer {
+    screen: Screen,
+    canvas: Canvas,
+    selection: MapSelection,
+    // None follows LCDC bit 4
+    tile_data_area_8000: Option<bool>,
+}
+
+impl MapViewer {
+    const MAP_SIZE: usize = 256;
+    const SCREEN_WIDTH: usize = 160;
+    const SCREEN_HEIGHT: usize = 144;
+    const MARGIN: usize = 4;
+    const LINE_HEIGHT: usize = Canvas::CHAR_HEIGHT + 2;
+    const WIDTH: usize = Self::MAP_SIZE + 2 * Self::MARGIN;
+    const HEIGHT: usize = Self::MAP_SIZE + 3 * Self::LINE_HEIGHT + 3 * Self::MARGIN;
+
+    pub fn new() -> Self {
+        let mut screen = Screen::new(Self::HEIGHT, Self::WIDTH, minifb::Scale::X2);
+        screen.set_title("Tile map");
+        Self {
+            screen,
+            canvas: Canvas::new(Self::WIDTH, Self::HEIGHT),
+            selection: MapSelection::Background,
+            tile_data_area_8000: None,
+        }
+    }
+
+    pub fn update(&mut self, gb: &mut Gameboy) {
+        let lcdc = gb.ppu.get_lcdc().clone();
+        for key in self.screen.get_keys_pressed() {
+            match key {
+                Key::B => self.selection = MapSelection::Background,
+                Key::W => self.selection = MapSelection::Window,
+                Key::M => {
+                    self.selection = if self.map_area_9c00(gb) {
+                        MapSelection::Fixed9800
+                    } else {
+                        MapSelection::Fixed9C00
+                    }
+                }
+                Key::T => {
+                    self.tile_data_area_8000 =
+                        Some(!self.tile_data_area_8000.unwrap_or(lcdc.bg_and_window_tile_data_area))
+                }
+                Key::Escape => {
+                    self.selection = MapSelection::Background;
+                    self.tile_data_area_8000 = None;
+                }
+                _ => {}
+            }
+        }
+
+        gb.ppu.process_tile_data();
+        self.draw(gb);
+        self.screen.set_frame_buffer(self.canvas.buffer());
+        self.screen.update();
+    }
+
+    fn map_area_9c00(&self, gb: &Gameboy) -> bool {
+        let lcdc = gb.ppu.get_lcdc();
+        match self.selection {
+            MapSelection::Background => lcdc.bg_tile_map_area,
+            MapSelection::Window => lcdc.window_tile_map_area,
+            MapSelection::Fixed9800 => false,
+            MapSelection::Fixed9C00 => true,
+        }
+    }
+
+    /// address of the first byte of a tile, the $8800 area uses signed tile ids based at $9000
+    fn tile_data_addr(tile_id: u8, tile_data_area_8000: bool) -> u16 {
+        if tile_data_area_8000 {
+            memory::ppu::TILE_DATA_AREA_8000.begin + tile_id as u16 * 16
+        } else {
+            (0x9000 + tile_id as i8 as i32 * 16) as u16
+        }
+    }
+
+    /// outline of a rectangle on the map, edges that leave the map continue on the opposite side
+    fn draw_wrapped_rect(&mut self, x: usize, y: usize, width: usize, height: usize, color: u32) {
+        let wrap = |value: usize| value % Self::MAP_SIZE;
+        for i in 0..width {
+            self.canvas
+                .set_pixel(Self::MARGIN + wrap(x + i), Self::MARGIN + wrap(y), color);
+            self.canvas
+                .set_pixel(Self::MARGIN + wrap(x + i), Self::MARGIN + wrap(y + height - 1), color);
+        }
+        for i in 0..height {
+            self.canvas
+                .set_pixel(Self::MARGIN + wrap(x), Self::MARGIN + wrap(y + i), color);
+            self.canvas
+                .set_pixel(Self::MARGIN + wrap(x + width - 1), Self::MARGIN + wrap(y + i), color);
+        }
+    }
+
+    fn draw(&mut self, gb: &Gameboy) {
+        self.canvas.clear(BACKGROUND);
+
+        let lcdc = gb.ppu.get_lcdc().clone();
+        let map_area_9c00 = self.map_area_9c00(gb);
+        let tile_data_area_8000 = self.tile_data_area_8000.unwrap_or(lcdc.bg_and_window_tile_data_area);
+        let map = gb.ppu.get_map_frame_buffer(map_area_9c00, tile_data_area_8000);
+        self.canvas.blit(Self::MARGIN, Self::MARGIN, &map, Self::MAP_SIZE, 1);
+
+        let (scx, scy) = gb.ppu.get_scroll();
+        let (wx, wy) = gb.ppu.get_window_position();
+        // the window starts at WX-7 on screen and always shows the map from its top left corner
+        let window_x = (wx as usize).saturating_sub(7);
+        let window_y = wy as usize;
+        let window_visible = lcdc.window_enable && window_x < Self::SCREEN_WIDTH && window_y < Self::SCREEN_HEIGHT;
+        if self.selection == MapSelection::Window {
+            if window_visible {
+                self.draw_wrapped_rect(
+                    0,
+                    0,
+                    Self::SCREEN_WIDTH - window_x,
+                    Self::SCREEN_HEIGHT - window_y,
+                    HIGHLIGHT,
+                );
+            }
+        } else {
+            self.draw_wrapped_rect(
+                scx as usize,
+                scy as usize,
+                Self::SCREEN_WIDTH,
+                Self::SCREEN_HEIGHT,
+                HIGHLIGHT,
+            );
+            if window_visible {
+                let origin_x = (scx as usize + window_x) % Self::MAP_SIZE;
+                let origin_y = (scy as usize + window_y) % Self::MAP_SIZE;
+                self.draw_wrapped_rect(
+                    origin_x + Self::MAP_SIZE - 2,
+                    origin_y + Self::MAP_SIZE - 2,
+                    5,
+                    5,
+                    ACCENT,
+                );
+            }
+        }
+
+        let tile_map_start = if map_area_9c00 {
+            memory::ppu::TILE_MAP_AREA_9C00.begin
+        } else {
+            memory::ppu::TILE_MAP_AREA_9800.begin
+        };
+        let source = match self.selection {
+            MapSelection::Background => "BG (LCDC)",
+            MapSelection::Window => "window (LCDC)",
+            _ => "fixed",
+        };
+        let mut y = Self::MAP_SIZE + 2 * Self::MARGIN;
+        self.canvas.draw_text(
+            Self::MARGIN,
+            y,
+            &format!(
+                "map ${:04X} {}  data ${:04X}{}",
+                tile_map_start,
+                source,
+                if tile_data_area_8000 { 0x8000 } else { 0x8800 },
+                if self.tile_data_area_8000.is_none() {
+                    " (LCDC)"
+                } else {
+                    ""
+                }
+            ),
+            TEXT,
+        );
+        y += Self::LINE_HEIGHT;
+        self.canvas.draw_text(
+            Self::MARGIN,
+            y,
+            &format!("SCX {:>3} SCY {:>3}  WX {:>3} WY {:>3}", scx, scy, wx, wy),
+            TEXT_DIM,
+        );
+        y += Self::LINE_HEIGHT;
+
+        let hovered = self
+            .screen
+            .get_mouse_pos()
+            .and_then(|(x, y)| Some((x.checked_sub(Self::MARGIN)?, y.checked_sub(Self::MARGIN)?)))
+            .filter(|(x, y)| *x < Self::MAP_SIZE && *y < Self::MAP_SIZE);
+        match hovered {
+            Some((x, y_pos)) => {
+                let (tile_x, tile_y) = (x / 8, y_pos / 8);
+                let map_addr = tile_map_start + (tile_y * 32 + tile_x) as u16;
+                let tile_id = gb.read8_unlocked(map_addr);
+                self.canvas
+                    .draw_rect(Self::MARGIN + tile_x * 8, Self::MARGIN + tile_y * 8, 8, 8, ACCENT);
+                self.canvas.draw_text(
+                    Self::MARGIN,
+                    y,
+                    &format!(
+                        "tile {:>2},{:>2} @ ${:04X}  id ${:02X}  data ${:04X}",
+                        tile_x,
+                        tile_y,
+                        map_addr,
+                        tile_id,
+                        Self::tile_data_addr(tile_id, tile_data_area_8000)
+                    ),
+                    ACCENT,
+                );
+            }
+            None => {
+                self.canvas
+                    .draw_text(Self::MARGIN, y, "b/w: lcdc map  m: map  t: tile data", TEXT_DIM);
+            }
+        }
+    }
+}
+
+impl Default for MapViewer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[test]
+fn tile_data_addr_test() {
+    assert_eq!(MapViewer::tile_data_addr(0x00, true), 0x8000);
+    assert_eq!(MapViewer::tile_data_addr(0xFF, true), 0x8FF0);
+    assert_eq!(MapViewer::tile_data_addr(0x00, false), 0x9000);
+    assert_eq!(MapViewer::tile_data_addr(0x7F, false), 0x97F0);
+    assert_eq!(MapViewer::tile_data_addr(0x80, false), 0x8800);
+}
@@ -0,0 +1,277 @@
+use minifb::Key;
+
+use crate::{
+    gameboy::{debugger::Debugger, memory, Gameboy},
+    screen::{canvas::Canvas, Screen},
+};
+
+use super::{blend, hex_digit, ACCENT, BACKGROUND, HIGHLIGHT, SELECTION, TEXT, TEXT_DIM};
+
+/// Hex and ascii view of the whole address space.
+/// Click a byte or move the cursor with the arrow keys and type two hex digits to write it,
+/// writes go through the bus just like cpu writes. `G` followed by four hex digits and enter jumps to an address.
+pub struct MemoryViewer {
+    screen: Screen,
+    canvas: Canvas,
+    first_row: usize,
+    cursor: u16,
+    // high nibble of a byte that is being typed
+    editing: Option<u8>,
+    goto: Option<String>,
+}
+
+impl MemoryViewer {
+    const ROWS: usize = 32;
+    const BYTES_PER_ROW: usize = 16;
+    const TOTAL_ROWS: usize = 0x10000 / Self::BYTES_PER_ROW;
+    const LINE_HEIGHT: usize = Canvas::CHAR_HEIGHT + 2;
+    const MARGIN: usize = 4;
+    // column positions in characters
+    const HEX_COLUMN: usize = 6;
+    const ASCII_COLUMN: usize = Self::HEX_COLUMN + Self::BYTES_PER_ROW * 3 + 1;
+    const REGION_COLUMN: usize = Self::ASCII_COLUMN + Self::BYTES_PER_ROW + 2;
+    const COLUMNS: usize = Self::REGION_COLUMN + 6;
+    const WIDTH: usize = Self::COLUMNS * Canvas::CHAR_WIDTH + 2 * Self::MARGIN;
+    const HEIGHT: usize = (Self::ROWS + 3) * Self::LINE_HEIGHT + 2 * Self::MARGIN;
+
+    pub fn new(gb: &mut Gameboy) -> Self {
+        gb.debugger.track_writes();
+        let mut screen = Screen::new(Self::HEIGHT, Self::WIDTH, minifb::Scale::X2);
+        screen.set_title("Memory");
+        Self {
+            screen,
+            canvas: Canvas::new(Self::WIDTH, Self::HEIGHT),
+            first_row: 0,
+            cursor: 0,
+            editing: None,
+            goto: None,
+        }
+    }
+
+    pub fn update(&mut self, gb: &mut Gameboy) {
+        self.handle_input(gb);
+        self.draw(gb);
+        gb.debugger.age_recent_writes();
+        self.screen.set_frame_buffer(self.canvas.buffer());
+        self.screen.update();
+    }
+
+    fn region(addr: u16) -> &'static str {
+        if addr <= memory::cartridge::ROM_BANK_0.end {
+            "ROM0"
+        } else if addr <= memory::cartridge::ROM_BANK_N.end {
+            "ROMX"
+        } else if addr <= memory::ppu::VRAM.end {
+            "VRAM"
+        } else if addr <= memory::cartridge::EXTERNAL_RAM.end {
+            "SRAM"
+        } else if addr <= memory::WRAM.end {
+            "WRAM"
+        } else if addr <= memory::ECHO_RAM.end {
+            "ECHO"
+        } else if addr <= memory::ppu::OAM.end {
+            "OAM"
+        } else if addr < memory::IO_REGISTERS.begin {
+            "-"
+        } else if addr <= memory::IO_REGISTERS.end {
+            "IO"
+        } else if addr <= memory::HRAM.end {
+            "HRAM"
+        } else {
+            "IE"
+        }
+    }
+
+    fn handle_input(&mut self, gb: &mut Gameboy) {
+        let scroll = self.screen.get_scroll();
+        if scroll > 0. {
+            self.first_row = self.first_row.saturating_sub(3);
+        } else if scroll < 0. {
+            self.first_row = std::cmp::min(self.first_row + 3, Self::TOTAL_ROWS - Self::ROWS);
+        }
+
+        if let Some((x, y)) = self.screen.get_mouse_click() {
+            if let Some(addr) = self.addr_at(x, y) {
+                self.cursor = addr;
+                self.editing = None;
+            }
+        }
+
+        for key in self.screen.get_keys_pressed() {
+            if let Some(goto) = self.goto.as_mut() {
+                match key {
+                    Key::Enter | Key::NumPadEnter => {
+                        if let Some(addr) = crate::gameboy::debugger::parse_u16(goto) {
+                            self.move_cursor_to(addr);
+                        }
+                        self.goto = None;
+                    }
+                    Key::Escape => self.goto = None,
+                    Key::Backspace => {
+                        goto.pop();
+                    }
+                    key => {
+                        if let Some(digit) = hex_digit(key) {
+                            if goto.len() < 4 {
+                                goto.push(char::from_digit(digit as u32, 16).unwrap());
+                            }
+                        }
+                    }
+                }
+                continue;
+            }
+            match key {
+                Key::Up => self.move_cursor_to(self.cursor.wrapping_sub(Self::BYTES_PER_ROW as u16)),
+                Key::Down => self.move_cursor_to(self.cursor.wrapping_add(Self::BYTES_PER_ROW as u16)),
+                Key::Left => self.move_cursor_to(self.cursor.wrapping_sub(1)),
+                Key::Right => self.move_cursor_to(self.cursor.wrapping_add(1)),
+                Key::PageUp => {
+                    self.move_cursor_to(self.cursor.saturating_sub((Self::BYTES_PER_ROW * Self::ROWS) as u16))
+                }
+                Key::PageDown => {
+                    self.move_cursor_to(self.cursor.saturating_add((Self::BYTES_PER_ROW * Self::ROWS) as u16))
+                }
+                Key::Home => self.move_cursor_to(0x0000),
+                Key::End => self.move_cursor_to(0xFFFF),
+                Key::G => self.goto = Some(String::new()),
+                Key::Escape => self.editing = None,
+                key => {
+                    if let Some(digit) = hex_digit(key) {
+                        match self.editing.take() {
+                            None => self.editing = Some(digit),
+                            Some(high) => {
+                                gb.write8(self.cursor, high << 4 | digit);
+                                self.move_cursor_to(self.cursor.wrapping_add(1));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn move_cursor_to(&mut self, addr: u16) {
+        self.cursor = addr;
+        self.editing = None;
+        let row = addr as usize / Self::BYTES_PER_ROW;
+        if row < self.first_row {
+            self.first_row = row;
+        } else if row >= self.first_row + Self::ROWS {
+            self.first_row = row + 1 - Self::ROWS;
+        }
+    }
+
+    /// address of the hex or ascii cell at the given pixel
+    fn addr_at(&self, x: usize, y: usize) -> Option<u16> {
+        let row = y.checked_sub(Self::MARGIN + Self::LINE_HEIGHT)? / Self::LINE_HEIGHT;
+        let column = x.checked_sub(Self::MARGIN)? / Canvas::CHAR_WIDTH;
+        if row >= Self::ROWS {
+            return None;
+        }
+        let byte = if (Self::HEX_COLUMN..Self::ASCII_COLUMN - 1).contains(&column) {
+            (column - Self::HEX_COLUMN) / 3
+        } else if (Self::ASCII_COLUMN..Self::ASCII_COLUMN + Self::BYTES_PER_ROW).contains(&column) {
+            column - Self::ASCII_COLUMN
+        } else {
+            return None;
+        };
+        Some(((self.first_row + row) * Self::BYTES_PER_ROW + byte) as u16)
+    }
+
+    fn column_x(column: usize) -> usize {
+        Self::MARGIN + column * Canvas::CHAR_WIDTH
+    }
+
+    fn draw(&mut self, gb: &Gameboy) {
+        self.canvas.clear(BACKGROUND);
+
+        let y = Self::MARGIN;
+        self.canvas.draw_text(Self::column_x(0), y, "ADDR", TEXT_DIM);
+        for i in 0..Self::BYTES_PER_ROW {
+            self.canvas.draw_text(
+                Self::column_x(Self::HEX_COLUMN + i * 3),
+                y,
+                &format!("{:02X}", i),
+                TEXT_DIM,
+            );
+        }
+        self.canvas
+            .draw_text(Self::column_x(Self::ASCII_COLUMN), y, "ASCII", TEXT_DIM);
+        self.canvas
+            .draw_text(Self::column_x(Self::REGION_COLUMN), y, "REGION", TEXT_DIM);
+
+        for row in 0..Self::ROWS {
+            let base = ((self.first_row + row) * Self::BYTES_PER_ROW) as u16;
+            let y = Self::MARGIN + (row + 1) * Self::LINE_HEIGHT;
+            let region = Self::region(base);
+            if row > 0 && region != Self::region(base.wrapping_sub(1)) {
+                self.canvas
+                    .fill_rect(Self::MARGIN, y - 2, Self::WIDTH - 2 * Self::MARGIN, 1, TEXT_DIM);
+            }
+            self.canvas
+                .draw_text(Self::column_x(0), y, &format!("{:04X}", base), ACCENT);
+            self.canvas
+                .draw_text(Self::column_x(Self::REGION_COLUMN), y, region, TEXT_DIM);
+
+            for i in 0..Self::BYTES_PER_ROW {
+                let addr = base + i as u16;
+                let value = gb.read8_unlocked(addr);
+                let hex_x = Self::column_x(Self::HEX_COLUMN + i * 3);
+                let ascii_x = Self::column_x(Self::ASCII_COLUMN + i);
+                if addr == self.cursor {
+                    self.canvas.fill_rect(
+                        hex_x - 1,
+                        y - 1,
+                        2 * Canvas::CHAR_WIDTH + 1,
+                        Canvas::CHAR_HEIGHT + 1,
+                        SELECTION,
+                    );
+                    self.canvas.fill_rect(
+                        ascii_x - 1,
+                        y - 1,
+                        Canvas::CHAR_WIDTH + 1,
+                        Canvas::CHAR_HEIGHT + 1,
+                        SELECTION,
+                    );
+                }
+                let age = gb.debugger.recent_write(addr) as f32 / Debugger::WRITE_HIGHLIGHT_FRAMES as f32;
+                let color = blend(TEXT, HIGHLIGHT, age);
+                let hex = match self.editing {
+                    Some(high) if addr == self.cursor => format!("{:X}_", high),
+                    _ => format!("{:02X}", value),
+                };
+                self.canvas.draw_text(hex_x, y, &hex, color);
+                let c = if (0x20..0x7F).contains(&value) {
+                    value as char
+                } else {
+                    '.'
+                };
+                self.canvas.draw_text(ascii_x, y, &c.to_string(), color);
+            }
+        }
+
+        let y = Self::MARGIN + (Self::ROWS + 1) * Self::LINE_HEIGHT + 2;
+        let status = match &self.goto {
+            Some(goto) => format!("goto: {}_", goto),
+            None => format!(
+                "{:04X} {} = {:#04X}   hex digits: edit  g: goto  arrows/pgup/pgdn/wheel: scroll",
+                self.cursor,
+                Self::region(self.cursor),
+                gb.read8_unlocked(self.cursor)
+            ),
+        };
+        self.canvas.draw_text(Self::column_x(0), y, &status, TEXT);
+    }
+}
+
+#[test]
+fn memory_region_test() {
+    assert_eq!(MemoryViewer::region(0x0150), "ROM0");
+    assert_eq!(MemoryViewer::region(0x4000), "ROMX");
+    assert_eq!(MemoryViewer::region(0x9FFF), "VRAM");
+    assert_eq!(MemoryViewer::region(0xC000), "WRAM");
+    assert_eq!(MemoryViewer::region(0xFEA0), "-");
+    assert_eq!(MemoryViewer::region(0xFF44), "IO");
+    assert_eq!(MemoryViewer::region(0xFF80), "HRAM");
+    assert_eq!(MemoryViewer::region(0xFFFF), "IE");
+}
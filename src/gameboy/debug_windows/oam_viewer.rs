@@ -0,0 +1,137 @@
+use minifb::Key;
+
+use crate::{
+    gameboy::Gameboy,
+    screen::{canvas::Canvas, Screen},
+};
+
+use super::{ACCENT, BACKGROUND, SELECTION, TEXT, TEXT_DIM};
+
+/// All 40 oam entries with their decoded attributes and the object drawn with its palette.
+/// Entries the oam search selects on the inspected line are highlighted, the line follows LY
+/// until it is pinned with the up and down keys, escape follows LY again.
+pub struct OamViewer {
+    screen: Screen,
+    canvas: Canvas,
+    pinned_line: Option<u8>,
+}
+
+impl OamViewer {
+    const ENTRIES: usize = 40;
+    const GRID_COLUMNS: usize = 4;
+    const ZOOM: usize = 2;
+    const MARGIN: usize = 4;
+    const LINE_HEIGHT: usize = Canvas::CHAR_HEIGHT + 2;
+    const HEADER_HEIGHT: usize = 2 * Self::LINE_HEIGHT + 2;
+    const CELL_WIDTH: usize = 8 * Self::ZOOM + 4 + 17 * Canvas::CHAR_WIDTH;
+    const CELL_HEIGHT: usize = 16 * Self::ZOOM + 6;
+    const WIDTH: usize = Self::GRID_COLUMNS * Self::CELL_WIDTH + 2 * Self::MARGIN;
+    const HEIGHT: usize =
+        Self::HEADER_HEIGHT + (Self::ENTRIES / Self::GRID_COLUMNS) * Self::CELL_HEIGHT + 2 * Self::MARGIN;
+    const LAST_VISIBLE_LINE: u8 = 143;
+
+    pub fn new() -> Self {
+        let mut screen = Screen::new(Self::HEIGHT, Self::WIDTH, minifb::Scale::X2);
+        screen.set_title("OAM");
+        Self {
+            screen,
+            canvas: Canvas::new(Self::WIDTH, Self::HEIGHT),
+            pinned_line: None,
+        }
+    }
+
+    pub fn update(&mut self, gb: &Gameboy) {
+        let ly = gb.ppu.get_ly();
+        for key in self.screen.get_keys_pressed() {
+            let line = self.pinned_line.unwrap_or(ly);
+            match key {
+                Key::Up => self.pinned_line = Some(line.saturating_sub(1)),
+                Key::Down => self.pinned_line = Some(std::cmp::min(line + 1, Self::LAST_VISIBLE_LINE)),
+                Key::Escape => self.pinned_line = None,
+                _ => {}
+            }
+        }
+        self.draw(gb);
+        self.screen.set_frame_buffer(self.canvas.buffer());
+        self.screen.update();
+    }
+
+    fn draw(&mut self, gb: &Gameboy) {
+        self.canvas.clear(BACKGROUND);
+
+        let line = self.pinned_line.unwrap_or_else(|| gb.ppu.get_ly());
+        let selected = gb.ppu.get_objects_on_line(line);
+        let height = gb.ppu.get_object_height();
+        let header = format!(
+            "8x{} objects   line {:>3} {}",
+            height,
+            line,
+            if self.pinned_line.is_some() {
+                "(pinned, esc follows LY)"
+            } else {
+                "(LY, up/down to pin)"
+            }
+        );
+        self.canvas.draw_text(Self::MARGIN, Self::MARGIN, &header, TEXT);
+        self.canvas.draw_text(
+            Self::MARGIN,
+            Self::MARGIN + Self::LINE_HEIGHT,
+            &format!("{} selected on this line", selected.len()),
+            ACCENT,
+        );
+
+        for index in 0..Self::ENTRIES {
+            let x = Self::MARGIN + (index % Self::GRID_COLUMNS) * Self::CELL_WIDTH;
+            let y = Self::MARGIN + Self::HEADER_HEIGHT + (index / Self::GRID_COLUMNS) * Self::CELL_HEIGHT;
+            if selected.contains(&index) {
+                self.canvas
+                    .fill_rect(x, y, Self::CELL_WIDTH - 2, Self::CELL_HEIGHT - 2, SELECTION);
+                self.canvas
+                    .draw_rect(x, y, Self::CELL_WIDTH - 2, Self::CELL_HEIGHT - 2, ACCENT);
+            }
+
+            // transparent pixels stay dark so the object outline is visible
+            let sprite_x = x + 2;
+            let sprite_y = y + 2;
+            self.canvas
+                .fill_rect(sprite_x, sprite_y, 8 * Self::ZOOM, height * Self::ZOOM, TEXT_DIM);
+            for (i, pixel) in gb.ppu.get_object_pixels(index).iter().enumerate() {
+                if let Some(color) = pixel {
+                    self.canvas.fill_rect(
+                        sprite_x + (i % 8) * Self::ZOOM,
+                        sprite_y + (i / 8) * Self::ZOOM,
+                        Self::ZOOM,
+                        Self::ZOOM,
+                        *color,
+                    );
+                }
+            }
+
+            let entry = gb.ppu.get_oam_entry(index);
+            let offscreen = entry.x_pos <= -8 || entry.x_pos >= 160 || entry.y_pos <= -16 || entry.y_pos >= 144;
+            let color = if offscreen { TEXT_DIM } else { TEXT };
+            let attributes = &entry.attributes;
+            let lines = [
+                format!("{:02} X{:>4} Y{:>4}", index, entry.x_pos, entry.y_pos),
+                format!("tile {:02X}  OBP{}", entry.tile_index, attributes.palette_number),
+                format!(
+                    "{} {} {}",
+                    if attributes.x_flip { "XF" } else { "--" },
+                    if attributes.y_flip { "YF" } else { "--" },
+                    if attributes.bg_window_override { "BG" } else { "--" }
+                ),
+            ];
+            let text_x = sprite_x + 8 * Self::ZOOM + 4;
+            for (i, text) in lines.iter().enumerate() {
+                self.canvas
+                    .draw_text(text_x, sprite_y + i * Self::LINE_HEIGHT, text, color);
+            }
+        }
+    }
+}
+
+impl Default for OamViewer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
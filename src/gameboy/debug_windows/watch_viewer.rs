@@ -0,0 +1,93 @@
+use crate::{
+    gameboy::Gameboy,
+    screen::{canvas::Canvas, Screen},
+};
+
+use super::{ACCENT, BACKGROUND, HIGHLIGHT, TEXT, TEXT_DIM};
+
+/// The ram watch list with the values of the last frame, values that changed since the frame before are highlighted.
+/// Watches are added and removed with the `watch` debugger command or in the watch file of the rom.
+pub struct WatchViewer {
+    screen: Screen,
+    canvas: Canvas,
+}
+
+impl WatchViewer {
+    const MARGIN: usize = 4;
+    const LINE_HEIGHT: usize = Canvas::CHAR_HEIGHT + 2;
+    const ROWS: usize = 24;
+    const COLUMNS: usize = 48;
+    const WIDTH: usize = Self::COLUMNS * Canvas::CHAR_WIDTH + 2 * Self::MARGIN;
+    const HEIGHT: usize = (Self::ROWS + 1) * Self::LINE_HEIGHT + 2 * Self::MARGIN;
+    const NAME_LENGTH: usize = 20;
+
+    pub fn new() -> Self {
+        let mut screen = Screen::new(Self::HEIGHT, Self::WIDTH, minifb::Scale::X2);
+        screen.set_title("RAM watch");
+        Self {
+            screen,
+            canvas: Canvas::new(Self::WIDTH, Self::HEIGHT),
+        }
+    }
+
+    pub fn update(&mut self, gb: &Gameboy) {
+        self.draw(gb);
+        self.screen.set_frame_buffer(self.canvas.buffer());
+        self.screen.update();
+    }
+
+    fn draw(&mut self, gb: &Gameboy) {
+        self.canvas.clear(BACKGROUND);
+        let ram_watch = gb.ram_watch();
+        self.canvas.draw_text(
+            Self::MARGIN,
+            Self::MARGIN,
+            &format!("{:<4} {:<5} {:<20} {:>8}", "addr", "type", "name", "value"),
+            ACCENT,
+        );
+        if ram_watch.watches().is_empty() {
+            self.canvas.draw_text(
+                Self::MARGIN,
+                Self::MARGIN + Self::LINE_HEIGHT,
+                "no watches, use 'watch add <addr>'",
+                TEXT_DIM,
+            );
+        }
+        // the last row lists how many watches do not fit
+        let shown = if ram_watch.watches().len() > Self::ROWS {
+            Self::ROWS - 1
+        } else {
+            Self::ROWS
+        };
+        for (i, watch) in ram_watch.watches().iter().take(shown).enumerate() {
+            let y = Self::MARGIN + (i + 1) * Self::LINE_HEIGHT;
+            let (value, changed) = ram_watch.value(i);
+            let name: String = watch.name.chars().take(Self::NAME_LENGTH).collect();
+            let x = self.canvas.draw_text(
+                Self::MARGIN,
+                y,
+                &format!("{:04X} {:<5} {:<20} ", watch.addr, watch.kind, name),
+                TEXT_DIM,
+            );
+            let (text, color) = match value {
+                Some(value) => (format!("{:>8}", value), if changed { HIGHLIGHT } else { TEXT }),
+                None => (format!("{:>8}", "-"), TEXT_DIM),
+            };
+            self.canvas.draw_text(x, y, &text, color);
+        }
+        if ram_watch.watches().len() > shown {
+            self.canvas.draw_text(
+                Self::MARGIN,
+                Self::MARGIN + Self::ROWS * Self::LINE_HEIGHT,
+                &format!("... {} more", ram_watch.watches().len() - shown),
+                TEXT_DIM,
+            );
+        }
+    }
+}
+
+impl Default for WatchViewer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
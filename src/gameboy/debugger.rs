@@ -0,0 +1,490 @@
+mod breakpoint;
+mod cdl;
+mod crash;
+mod event_log;
+mod heatmap;
+mod history;
+mod profiler;
+mod ram_search;
+mod ram_watch;
+mod time_travel;
+mod trace_diff;
+
+use std::{
+    cell::RefCell,
+    io::{self, Write},
+    path::PathBuf,
+};
+
+use super::{cpu::CPU, Gameboy};
+
+pub use self::breakpoint::{DebugEvent, EventBreakpoint};
+pub use self::cdl::CodeDataLogger;
+pub use self::crash::CrashReason;
+pub use self::event_log::{EventLog, IoEvent, IoEventKind};
+pub use self::heatmap::{Access, AccessHeatmap, Heat};
+use self::history::{HistoryEntry, InstructionHistory};
+pub use self::profiler::Profiler;
+use self::ram_search::RamSearchCommand;
+pub use self::ram_search::{RamSearch, RamSearchResult, SearchFilter, SearchSize};
+use self::ram_watch::RamWatchCommand;
+pub use self::ram_watch::{parse_watch_file, RamWatch, Watch, WatchType};
+pub use self::time_travel::{Checkpoint, TimeTravel};
+use self::trace_diff::{divergence_report, TraceCheck};
+pub use self::trace_diff::{TraceDiff, TraceState};
+
+pub struct Debugger {
+    event_breakpoints: Vec<EventBreakpoint>,
+    break_reason: Option<String>,
+    steps_left: u32,
+    frames_left: u32,
+
+    history: InstructionHistory,
+    crash_report_dir: PathBuf,
+    pending_crash: Option<CrashReason>,
+    crash_reported: bool,
+
+    // logged from the read path which only has shared access to the gameboy
+    code_data_logger: RefCell<Option<CodeDataLogger>>,
+    profiler: Option<Profiler>,
+    // frames left to highlight each address in the memory viewer, only allocated while it is open
+    recent_writes: Option<Vec<u8>>,
+    ram_search: Option<RamSearch>,
+    ram_watch: RamWatch,
+    trace_diff: Option<TraceDiff>,
+    // only allocated once the event viewer or the events command asked for it
+    event_log: Option<EventLog>,
+    // fed from the read path like the code data logger
+    heatmap: RefCell<Option<AccessHeatmap>>,
+    // executed instructions, the position reverse stepping counts in
+    instructions: u64,
+    time_travel: Option<TimeTravel>,
+    // set while time travel executes instructions a second time
+    replaying: bool,
+}
+
+enum DebuggerCommand {
+    Continue,
+    Step(u32),
+    Frame(u32),
+    ReverseStep(u64),
+    ReverseContinue,
+    Break(EventBreakpoint),
+    Delete(usize),
+    List,
+    Registers,
+    CodeDataLog,
+    Events(Option<PathBuf>),
+    RamSearch(RamSearchCommand),
+    RamWatch(RamWatchCommand),
+    Cheats,
+    ToggleCheat(usize),
+    Quit,
+    Help,
+}
+
+impl std::str::FromStr for DebuggerCommand {
+    type Err = String;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let line = line.trim();
+        let (command, args) = line.split_once(' ').unwrap_or((line, ""));
+        let args = args.trim();
+        match command {
+            "c" | "continue" => Ok(DebuggerCommand::Continue),
+            "s" | "step" => match args {
+                "" => Ok(DebuggerCommand::Step(1)),
+                count => count
+                    .parse()
+                    .map(DebuggerCommand::Step)
+                    .map_err(|_| format!("invalid step count {}", count)),
+            },
+            "f" | "frame" => match args {
+                "" => Ok(DebuggerCommand::Frame(1)),
+                count => count
+                    .parse()
+                    .map(DebuggerCommand::Frame)
+                    .map_err(|_| format!("invalid frame count {}", count)),
+            },
+            "rs" | "reverse-step" => match args {
+                "" => Ok(DebuggerCommand::ReverseStep(1)),
+                count => count
+                    .parse()
+                    .map(DebuggerCommand::ReverseStep)
+                    .map_err(|_| format!("invalid step count {}", count)),
+            },
+            "rc" | "reverse-continue" => Ok(DebuggerCommand::ReverseContinue),
+            "b" | "break" => Ok(DebuggerCommand::Break(args.parse()?)),
+            "d" | "delete" => args
+                .parse()
+                .map(DebuggerCommand::Delete)
+                .map_err(|_| format!("invalid breakpoint index {}", args)),
+            "l" | "list" => Ok(DebuggerCommand::List),
+            "r" | "regs" => Ok(DebuggerCommand::Registers),
+            "cdl" => Ok(DebuggerCommand::CodeDataLog),
+            "events" => Ok(DebuggerCommand::Events((!args.is_empty()).then(|| PathBuf::from(args)))),
+            "search" => args.parse().map(DebuggerCommand::RamSearch),
+            "watch" => args.parse().map(DebuggerCommand::RamWatch),
+            "cheats" => Ok(DebuggerCommand::Cheats),
+            "cheat" => args
+                .parse()
+                .map(DebuggerCommand::ToggleCheat)
+                .map_err(|_| format!("invalid cheat index {}", args)),
+            "q" | "quit" => Ok(DebuggerCommand::Quit),
+            "h" | "help" | "" => Ok(DebuggerCommand::Help),
+            _ => Err(format!("unknown command '{}'", command)),
+        }
+    }
+}
+
+impl Debugger {
+    const HELP: &'static str = "commands:
+    c, continue         resume emulation
+    s, step [n]         execute n instructions (default 1)
+    f, frame [n]        run n frames (default 1)
+    rs, reverse-step [n]
+                        go back n instructions (default 1)
+    rc, reverse-continue
+                        go back to the last time a breakpoint was hit
+    b, break <event>    add an event breakpoint:
+                            int [vblank|stat|timer|serial|joypad]
+                            mode <0-3> [ly]
+                            dma
+                            io <addr>[-<addr>]
+    d, delete <n>       remove breakpoint n
+    l, list             list breakpoints
+    r, regs             print cpu registers
+    cdl                 write the code data log and print rom coverage
+    events [file]       print the io writes and interrupt requests of the last frame
+                        or write them to a csv file, the first use starts logging
+    search new [8|16] [bcd]
+                        start a ram search over external ram, wram and hram
+    search <filter>     keep the candidates that are eq, changed, inc, dec
+                        or = <value> compared to the last search
+    search              list the candidates
+    watch               list the watched addresses with their values
+    watch add <addr> [type] [name]
+                        watch an address as u8, i8, u16, u16be, i16, i16be, bcd or bcd16
+    watch del <n>       remove watch n
+    watch save          write the watch list to the watch file of the rom
+    watch csv <file>    write the watched values to a csv file every frame
+    cheats              list cheats
+    cheat <n>           enable or disable cheat n
+    q, quit             stop emulation";
+    const DEFAULT_HISTORY_SIZE: usize = 256;
+    const DEFAULT_CRASH_REPORT_DIR: &'static str = "crash_reports";
+    pub const WRITE_HIGHLIGHT_FRAMES: u8 = 60;
+
+    pub fn new() -> Self {
+        Self {
+            event_breakpoints: Vec::new(),
+            break_reason: None,
+            steps_left: 0,
+            frames_left: 0,
+
+            history: InstructionHistory::new(Self::DEFAULT_HISTORY_SIZE),
+            crash_report_dir: PathBuf::from(Self::DEFAULT_CRASH_REPORT_DIR),
+            pending_crash: None,
+            crash_reported: false,
+
+            code_data_logger: RefCell::new(None),
+            profiler: None,
+            recent_writes: None,
+            ram_search: None,
+            ram_watch: RamWatch::new(),
+            trace_diff: None,
+            event_log: None,
+            heatmap: RefCell::new(None),
+            instructions: 0,
+            time_travel: None,
+            replaying: false,
+        }
+    }
+
+    pub fn set_history_size(&mut self, size: usize) {
+        self.history.set_capacity(size);
+    }
+
+    pub fn set_crash_report_dir(&mut self, dir: PathBuf) {
+        self.crash_report_dir = dir;
+    }
+
+    /// lockups and illegal opcodes are only reported once per session
+    pub fn take_crash(&mut self) -> Option<CrashReason> {
+        let crash = self.pending_crash.take();
+        if crash.is_some() {
+            self.crash_reported = true;
+        }
+        crash
+    }
+
+    pub fn request_break(&mut self, reason: &str) {
+        self.break_reason = Some(reason.to_string());
+    }
+
+    pub fn is_break_pending(&self) -> bool {
+        self.break_reason.is_some()
+    }
+
+    pub fn add_event_breakpoint(&mut self, breakpoint: EventBreakpoint) -> usize {
+        self.event_breakpoints.push(breakpoint);
+        self.event_breakpoints.len() - 1
+    }
+
+    pub fn remove_event_breakpoint(&mut self, index: usize) -> Option<EventBreakpoint> {
+        if index < self.event_breakpoints.len() {
+            Some(self.event_breakpoints.remove(index))
+        } else {
+            None
+        }
+    }
+
+    pub fn on_event(&mut self, event: DebugEvent) {
+        if self.event_breakpoints.is_empty() {
+            return;
+        }
+        if let Some(index) = self.event_breakpoints.iter().position(|bp| bp.matches(&event)) {
+            self.break_reason = Some(format!("breakpoint {} hit: {}", index, event));
+        }
+    }
+
+    pub fn on_frame(&mut self) {
+        if let Some(event_log) = self.event_log.as_mut() {
+            event_log.end_frame();
+        }
+        if let Some(heatmap) = self.heatmap.get_mut() {
+            heatmap.decay();
+        }
+        if self.frames_left > 0 {
+            self.frames_left -= 1;
+            if self.frames_left == 0 {
+                self.request_break("frame");
+            }
+        }
+    }
+
+    pub fn log_events(&mut self) {
+        if self.event_log.is_none() {
+            self.event_log = Some(EventLog::new());
+        }
+    }
+
+    pub fn event_log(&self) -> Option<&EventLog> {
+        self.event_log.as_ref()
+    }
+
+    pub fn heatmap(&self) -> std::cell::Ref<'_, Option<AccessHeatmap>> {
+        self.heatmap.borrow()
+    }
+
+    pub fn track_writes(&mut self) {
+        if self.recent_writes.is_none() {
+            self.recent_writes = Some(vec![0; 0x10000]);
+        }
+    }
+
+    pub fn on_write(&mut self, addr: u16) {
+        if let Some(recent_writes) = self.recent_writes.as_mut() {
+            recent_writes[addr as usize] = Self::WRITE_HIGHLIGHT_FRAMES;
+        }
+    }
+
+    /// frames left until the last write to the address stops being highlighted
+    pub fn recent_write(&self, addr: u16) -> u8 {
+        self.recent_writes
+            .as_ref()
+            .map_or(0, |recent_writes| recent_writes[addr as usize])
+    }
+
+    pub fn age_recent_writes(&mut self) {
+        if let Some(recent_writes) = self.recent_writes.as_mut() {
+            for frames in recent_writes.iter_mut() {
+                *frames = frames.saturating_sub(1);
+            }
+        }
+    }
+
+    /// the cpu already holds the state after the instruction
+    pub fn on_instruction_executed(
+        &mut self,
+        cpu: &CPU,
+        bank: usize,
+        pc: u16,
+        opcode: u8,
+        cycles: u64,
+        next_bank: usize,
+    ) {
+        if let Some(profiler) = self.profiler.as_mut() {
+            profiler.on_instruction(bank, pc, opcode, cycles, next_bank, cpu);
+        }
+    }
+
+    pub fn on_interrupt_dispatched(&mut self, vector: u16, sp: u16) {
+        if let Some(profiler) = self.profiler.as_mut() {
+            profiler.on_interrupt(vector, sp);
+        }
+    }
+}
+
+impl Default for Debugger {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Gameboy {
+    /// Records the instruction about to be executed in the history and checks it for crashes,
+    /// trace divergences and the end of a step
+    pub(in crate::gameboy) fn debugger_on_instruction(&mut self, cpu: &CPU, bank: usize, opcode: u8) {
+        let entry = HistoryEntry::new(cpu, bank, opcode);
+        self.debugger.instructions += 1;
+        if self.debugger.replaying {
+            self.debugger.history.push(entry);
+            return;
+        }
+        if !self.debugger.crash_reported && self.debugger.pending_crash.is_none() {
+            self.debugger.pending_crash = CrashReason::detect(
+                &entry,
+                || self.read8_unlocked(cpu.pc.wrapping_add(1)),
+                cpu.ie_register.clone().into(),
+            );
+        }
+        let check = self
+            .debugger
+            .trace_diff
+            .as_mut()
+            .map(|trace_diff| (trace_diff.check(&entry), trace_diff.matched()));
+        match check {
+            None | Some((TraceCheck::Match, _)) => {}
+            Some((TraceCheck::Ended, matched)) => {
+                println!(
+                    "[debugger] reference trace ended after {} matching instructions",
+                    matched
+                );
+                self.debugger.trace_diff = None;
+            }
+            Some((TraceCheck::Diverged(divergence), matched)) => {
+                print!(
+                    "{}",
+                    divergence_report(
+                        &divergence,
+                        matched,
+                        self.decode_history_entry(&entry),
+                        self.debugger
+                            .history
+                            .iter()
+                            .map(|previous| self.decode_history_entry(previous))
+                    )
+                );
+                self.debugger.trace_diff = None;
+                self.debugger.request_break("trace diverged");
+            }
+        }
+        self.debugger.history.push(entry);
+
+        if self.debugger.steps_left > 0 {
+            self.debugger.steps_left -= 1;
+            if self.debugger.steps_left == 0 {
+                self.debugger.request_break("step");
+            }
+        }
+    }
+
+    /// Blocks on stdin until the user resumes emulation, returns false if emulation shall stop
+    pub(super) fn debugger_repl(&mut self) -> bool {
+        if let Some(reason) = self.debugger.break_reason.take() {
+            println!("[debugger] {} at pc {:#06X}", reason, self.cpu.pc);
+        }
+        loop {
+            print!("(gbdb) ");
+            io::stdout().flush().unwrap();
+            let mut line = String::new();
+            if io::stdin().read_line(&mut line).unwrap_or(0) == 0 {
+                return false;
+            }
+            let command = match line.parse::<DebuggerCommand>() {
+                Ok(command) => command,
+                Err(err) => {
+                    println!("{}", err);
+                    continue;
+                }
+            };
+            match command {
+                DebuggerCommand::Continue => return true,
+                DebuggerCommand::Step(count) => {
+                    self.debugger.steps_left = count;
+                    return true;
+                }
+                DebuggerCommand::Frame(count) => {
+                    self.debugger.frames_left = count;
+                    return true;
+                }
+                DebuggerCommand::ReverseStep(count) => self.reverse_step(count),
+                DebuggerCommand::ReverseContinue => self.reverse_continue(),
+                DebuggerCommand::Break(breakpoint) => {
+                    println!("breakpoint {}: {}", self.debugger.event_breakpoints.len(), breakpoint);
+                    self.debugger.add_event_breakpoint(breakpoint);
+                }
+                DebuggerCommand::Delete(index) => match self.debugger.remove_event_breakpoint(index) {
+                    Some(breakpoint) => println!("removed breakpoint {}: {}", index, breakpoint),
+                    None => println!("no breakpoint {}", index),
+                },
+                DebuggerCommand::List => {
+                    for (i, breakpoint) in self.debugger.event_breakpoints.iter().enumerate() {
+                        println!("{}: {}", i, breakpoint);
+                    }
+                }
+                DebuggerCommand::Registers => println!("{}", self.cpu),
+                DebuggerCommand::CodeDataLog => match self.debugger.code_data_logger.borrow().as_ref() {
+                    Some(cdl) => {
+                        print!("{}", cdl.coverage_report());
+                        self.save_code_data_log();
+                    }
+                    None => println!("code data logger not enabled, start with --cdl <file>"),
+                },
+                DebuggerCommand::Events(path) => match (self.debugger.event_log.as_ref(), path) {
+                    (None, _) => {
+                        self.debugger.log_events();
+                        println!("event log started, it holds the events of the last frame from the next frame on");
+                    }
+                    (Some(event_log), None) => {
+                        for event in event_log.last_frame() {
+                            println!("{}", event);
+                        }
+                    }
+                    (Some(_), Some(path)) => match self.save_event_log(&path) {
+                        Ok(()) => println!("events written to {}", path.display()),
+                        Err(err) => println!("failed to write {}: {}", path.display(), err),
+                    },
+                },
+                DebuggerCommand::RamSearch(command) => self.ram_search_command(command),
+                DebuggerCommand::RamWatch(command) => self.ram_watch_command(command),
+                DebuggerCommand::Cheats => {
+                    for (i, cheat) in self.cheats().iter().enumerate() {
+                        println!("{}: {}", i, cheat);
+                    }
+                }
+                DebuggerCommand::ToggleCheat(index) => match self.cheats().get(index).map(|cheat| cheat.enabled) {
+                    Some(enabled) => {
+                        self.set_cheat_enabled(index, !enabled);
+                        println!("{}: {}", index, self.cheats()[index]);
+                    }
+                    None => println!("no cheat {}", index),
+                },
+                DebuggerCommand::Quit => return false,
+                DebuggerCommand::Help => println!("{}", Debugger::HELP),
+            }
+        }
+    }
+}
+
+/// parses addresses like `ff40`, `0xff40` or `$ff40`
+pub fn parse_u16(text: &str) -> Option<u16> {
+    let text = text.trim();
+    let text = text
+        .strip_prefix("0x")
+        .or_else(|| text.strip_prefix("0X"))
+        .or_else(|| text.strip_prefix('$'))
+        .unwrap_or(text);
+    u16::from_str_radix(text, 16).ok()
+}
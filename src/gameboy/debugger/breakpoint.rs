@@ -0,0 +1,144 @@
+use std::fmt;
+
+use super::parse_u16;
+
+const INTERRUPT_VECTORS: [(&str, u16); 5] = [
+    ("vblank", 0x40),
+    ("stat", 0x48),
+    ("timer", 0x50),
+    ("serial", 0x58),
+    ("joypad", 0x60),
+];
+
+const PPU_MODES: [&str; 4] = ["HBLANK", "VBLANK", "SEARCHING_OAM", "TRANSFERRING_DATA_TO_LCD"];
+
+/// Things happening inside the emulated hardware that a breakpoint can react to
+#[derive(Copy, Clone, Debug)]
+pub enum DebugEvent {
+    Interrupt { vector: u16 },
+    PPUMode { mode: u8, ly: u8 },
+    DMA { source: u8 },
+    IOWrite { addr: u16, value: u8 },
+}
+
+#[derive(Clone, Debug)]
+pub enum EventBreakpoint {
+    Interrupt(Option<u16>),
+    PPUMode { mode: u8, ly: Option<u8> },
+    DMA,
+    IOWrite { begin: u16, end: u16 },
+}
+
+impl EventBreakpoint {
+    pub fn matches(&self, event: &DebugEvent) -> bool {
+        match (self, event) {
+            (EventBreakpoint::Interrupt(vector), DebugEvent::Interrupt { vector: event_vector }) => {
+                vector.is_none() || *vector == Some(*event_vector)
+            }
+            (
+                EventBreakpoint::PPUMode { mode, ly },
+                DebugEvent::PPUMode {
+                    mode: event_mode,
+                    ly: event_ly,
+                },
+            ) => mode == event_mode && (ly.is_none() || *ly == Some(*event_ly)),
+            (EventBreakpoint::DMA, DebugEvent::DMA { .. }) => true,
+            (EventBreakpoint::IOWrite { begin, end }, DebugEvent::IOWrite { addr, .. }) => addr >= begin && addr <= end,
+            _ => false,
+        }
+    }
+}
+
+pub(super) fn interrupt_name(vector: u16) -> &'static str {
+    match INTERRUPT_VECTORS.iter().find(|(_, v)| *v == vector) {
+        Some((name, _)) => name,
+        None => "unknown",
+    }
+}
+
+impl std::str::FromStr for EventBreakpoint {
+    type Err = String;
+
+    /// parses breakpoint specs as typed in the debugger, e.g. `int vblank`, `mode 3 40`, `dma` or `io ff04-ff07`
+    fn from_str(spec: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = spec.split_whitespace().collect();
+        match parts.as_slice() {
+            ["int"] => Ok(EventBreakpoint::Interrupt(None)),
+            ["int", name] => match INTERRUPT_VECTORS.iter().find(|(n, _)| n == name) {
+                Some((_, vector)) => Ok(EventBreakpoint::Interrupt(Some(*vector))),
+                None => Err(format!("unknown interrupt {}", name)),
+            },
+            ["mode", mode] | ["mode", mode, _] => {
+                let mode: u8 = match mode.parse() {
+                    Ok(mode) if mode <= 3 => mode,
+                    _ => return Err(format!("invalid ppu mode {}", mode)),
+                };
+                let ly = match parts.get(2) {
+                    Some(ly) => Some(ly.parse::<u8>().map_err(|_| format!("invalid line {}", ly))?),
+                    None => None,
+                };
+                Ok(EventBreakpoint::PPUMode { mode, ly })
+            }
+            ["dma"] => Ok(EventBreakpoint::DMA),
+            ["io", range] => {
+                let (begin, end) = match range.split_once('-') {
+                    Some((begin, end)) => (parse_u16(begin), parse_u16(end)),
+                    None => (parse_u16(range), parse_u16(range)),
+                };
+                match (begin, end) {
+                    (Some(begin), Some(end)) if begin <= end => Ok(EventBreakpoint::IOWrite { begin, end }),
+                    _ => Err(format!("invalid address range {}", range)),
+                }
+            }
+            _ => Err(format!("unknown breakpoint '{}'", spec)),
+        }
+    }
+}
+
+impl fmt::Display for EventBreakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EventBreakpoint::Interrupt(None) => write!(f, "any interrupt"),
+            EventBreakpoint::Interrupt(Some(vector)) => write!(f, "{} interrupt", interrupt_name(*vector)),
+            EventBreakpoint::PPUMode { mode, ly: None } => write!(f, "ppu enters {}", PPU_MODES[*mode as usize]),
+            EventBreakpoint::PPUMode { mode, ly: Some(ly) } => {
+                write!(f, "ppu enters {} on LY {}", PPU_MODES[*mode as usize], ly)
+            }
+            EventBreakpoint::DMA => write!(f, "oam dma start"),
+            EventBreakpoint::IOWrite { begin, end } if begin == end => write!(f, "write to {:#06X}", begin),
+            EventBreakpoint::IOWrite { begin, end } => write!(f, "write to {:#06X}-{:#06X}", begin, end),
+        }
+    }
+}
+
+impl fmt::Display for DebugEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DebugEvent::Interrupt { vector } => {
+                write!(f, "{} interrupt serviced ({:#06X})", interrupt_name(*vector), vector)
+            }
+            DebugEvent::PPUMode { mode, ly } => write!(f, "ppu entered {} on LY {}", PPU_MODES[*mode as usize], ly),
+            DebugEvent::DMA { source } => write!(f, "oam dma started from {:#06X}", (*source as u16) << 8),
+            DebugEvent::IOWrite { addr, value } => write!(f, "{:#04X} written to {:#06X}", value, addr),
+        }
+    }
+}
+
+#[test]
+fn event_breakpoint_test() {
+    let bp: EventBreakpoint = "mode 3 40".parse().unwrap();
+    assert!(bp.matches(&DebugEvent::PPUMode { mode: 3, ly: 40 }));
+    assert!(!bp.matches(&DebugEvent::PPUMode { mode: 3, ly: 41 }));
+    assert!(!bp.matches(&DebugEvent::PPUMode { mode: 2, ly: 40 }));
+
+    let bp: EventBreakpoint = "io ff04-ff07".parse().unwrap();
+    assert!(bp.matches(&DebugEvent::IOWrite { addr: 0xFF05, value: 0 }));
+    assert!(!bp.matches(&DebugEvent::IOWrite { addr: 0xFF40, value: 0 }));
+
+    let bp: EventBreakpoint = "int vblank".parse().unwrap();
+    assert!(bp.matches(&DebugEvent::Interrupt { vector: 0x40 }));
+    assert!(!bp.matches(&DebugEvent::Interrupt { vector: 0x50 }));
+
+    assert!("int nmi".parse::<EventBreakpoint>().is_err());
+    assert!("mode 4".parse::<EventBreakpoint>().is_err());
+}
@@ -0,0 +1,187 @@
+/*
+## Code/Data Log
+
+One flag byte per byte of cartridge rom, the lower two bits follow the layout used by FCEUX and Mesen.
+
+Bit | Meaning
+----|---------------------------------------------------
+ 0  | executed, either as opcode or as operand
+ 1  | read as data by the cpu
+ 2  | executed as the first byte of an instruction
+ 3  | copied to vram and fetched as tile data by the ppu
+*/
+use std::{
+    fmt::Write as _,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::gameboy::{cpu::CPU, memory, Gameboy};
+
+pub const CODE: u8 = 0x01;
+pub const DATA: u8 = 0x02;
+pub const OPCODE: u8 = 0x04;
+pub const TILE_DATA: u8 = 0x08;
+
+pub struct CodeDataLogger {
+    path: PathBuf,
+    flags: Vec<u8>,
+    // pc and length of the instruction currently executing, its fetches are not data reads
+    current_instruction: (u16, u16),
+    // last rom byte read by the cpu, used to follow copies into vram
+    last_rom_read: Option<(usize, u8)>,
+    // rom offset each tile data byte in vram was copied from
+    vram_source: Vec<Option<usize>>,
+}
+
+impl CodeDataLogger {
+    const BANK_SIZE: usize = memory::cartridge::ROM_BANK_N.size;
+
+    /// continues logging into an existing log if the file matches the rom size
+    pub fn new(path: PathBuf, rom_size: usize) -> Self {
+        let flags = match fs::read(&path) {
+            Ok(flags) if flags.len() == rom_size => {
+                log::info!("continuing code data log {}", path.display());
+                flags
+            }
+            _ => vec![0; rom_size],
+        };
+        Self {
+            path,
+            flags,
+            current_instruction: (0, 0),
+            last_rom_read: None,
+            vram_source: vec![None; memory::ppu::TILE_DATA.size],
+        }
+    }
+
+    pub fn mark(&mut self, offset: usize, flags: u8) {
+        if let Some(entry) = self.flags.get_mut(offset) {
+            *entry |= flags;
+        }
+    }
+
+    pub fn flags(&self) -> &[u8] {
+        &self.flags
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        fs::write(&self.path, &self.flags)?;
+        fs::write(Self::report_path(&self.path), self.coverage_report())
+    }
+
+    fn report_path(path: &Path) -> PathBuf {
+        let mut name = path.as_os_str().to_owned();
+        name.push(".coverage.txt");
+        PathBuf::from(name)
+    }
+
+    pub fn coverage_report(&self) -> String {
+        let mut report = String::new();
+        writeln!(report, "bank  |  code   |  data   |  tiles  | unused").unwrap();
+        let mut total_unused = 0;
+        for (bank, flags) in self.flags.chunks(Self::BANK_SIZE).enumerate() {
+            let count = |flag: u8| flags.iter().filter(|f| *f & flag != 0).count();
+            let unused = flags.iter().filter(|f| **f == 0).count();
+            total_unused += unused;
+            let percent = |count: usize| count as f32 * 100. / flags.len() as f32;
+            writeln!(
+                report,
+                "{:#04X}  | {:>6.2}% | {:>6.2}% | {:>6.2}% | {:>6.2}%",
+                bank,
+                percent(count(CODE)),
+                percent(count(DATA)),
+                percent(count(TILE_DATA)),
+                percent(unused)
+            )
+            .unwrap();
+        }
+        writeln!(
+            report,
+            "total: {} of {} bytes never accessed",
+            total_unused,
+            self.flags.len()
+        )
+        .unwrap();
+        report
+    }
+}
+
+impl Gameboy {
+    pub fn enable_code_data_logger(&mut self, path: PathBuf) {
+        let rom_size = self.cartridge.rom_size();
+        *self.debugger.code_data_logger.borrow_mut() = Some(CodeDataLogger::new(path, rom_size));
+    }
+
+    pub(in crate::gameboy) fn cdl_log_instruction(&self, pc: u16, opcode: u8) {
+        if let Some(cdl) = self.debugger.code_data_logger.borrow_mut().as_mut() {
+            let length = CPU::instruction_length(opcode);
+            cdl.current_instruction = (pc, length);
+            for i in 0..length {
+                if let Some(offset) = self.cartridge.rom_offset(pc.wrapping_add(i)) {
+                    cdl.mark(offset, if i == 0 { CODE | OPCODE } else { CODE });
+                }
+            }
+        }
+    }
+
+    pub(in crate::gameboy) fn cdl_log_read(&self, addr: u16, value: u8) {
+        if let Some(cdl) = self.debugger.code_data_logger.borrow_mut().as_mut() {
+            let (pc, length) = cdl.current_instruction;
+            if addr.wrapping_sub(pc) < length {
+                return;
+            }
+            if let Some(offset) = self.cartridge.rom_offset(addr) {
+                cdl.mark(offset, DATA);
+                cdl.last_rom_read = Some((offset, value));
+            }
+        }
+    }
+
+    /// tile data copied straight from rom keeps a reference to its source so the ppu fetch can be traced back
+    pub(in crate::gameboy) fn cdl_log_write(&self, addr: u16, value: u8) {
+        if let Some(cdl) = self.debugger.code_data_logger.borrow_mut().as_mut() {
+            if addr >= memory::ppu::TILE_DATA.begin && addr <= memory::ppu::TILE_DATA.end {
+                let source = match cdl.last_rom_read {
+                    Some((offset, rom_value)) if rom_value == value => Some(offset),
+                    _ => None,
+                };
+                cdl.vram_source[(addr - memory::ppu::TILE_DATA.begin) as usize] = source;
+            }
+        }
+    }
+
+    pub(in crate::gameboy) fn cdl_log_tile_fetch(&self, addr: u16) {
+        if let Some(cdl) = self.debugger.code_data_logger.borrow_mut().as_mut() {
+            if addr >= memory::ppu::TILE_DATA.begin && addr <= memory::ppu::TILE_DATA.end {
+                if let Some(offset) = cdl.vram_source[(addr - memory::ppu::TILE_DATA.begin) as usize] {
+                    cdl.mark(offset, TILE_DATA);
+                }
+            }
+        }
+    }
+
+    pub fn save_code_data_log(&self) {
+        if let Some(cdl) = self.debugger.code_data_logger.borrow().as_ref() {
+            match cdl.save() {
+                Ok(()) => log::info!("code data log written to {}", cdl.path.display()),
+                Err(err) => log::error!("failed to write code data log {}: {}", cdl.path.display(), err),
+            }
+        }
+    }
+}
+
+#[test]
+fn code_data_logger_test() {
+    let mut cdl = CodeDataLogger::new(PathBuf::from("/nonexistent/test.cdl"), 0x8000);
+    cdl.mark(0x0150, CODE | OPCODE);
+    cdl.mark(0x0151, CODE);
+    cdl.mark(0x0151, DATA);
+    cdl.mark(0x9000, DATA);
+    assert_eq!(cdl.flags()[0x0150], CODE | OPCODE);
+    assert_eq!(cdl.flags()[0x0151], CODE | DATA);
+
+    let report = cdl.coverage_report();
+    assert!(report.contains("total: 32766 of 32768 bytes never accessed"));
+    assert_eq!(report.lines().count(), 4);
+}
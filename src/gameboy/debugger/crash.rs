@@ -0,0 +1,123 @@
+use std::{
+    fmt,
+    fs::{self, File},
+    io::{self, Write},
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::gameboy::{memory, Gameboy};
+
+use super::history::HistoryEntry;
+
+const ILLEGAL_OPCODES: [u8; 11] = [0xD3, 0xDB, 0xDD, 0xE3, 0xE4, 0xEB, 0xEC, 0xED, 0xF4, 0xFC, 0xFD];
+
+#[derive(Clone, Debug)]
+pub enum CrashReason {
+    Panic(String),
+    Lockup(String),
+    IllegalOpcode { pc: u16, opcode: u8 },
+}
+
+impl fmt::Display for CrashReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CrashReason::Panic(message) => write!(f, "panic: {}", message),
+            CrashReason::Lockup(message) => write!(f, "lockup: {}", message),
+            CrashReason::IllegalOpcode { pc, opcode } => {
+                write!(f, "illegal opcode {:#04X} at {:#06X}", opcode, pc)
+            }
+        }
+    }
+}
+
+impl CrashReason {
+    /// Checks the instruction about to be executed for things the cpu can never recover from,
+    /// `operand` is the byte after the opcode and only asked for by a jr
+    pub fn detect(entry: &HistoryEntry, operand: impl FnOnce() -> u8, interrupts_enabled: u8) -> Option<Self> {
+        let opcode = entry.opcode;
+        if ILLEGAL_OPCODES.contains(&opcode) {
+            return Some(CrashReason::IllegalOpcode { pc: entry.pc, opcode });
+        }
+        let no_interrupts = !entry.ime || interrupts_enabled & 0x1F == 0;
+        if opcode == 0x18 && no_interrupts && operand() == 0xFE {
+            return Some(CrashReason::Lockup(format!(
+                "endless jr loop at {:#06X} with interrupts disabled",
+                entry.pc
+            )));
+        }
+        if opcode == 0x76 && interrupts_enabled & 0x1F == 0 {
+            return Some(CrashReason::Lockup(format!(
+                "halt at {:#06X} with all interrupts disabled in IE",
+                entry.pc
+            )));
+        }
+        None
+    }
+}
+
+impl Gameboy {
+    /// Writes history, io registers and a save state into a new directory inside the crash report directory
+    pub fn write_crash_report(&self, reason: &CrashReason) -> io::Result<PathBuf> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.as_millis())
+            .unwrap_or(0);
+        let dir = self.debugger.crash_report_dir.join(format!("crash_{}", timestamp));
+        fs::create_dir_all(&dir)?;
+
+        let mut report = File::create(dir.join("report.txt"))?;
+        writeln!(report, "{}", reason)?;
+        writeln!(report, "cartridge: {}", self.cartridge.title())?;
+        writeln!(
+            report,
+            "rom bank: {}",
+            self.cartridge.rom_bank(memory::cartridge::ROM_BANK_N.begin)
+        )?;
+        writeln!(report, "\n{}", self.cpu)?;
+        writeln!(
+            report,
+            "\nlast {} instructions (oldest first):",
+            self.debugger.history.len()
+        )?;
+        for entry in self.debugger.history.iter() {
+            writeln!(report, "{}", self.decode_history_entry(entry))?;
+        }
+
+        let mut io_registers = File::create(dir.join("io_registers.txt"))?;
+        for addr in (memory::IO_REGISTERS.begin..=memory::IO_REGISTERS.end).chain([memory::interrupt::IE]) {
+            writeln!(io_registers, "{:#06X}: {:#04X}", addr, self.read8_unlocked(addr))?;
+        }
+
+        fs::write(dir.join("state.gbss"), self.save_state())?;
+
+        log::error!("{}, crash report written to {}", reason, dir.display());
+        Ok(dir)
+    }
+}
+
+#[test]
+fn crash_detect_test() {
+    let cpu = crate::gameboy::cpu::CPU::new();
+    let entry = HistoryEntry::new(&cpu, 1, 0xDD);
+    assert!(matches!(
+        CrashReason::detect(&entry, || 0x00, 0x1F),
+        Some(CrashReason::IllegalOpcode { opcode: 0xDD, .. })
+    ));
+
+    let mut entry = HistoryEntry::new(&cpu, 1, 0x18);
+    assert!(matches!(
+        CrashReason::detect(&entry, || 0xFE, 0x01),
+        Some(CrashReason::Lockup(_))
+    ));
+    assert!(CrashReason::detect(&entry, || 0xFD, 0x01).is_none());
+    entry.ime = true;
+    assert!(CrashReason::detect(&entry, || 0xFE, 0x01).is_none());
+
+    let entry = HistoryEntry::new(&cpu, 1, 0x76);
+    assert!(matches!(
+        CrashReason::detect(&entry, || 0x00, 0x00),
+        Some(CrashReason::Lockup(_))
+    ));
+    assert!(CrashReason::detect(&entry, || 0x00, 0x01).is_none());
+}
@@ -0,0 +1,186 @@
+use std::{
+    fmt,
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+use crate::gameboy::Gameboy;
+
+use super::breakpoint::interrupt_name;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum IoEventKind {
+    Write {
+        addr: u16,
+        value: u8,
+    },
+    /// a flag rose in IF, either raised by the hardware or written by the cpu
+    InterruptRequest {
+        vector: u16,
+    },
+}
+
+/// an event with the ppu position it happened at
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct IoEvent {
+    pub ly: u8,
+    pub dot: u16,
+    pub kind: IoEventKind,
+}
+
+impl fmt::Display for IoEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "LY {:>3} dot {:>3}: ", self.ly, self.dot)?;
+        match self.kind {
+            IoEventKind::Write { addr, value } => write!(f, "{:#04X} written to {:#06X}", value, addr),
+            IoEventKind::InterruptRequest { vector } => write!(f, "{} interrupt requested", interrupt_name(vector)),
+        }
+    }
+}
+
+/// Records the IO writes and interrupt requests of the running frame,
+/// the events of the last finished frame are kept for the timeline and the csv export.
+pub struct EventLog {
+    current: Vec<IoEvent>,
+    last_frame: Vec<IoEvent>,
+    frame: u64,
+    // IF after the last tick, bits rising since then are requests
+    interrupt_flags: u8,
+}
+
+impl EventLog {
+    pub fn new() -> Self {
+        Self {
+            current: Vec::new(),
+            last_frame: Vec::new(),
+            frame: 0,
+            interrupt_flags: 0,
+        }
+    }
+
+    pub fn push(&mut self, ly: u8, dot: u16, kind: IoEventKind) {
+        self.current.push(IoEvent { ly, dot, kind });
+    }
+
+    pub fn on_interrupt_flags(&mut self, ly: u8, dot: u16, flags: u8) {
+        let requested = flags & !self.interrupt_flags;
+        self.interrupt_flags = flags;
+        for bit in 0..5 {
+            if requested & (1 << bit) != 0 {
+                self.push(ly, dot, IoEventKind::InterruptRequest { vector: 0x40 + bit * 8 });
+            }
+        }
+    }
+
+    pub fn end_frame(&mut self) {
+        self.last_frame = std::mem::take(&mut self.current);
+        self.frame += 1;
+    }
+
+    pub fn last_frame(&self) -> &[IoEvent] {
+        &self.last_frame
+    }
+
+    /// number of the last finished frame since logging started
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    pub fn write_csv(&self, writer: &mut impl Write) -> io::Result<()> {
+        writeln!(writer, "frame,ly,dot,event,address,value")?;
+        for event in self.last_frame.iter() {
+            match event.kind {
+                IoEventKind::Write { addr, value } => writeln!(
+                    writer,
+                    "{},{},{},write,{:#06X},{:#04X}",
+                    self.frame, event.ly, event.dot, addr, value
+                )?,
+                IoEventKind::InterruptRequest { vector } => writeln!(
+                    writer,
+                    "{},{},{},{} interrupt,{:#06X},",
+                    self.frame,
+                    event.ly,
+                    event.dot,
+                    interrupt_name(vector),
+                    vector
+                )?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for EventLog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Gameboy {
+    /// called for writes to the io registers and IE
+    pub(in crate::gameboy) fn log_io_write(&mut self, addr: u16, value: u8) {
+        if let Some(event_log) = self.debugger.event_log.as_mut() {
+            event_log.push(
+                self.ppu.get_ly(),
+                self.ppu.get_line_dot(),
+                IoEventKind::Write { addr, value },
+            );
+        }
+    }
+
+    /// called after every tick, the hardware raises interrupt flags in many places
+    pub(in crate::gameboy) fn log_interrupt_requests(&mut self) {
+        if let Some(event_log) = self.debugger.event_log.as_mut() {
+            let flags = self.cpu.if_register.clone().into();
+            event_log.on_interrupt_flags(self.ppu.get_ly(), self.ppu.get_line_dot(), flags);
+        }
+    }
+
+    /// writes the events of the last finished frame
+    pub fn save_event_log(&self, path: &Path) -> io::Result<()> {
+        match self.debugger.event_log.as_ref() {
+            Some(event_log) => {
+                let mut writer = BufWriter::new(File::create(path)?);
+                event_log.write_csv(&mut writer)?;
+                writer.flush()
+            }
+            None => Err(io::Error::other("event log not enabled")),
+        }
+    }
+}
+
+#[test]
+fn event_log_test() {
+    let mut event_log = EventLog::new();
+    event_log.push(
+        10,
+        252,
+        IoEventKind::Write {
+            addr: 0xFF42,
+            value: 0x08,
+        },
+    );
+    event_log.on_interrupt_flags(144, 4, 0x01);
+    event_log.on_interrupt_flags(144, 8, 0x01);
+    event_log.on_interrupt_flags(150, 0, 0x05);
+    assert_eq!(event_log.last_frame().len(), 0);
+
+    event_log.end_frame();
+    assert_eq!(event_log.last_frame().len(), 3);
+    assert_eq!(
+        event_log.last_frame()[2],
+        IoEvent {
+            ly: 150,
+            dot: 0,
+            kind: IoEventKind::InterruptRequest { vector: 0x50 }
+        }
+    );
+
+    let mut csv = Vec::new();
+    event_log.write_csv(&mut csv).unwrap();
+    let csv = String::from_utf8(csv).unwrap();
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines[1], "1,10,252,write,0xFF42,0x08");
+    assert_eq!(lines[2], "1,144,4,vblank interrupt,0x0040,");
+}
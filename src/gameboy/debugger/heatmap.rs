@@ -0,0 +1,137 @@
+use crate::gameboy::{cpu::CPU, memory, Gameboy};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Access {
+    Read = 0,
+    Write = 1,
+    Execute = 2,
+}
+
+/// heat of the reads, writes and executes of one byte
+pub type Heat = [u8; 3];
+
+/// Decaying access counts over the address space and over the banked memory behind it.
+/// Every access adds heat, every frame takes away a sixteenth, so a byte touched once fades out
+/// within a second while bytes accessed every frame stay saturated.
+pub struct AccessHeatmap {
+    address_space: Vec<Heat>,
+    // indexed by rom offset and by bank * 0x2000 + offset into external ram
+    rom: Vec<Heat>,
+    ram: Vec<Heat>,
+}
+
+impl AccessHeatmap {
+    const INCREMENT: u8 = 64;
+
+    pub fn new(rom_size: usize) -> Self {
+        Self {
+            address_space: vec![[0; 3]; 0x10000],
+            rom: vec![[0; 3]; rom_size],
+            ram: Vec::new(),
+        }
+    }
+
+    fn heat(heat: &mut Heat, access: Access) {
+        let heat = &mut heat[access as usize];
+        *heat = heat.saturating_add(Self::INCREMENT);
+    }
+
+    pub fn log(&mut self, addr: u16, access: Access) {
+        Self::heat(&mut self.address_space[addr as usize], access);
+    }
+
+    pub fn log_rom(&mut self, offset: usize, access: Access) {
+        if let Some(heat) = self.rom.get_mut(offset) {
+            Self::heat(heat, access);
+        }
+    }
+
+    pub fn log_ram(&mut self, offset: usize, access: Access) {
+        if offset >= self.ram.len() {
+            // external ram is only tracked up to the highest bank that was accessed
+            let banks = offset / memory::cartridge::EXTERNAL_RAM.size + 1;
+            self.ram.resize(banks * memory::cartridge::EXTERNAL_RAM.size, [0; 3]);
+        }
+        Self::heat(&mut self.ram[offset], access);
+    }
+
+    pub fn decay(&mut self) {
+        for heat in self
+            .address_space
+            .iter_mut()
+            .chain(self.rom.iter_mut())
+            .chain(self.ram.iter_mut())
+            .flatten()
+        {
+            *heat -= *heat / 16 + (*heat > 0 && *heat < 16) as u8;
+        }
+    }
+
+    pub fn address_space(&self) -> &[Heat] {
+        &self.address_space
+    }
+
+    pub fn rom(&self) -> &[Heat] {
+        &self.rom
+    }
+
+    pub fn ram(&self) -> &[Heat] {
+        &self.ram
+    }
+}
+
+impl Gameboy {
+    pub fn enable_heatmap(&mut self) {
+        let mut heatmap = self.debugger.heatmap.borrow_mut();
+        if heatmap.is_none() {
+            *heatmap = Some(AccessHeatmap::new(self.cartridge.rom_size()));
+        }
+    }
+
+    /// called for cpu reads and writes
+    pub(in crate::gameboy) fn heatmap_log(&self, addr: u16, access: Access) {
+        if let Some(heatmap) = self.debugger.heatmap.borrow_mut().as_mut() {
+            heatmap.log(addr, access);
+            if let Some(offset) = self.cartridge.rom_offset(addr) {
+                heatmap.log_rom(offset, access);
+            } else if addr >= memory::cartridge::EXTERNAL_RAM.begin && addr <= memory::cartridge::EXTERNAL_RAM.end {
+                let offset = self.cartridge.ram_bank() * memory::cartridge::EXTERNAL_RAM.size
+                    + (addr - memory::cartridge::EXTERNAL_RAM.begin) as usize;
+                heatmap.log_ram(offset, access);
+            }
+        }
+    }
+
+    /// marks all bytes of the instruction at pc as executed
+    pub(in crate::gameboy) fn heatmap_log_instruction(&self, pc: u16, opcode: u8) {
+        if self.debugger.heatmap.borrow().is_none() {
+            return;
+        }
+        for i in 0..CPU::instruction_length(opcode) {
+            self.heatmap_log(pc.wrapping_add(i), Access::Execute);
+        }
+    }
+}
+
+#[test]
+fn heatmap_test() {
+    let mut heatmap = AccessHeatmap::new(0x8000);
+    heatmap.log(0xC000, Access::Write);
+    for _ in 0..4 {
+        heatmap.log(0x0150, Access::Execute);
+        heatmap.log_rom(0x0150, Access::Execute);
+    }
+    heatmap.log_ram(0x2001, Access::Read);
+    assert_eq!(heatmap.address_space()[0xC000], [0, 64, 0]);
+    assert_eq!(heatmap.address_space()[0x0150], [0, 0, 255]);
+    assert_eq!(heatmap.rom()[0x0150], [0, 0, 255]);
+    assert_eq!(heatmap.ram().len(), 0x4000);
+    assert_eq!(heatmap.ram()[0x2001], [64, 0, 0]);
+
+    heatmap.decay();
+    assert_eq!(heatmap.address_space()[0xC000], [0, 60, 0]);
+    for _ in 0..120 {
+        heatmap.decay();
+    }
+    assert_eq!(heatmap.address_space()[0x0150], [0, 0, 0]);
+}
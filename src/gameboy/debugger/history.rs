@@ -0,0 +1,169 @@
+use std::{collections::VecDeque, fmt};
+
+use crate::gameboy::{cpu::CPU, Gameboy};
+
+/// Snapshot of the cpu taken right before an instruction is executed. Only the opcode is fetched while
+/// running, the operands are read when the entry is printed.
+#[derive(Clone, Debug)]
+pub struct HistoryEntry {
+    pub pc: u16,
+    pub bank: usize,
+    pub opcode: u8,
+    pub a: u8,
+    pub f: u8,
+    pub b: u8,
+    pub c: u8,
+    pub d: u8,
+    pub e: u8,
+    pub h: u8,
+    pub l: u8,
+    pub sp: u16,
+    pub ime: bool,
+}
+
+impl HistoryEntry {
+    pub fn new(cpu: &CPU, bank: usize, opcode: u8) -> Self {
+        Self {
+            pc: cpu.pc,
+            bank,
+            opcode,
+            a: cpu.a,
+            f: cpu.f,
+            b: cpu.b,
+            c: cpu.c,
+            d: cpu.d,
+            e: cpu.e,
+            h: cpu.h,
+            l: cpu.l,
+            sp: cpu.sp,
+            ime: cpu.interrupt_master_enable,
+        }
+    }
+
+    /// the entry together with the two bytes following the opcode
+    pub fn decode(&self, operands: [u8; 2]) -> DecodedEntry<'_> {
+        DecodedEntry { entry: self, operands }
+    }
+}
+
+pub struct DecodedEntry<'a> {
+    entry: &'a HistoryEntry,
+    operands: [u8; 2],
+}
+
+impl fmt::Display for DecodedEntry<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let entry = self.entry;
+        write!(
+            f,
+            "{:02X}:{:04X}  {:02X} {:02X} {:02X}  {:<14} AF={:02X}{:02X} BC={:02X}{:02X} DE={:02X}{:02X} HL={:02X}{:02X} SP={:04X} IME={}",
+            entry.bank,
+            entry.pc,
+            entry.opcode,
+            self.operands[0],
+            self.operands[1],
+            CPU::mnemonic(entry.opcode, self.operands[0]),
+            entry.a,
+            entry.f,
+            entry.b,
+            entry.c,
+            entry.d,
+            entry.e,
+            entry.h,
+            entry.l,
+            entry.sp,
+            entry.ime as u8
+        )
+    }
+}
+
+impl Gameboy {
+    /// Reads the operands of a history entry from the rom bank it was executed in. Code outside of rom is
+    /// read as it is now, it may have been overwritten since.
+    pub(super) fn decode_history_entry<'a>(&self, entry: &'a HistoryEntry) -> DecodedEntry<'a> {
+        let operand = |offset: u16| {
+            let addr = entry.pc.wrapping_add(offset);
+            self.cartridge
+                .read_rom_bank(entry.bank, addr)
+                .unwrap_or_else(|| self.read8_unlocked(addr))
+        };
+        entry.decode([operand(1), operand(2)])
+    }
+}
+
+/// Ring buffer of the most recently executed instructions
+#[derive(Clone)]
+pub struct InstructionHistory {
+    entries: VecDeque<HistoryEntry>,
+    capacity: usize,
+}
+
+impl InstructionHistory {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    pub fn push(&mut self, entry: HistoryEntry) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.entries.len() >= self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(entry);
+    }
+
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        while self.entries.len() > capacity {
+            self.entries.pop_front();
+        }
+    }
+
+    /// oldest entry first
+    pub fn iter(&self) -> impl Iterator<Item = &HistoryEntry> {
+        self.entries.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+}
+
+#[test]
+fn instruction_history_test() {
+    let cpu = CPU::new();
+    let mut history = InstructionHistory::new(3);
+    for pc in 0..5 {
+        let mut entry = HistoryEntry::new(&cpu, 0, 0x00);
+        entry.pc = pc;
+        history.push(entry);
+    }
+    assert_eq!(history.len(), 3);
+    assert_eq!(
+        history.iter().map(|entry| entry.pc).collect::<Vec<u16>>(),
+        vec![2, 3, 4]
+    );
+
+    history.set_capacity(1);
+    assert_eq!(history.iter().next().unwrap().pc, 4);
+}
+
+#[test]
+fn decode_history_entry_test() {
+    let mut rom = vec![0; 0x10000];
+    rom[0x147] = 0x01; // MBC1
+    rom[0x148] = 0x01; // 4 banks
+    rom[2 * 0x4000..2 * 0x4000 + 3].copy_from_slice(&[0xC3, 0x34, 0x12]);
+    let gb = Gameboy::headless("decode_history_entry", &rom);
+    let mut entry = HistoryEntry::new(&gb.cpu, 2, 0xC3);
+    entry.pc = 0x4000;
+    // the operands come from the bank the instruction was executed in, not from the mapped one
+    assert!(gb
+        .decode_history_entry(&entry)
+        .to_string()
+        .starts_with("02:4000  C3 34 12"));
+}
@@ -0,0 +1,327 @@
+use std::{
+    cmp::Reverse,
+    collections::HashMap,
+    fmt::{self, Write as _},
+    fs, io,
+    path::PathBuf,
+};
+
+use crate::gameboy::{cpu::CPU, Gameboy};
+
+const CALL_OPCODES: [u8; 5] = [0xC4, 0xCC, 0xCD, 0xD4, 0xDC];
+const HOTTEST_INSTRUCTIONS: usize = 100;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum Frame {
+    Routine { bank: usize, addr: u16 },
+    Interrupt(u16),
+}
+
+impl fmt::Display for Frame {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Frame::Routine { bank, addr } => write!(f, "{:02X}:{:04X}", bank, addr),
+            Frame::Interrupt(vector) => match vector {
+                0x40 => write!(f, "int_vblank"),
+                0x48 => write!(f, "int_stat"),
+                0x50 => write!(f, "int_timer"),
+                0x58 => write!(f, "int_serial"),
+                0x60 => write!(f, "int_joypad"),
+                _ => write!(f, "int_{:04X}", vector),
+            },
+        }
+    }
+}
+
+#[derive(Default)]
+struct FrameCycles {
+    inclusive: u64,
+    exclusive: u64,
+    calls: u64,
+}
+
+struct StackEntry {
+    frame: Frame,
+    // stack pointer right after the return address was pushed, the frame is left once sp rises above it
+    sp: u16,
+}
+
+/// Accumulates executed t-cycles per instruction, per routine and per interrupt handler.
+/// Routines are tracked with a shadow call stack which is unwound by the stack pointer,
+/// so routines that drop their return address are left correctly as well.
+pub struct Profiler {
+    output_dir: PathBuf,
+    total_cycles: u64,
+    instructions: HashMap<(usize, u16), u64>,
+    frames: HashMap<Frame, FrameCycles>,
+    stack: Vec<StackEntry>,
+    folded_stacks: Vec<(Vec<Frame>, u64)>,
+    folded_index: HashMap<Vec<Frame>, usize>,
+    // index into folded_stacks for the current call stack, invalidated on every call and return
+    current_stack: Option<usize>,
+}
+
+impl Profiler {
+    pub fn new(output_dir: PathBuf) -> Self {
+        Self {
+            output_dir,
+            total_cycles: 0,
+            instructions: HashMap::new(),
+            frames: HashMap::new(),
+            stack: Vec::new(),
+            folded_stacks: Vec::new(),
+            folded_index: HashMap::new(),
+            current_stack: None,
+        }
+    }
+
+    /// called after the instruction at bank:pc was executed, the cpu already holds the following state
+    pub fn on_instruction(&mut self, bank: usize, pc: u16, opcode: u8, cycles: u64, next_bank: usize, cpu: &CPU) {
+        self.total_cycles += cycles;
+        *self.instructions.entry((bank, pc)).or_default() += cycles;
+        self.add_stack_cycles(cycles);
+
+        while matches!(self.stack.last(), Some(entry) if entry.sp < cpu.sp) {
+            self.stack.pop();
+            self.current_stack = None;
+        }
+
+        let is_call = CALL_OPCODES.contains(&opcode) || opcode & 0xC7 == 0xC7;
+        if is_call && cpu.pc != pc.wrapping_add(CPU::instruction_length(opcode)) {
+            self.enter(
+                Frame::Routine {
+                    bank: next_bank,
+                    addr: cpu.pc,
+                },
+                cpu.sp,
+            );
+        }
+    }
+
+    pub fn on_interrupt(&mut self, vector: u16, sp: u16) {
+        self.enter(Frame::Interrupt(vector), sp);
+    }
+
+    fn enter(&mut self, frame: Frame, sp: u16) {
+        self.frames.entry(frame).or_default().calls += 1;
+        self.stack.push(StackEntry { frame, sp });
+        self.current_stack = None;
+    }
+
+    fn add_stack_cycles(&mut self, cycles: u64) {
+        for entry in self.stack.iter() {
+            self.frames.entry(entry.frame).or_default().inclusive += cycles;
+        }
+        if let Some(entry) = self.stack.last() {
+            self.frames.entry(entry.frame).or_default().exclusive += cycles;
+        }
+
+        let index = match self.current_stack {
+            Some(index) => index,
+            None => {
+                let frames: Vec<Frame> = self.stack.iter().map(|entry| entry.frame).collect();
+                let index = match self.folded_index.get(&frames) {
+                    Some(index) => *index,
+                    None => {
+                        self.folded_stacks.push((frames.clone(), 0));
+                        self.folded_index.insert(frames, self.folded_stacks.len() - 1);
+                        self.folded_stacks.len() - 1
+                    }
+                };
+                self.current_stack = Some(index);
+                index
+            }
+        };
+        self.folded_stacks[index].1 += cycles;
+    }
+
+    fn percent(&self, cycles: u64) -> f64 {
+        if self.total_cycles == 0 {
+            0.
+        } else {
+            cycles as f64 * 100. / self.total_cycles as f64
+        }
+    }
+
+    pub fn report(&self) -> String {
+        let mut report = String::new();
+        writeln!(report, "total: {} t-cycles", self.total_cycles).unwrap();
+
+        let mut frames: Vec<(&Frame, &FrameCycles)> = self.frames.iter().collect();
+        frames.sort_by_key(|(_, cycles)| Reverse(cycles.inclusive));
+        for (title, interrupts) in [("interrupt handlers", true), ("routines", false)] {
+            writeln!(report, "\n{}:", title).unwrap();
+            writeln!(report, "   inclusive        |    self            |   calls  | entry").unwrap();
+            for (frame, cycles) in frames
+                .iter()
+                .filter(|(frame, _)| matches!(frame, Frame::Interrupt(_)) == interrupts)
+            {
+                writeln!(
+                    report,
+                    "{:>12} {:>6.2}% | {:>12} {:>6.2}% | {:>8} | {}",
+                    cycles.inclusive,
+                    self.percent(cycles.inclusive),
+                    cycles.exclusive,
+                    self.percent(cycles.exclusive),
+                    cycles.calls,
+                    frame
+                )
+                .unwrap();
+            }
+        }
+
+        let mut instructions: Vec<(&(usize, u16), &u64)> = self.instructions.iter().collect();
+        instructions.sort_by_key(|(_, cycles)| Reverse(**cycles));
+        writeln!(report, "\nhottest {} instructions:", HOTTEST_INSTRUCTIONS).unwrap();
+        for ((bank, pc), cycles) in instructions.iter().take(HOTTEST_INSTRUCTIONS) {
+            writeln!(
+                report,
+                "{:>12} {:>6.2}% | {:02X}:{:04X}",
+                cycles,
+                self.percent(**cycles),
+                bank,
+                pc
+            )
+            .unwrap();
+        }
+        report
+    }
+
+    /// one line per call stack in the format read by flamegraph.pl and inferno
+    pub fn folded_stacks(&self) -> String {
+        let mut folded = String::new();
+        for (frames, cycles) in self.folded_stacks.iter().filter(|(_, cycles)| *cycles > 0) {
+            folded.push_str("reset");
+            for frame in frames {
+                write!(folded, ";{}", frame).unwrap();
+            }
+            writeln!(folded, " {}", cycles).unwrap();
+        }
+        folded
+    }
+
+    pub fn save(&self) -> io::Result<PathBuf> {
+        fs::create_dir_all(&self.output_dir)?;
+        fs::write(self.output_dir.join("profile.txt"), self.report())?;
+        fs::write(self.output_dir.join("profile.folded"), self.folded_stacks())?;
+        Ok(self.output_dir.clone())
+    }
+}
+
+impl Gameboy {
+    pub fn enable_profiler(&mut self, output_dir: PathBuf) {
+        self.debugger.profiler = Some(Profiler::new(output_dir));
+    }
+
+    pub fn save_profile(&self) {
+        if let Some(profiler) = &self.debugger.profiler {
+            match profiler.save() {
+                Ok(dir) => log::info!("profile written to {}", dir.display()),
+                Err(err) => log::error!("failed to write profile: {}", err),
+            }
+        }
+    }
+}
+
+#[test]
+fn profiler_test() {
+    let mut cpu = CPU::new();
+    let mut profiler = Profiler::new(PathBuf::new());
+
+    // call 0x4000 from 0x0150
+    cpu.sp = 0xDFFC;
+    cpu.pc = 0x4000;
+    profiler.on_instruction(0, 0x0150, 0xCD, 24, 1, &cpu);
+    cpu.pc = 0x4001;
+    profiler.on_instruction(1, 0x4000, 0x00, 4, 1, &cpu);
+
+    // vblank interrupt inside the routine, returns with reti
+    cpu.sp = 0xDFFA;
+    profiler.on_interrupt(0x40, cpu.sp);
+    cpu.pc = 0x0041;
+    profiler.on_instruction(0, 0x0040, 0x00, 4, 0, &cpu);
+    cpu.sp = 0xDFFC;
+    cpu.pc = 0x4001;
+    profiler.on_instruction(0, 0x0041, 0xD9, 16, 1, &cpu);
+
+    // ret back to the caller
+    cpu.sp = 0xDFFE;
+    cpu.pc = 0x0153;
+    profiler.on_instruction(1, 0x4001, 0xC9, 16, 0, &cpu);
+
+    assert_eq!(profiler.total_cycles, 64);
+    let routine = &profiler.frames[&Frame::Routine { bank: 1, addr: 0x4000 }];
+    assert_eq!((routine.inclusive, routine.exclusive, routine.calls), (40, 20, 1));
+    let vblank = &profiler.frames[&Frame::Interrupt(0x40)];
+    assert_eq!((vblank.inclusive, vblank.exclusive, vblank.calls), (20, 20, 1));
+    assert!(profiler.stack.is_empty());
+
+    let folded = profiler.folded_stacks();
+    assert!(folded.contains("reset 24\n"));
+    assert!(folded.contains("reset;01:4000 20\n"));
+    assert!(folded.contains("reset;01:4000;int_vblank 20\n"));
+}
+
+#[test]
+fn profiler_cpu_test() {
+    let mut rom = vec![0; 0x10000];
+    rom[0x147] = 0x01; // MBC1
+    rom[0x148] = 0x01; // 4 banks
+    #[rustfmt::skip]
+    let program = [
+        0x31, 0xFE, 0xDF, // ld sp,$DFFE
+        0x3E, 0x03,       // ld a,3
+        0xEA, 0x00, 0x20, // ld ($2000),a
+        0xCD, 0x00, 0x40, // call $4000
+        0xAF,             // xor a
+        0xC4, 0x00, 0x02, // call nz,$0200
+        0xCC, 0x00, 0x02, // call z,$0200
+        0xD7,             // rst $10
+        0x18, 0xFE,       // jr -2
+    ];
+    rom[0x100..0x100 + program.len()].copy_from_slice(&program);
+    rom[0x0010] = 0xC9;
+    rom[0x200..0x202].copy_from_slice(&[0x00, 0xC9]);
+    rom[3 * 0x4000..3 * 0x4000 + 2].copy_from_slice(&[0x00, 0xC9]);
+    let mut gb = Gameboy::headless("profiler_cpu", &rom);
+    gb.enable_profiler(PathBuf::new());
+    unsafe { gb.run_instructions(30) };
+
+    let profiler = gb.debugger.profiler.as_ref().unwrap();
+    // the routine is entered in the bank selected when the call executed, the not taken call is no call
+    let routine = |bank, addr| &profiler.frames[&Frame::Routine { bank, addr }];
+    assert_eq!(routine(3, 0x4000).calls, 1);
+    assert_eq!(routine(0, 0x0200).calls, 1);
+    assert_eq!(routine(0, 0x0010).calls, 1);
+    assert_eq!(profiler.frames.len(), 3);
+    let body = profiler.instructions[&(0, 0x0200)] + profiler.instructions[&(0, 0x0201)];
+    assert_eq!(
+        (routine(0, 0x0200).inclusive, routine(0, 0x0200).exclusive),
+        (body, body)
+    );
+    // every return unwound the shadow stack
+    assert!(profiler.stack.is_empty());
+}
+
+#[test]
+fn profiler_time_travel_test() {
+    // ld sp,$DFFE then a loop calling a routine at $0200
+    let mut rom = vec![0; 0x8000];
+    rom[0x100..0x109].copy_from_slice(&[0x31, 0xFE, 0xDF, 0xCD, 0x00, 0x02, 0xC3, 0x03, 0x01]);
+    rom[0x200..0x202].copy_from_slice(&[0x00, 0xC9]);
+    let mut gb = Gameboy::headless("profiler_time_travel", &rom);
+    gb.enable_profiler(PathBuf::new());
+    gb.enable_time_travel();
+    gb.enable_heatmap();
+    unsafe { gb.run_instructions(1000) };
+
+    let total_cycles = gb.debugger.profiler.as_ref().unwrap().total_cycles;
+    let calls = gb.debugger.profiler.as_ref().unwrap().frames[&Frame::Routine { bank: 0, addr: 0x0200 }].calls;
+    gb.reverse_step(10);
+    assert_eq!(gb.debugger.instructions, 990);
+    // the replayed instructions were already profiled
+    let profiler = gb.debugger.profiler.as_ref().unwrap();
+    assert_eq!(profiler.total_cycles, total_cycles);
+    assert_eq!(profiler.frames[&Frame::Routine { bank: 0, addr: 0x0200 }].calls, calls);
+    assert!(gb.debugger.heatmap().is_some());
+}
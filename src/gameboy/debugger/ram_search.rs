@@ -0,0 +1,264 @@
+use crate::gameboy::{memory, memory::MemoryRange, Gameboy};
+
+use super::parse_u16;
+
+/// ram that games keep their variables in, external ram is searched in the currently mapped bank
+const SEARCH_REGIONS: [MemoryRange; 3] = [memory::cartridge::EXTERNAL_RAM, memory::WRAM, memory::HRAM];
+const MAX_LISTED_CANDIDATES: usize = 32;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SearchSize {
+    Byte,
+    /// little endian like the cpu stores them
+    Word,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SearchFilter {
+    Equal,
+    Changed,
+    Increased,
+    Decreased,
+    Value(u16),
+}
+
+impl std::str::FromStr for SearchFilter {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let (filter, args) = text.split_once(' ').unwrap_or((text, ""));
+        match filter {
+            "eq" | "equal" => Ok(SearchFilter::Equal),
+            "ne" | "changed" => Ok(SearchFilter::Changed),
+            "inc" | "increased" => Ok(SearchFilter::Increased),
+            "dec" | "decreased" => Ok(SearchFilter::Decreased),
+            "=" | "value" => parse_value(args.trim())
+                .map(SearchFilter::Value)
+                .ok_or_else(|| format!("invalid value '{}'", args.trim())),
+            _ => Err(format!("unknown search filter '{}'", filter)),
+        }
+    }
+}
+
+/// decimal by default, hex with a `$` or `0x` prefix
+fn parse_value(text: &str) -> Option<u16> {
+    if text.starts_with('$') || text.starts_with("0x") || text.starts_with("0X") {
+        parse_u16(text)
+    } else {
+        text.parse().ok()
+    }
+}
+
+pub enum RamSearchCommand {
+    Start { size: SearchSize, bcd: bool },
+    Filter(SearchFilter),
+    List,
+}
+
+impl std::str::FromStr for RamSearchCommand {
+    type Err = String;
+
+    fn from_str(args: &str) -> Result<Self, Self::Err> {
+        let (command, rest) = args.split_once(' ').unwrap_or((args, ""));
+        match command {
+            "new" | "start" => {
+                let mut size = SearchSize::Byte;
+                let mut bcd = false;
+                for option in rest.split_whitespace() {
+                    match option {
+                        "8" => size = SearchSize::Byte,
+                        "16" => size = SearchSize::Word,
+                        "bcd" => bcd = true,
+                        _ => return Err(format!("unknown search option '{}'", option)),
+                    }
+                }
+                Ok(RamSearchCommand::Start { size, bcd })
+            }
+            "" | "list" => Ok(RamSearchCommand::List),
+            _ => args.parse().map(RamSearchCommand::Filter),
+        }
+    }
+}
+
+pub struct RamSearchResult {
+    pub addr: u16,
+    pub previous: Option<u16>,
+    pub current: Option<u16>,
+}
+
+/// Narrows down the addresses of a game variable by comparing snapshots of the searched ram.
+/// Every filter compares against the snapshot taken by the previous filter or by the start of the search.
+pub struct RamSearch {
+    size: SearchSize,
+    bcd: bool,
+    snapshot: Vec<u8>,
+    candidates: Vec<u16>,
+}
+
+impl RamSearch {
+    /// `snapshot` holds the whole address space, only the searched regions are looked at
+    pub fn new(size: SearchSize, bcd: bool, snapshot: Vec<u8>) -> Self {
+        let width = match size {
+            SearchSize::Byte => 0,
+            SearchSize::Word => 1,
+        };
+        let candidates = SEARCH_REGIONS
+            .iter()
+            .flat_map(|region| region.begin..=region.end - width)
+            .collect();
+        Self {
+            size,
+            bcd,
+            snapshot,
+            candidates,
+        }
+    }
+
+    fn value(&self, memory: &[u8], addr: u16) -> Option<u16> {
+        let bytes: &[u8] = match self.size {
+            SearchSize::Byte => &memory[addr as usize..=addr as usize],
+            SearchSize::Word => &memory[addr as usize..=addr as usize + 1],
+        };
+        let mut value: u16 = 0;
+        for byte in bytes.iter().rev() {
+            value = if self.bcd {
+                if byte >> 4 > 9 || byte & 0xF > 9 {
+                    return None;
+                }
+                value * 100 + (byte >> 4) as u16 * 10 + (byte & 0xF) as u16
+            } else {
+                value << 8 | *byte as u16
+            };
+        }
+        Some(value)
+    }
+
+    /// keeps the candidates matching the filter, returns how many are left
+    pub fn filter(&mut self, filter: SearchFilter, snapshot: Vec<u8>) -> usize {
+        let candidates = std::mem::take(&mut self.candidates);
+        self.candidates = candidates
+            .into_iter()
+            .filter(|addr| {
+                let previous = self.value(&self.snapshot, *addr);
+                let current = self.value(&snapshot, *addr);
+                match (previous, current, filter) {
+                    (_, Some(current), SearchFilter::Value(value)) => current == value,
+                    (Some(previous), Some(current), SearchFilter::Equal) => current == previous,
+                    (Some(previous), Some(current), SearchFilter::Changed) => current != previous,
+                    (Some(previous), Some(current), SearchFilter::Increased) => current > previous,
+                    (Some(previous), Some(current), SearchFilter::Decreased) => current < previous,
+                    _ => false,
+                }
+            })
+            .collect();
+        self.snapshot = snapshot;
+        self.candidates.len()
+    }
+
+    pub fn candidates(&self) -> &[u16] {
+        &self.candidates
+    }
+
+    pub fn results(&self, snapshot: &[u8]) -> Vec<RamSearchResult> {
+        self.candidates
+            .iter()
+            .map(|addr| RamSearchResult {
+                addr: *addr,
+                previous: self.value(&self.snapshot, *addr),
+                current: self.value(snapshot, *addr),
+            })
+            .collect()
+    }
+}
+
+impl Gameboy {
+    fn ram_snapshot(&self) -> Vec<u8> {
+        let mut snapshot = vec![0; 0x10000];
+        for region in SEARCH_REGIONS.iter() {
+            for addr in region.begin..=region.end {
+                snapshot[addr as usize] = self.read8_unlocked(addr);
+            }
+        }
+        snapshot
+    }
+
+    /// starts a new search with every address of the searched ram as candidate
+    pub fn ram_search_start(&mut self, size: SearchSize, bcd: bool) -> usize {
+        let search = RamSearch::new(size, bcd, self.ram_snapshot());
+        let candidates = search.candidates().len();
+        self.debugger.ram_search = Some(search);
+        candidates
+    }
+
+    /// returns the number of candidates left, None if no search was started
+    pub fn ram_search_filter(&mut self, filter: SearchFilter) -> Option<usize> {
+        let snapshot = self.ram_snapshot();
+        self.debugger
+            .ram_search
+            .as_mut()
+            .map(|search| search.filter(filter, snapshot))
+    }
+
+    pub fn ram_search_results(&self) -> Vec<RamSearchResult> {
+        match &self.debugger.ram_search {
+            Some(search) => search.results(&self.ram_snapshot()),
+            None => Vec::new(),
+        }
+    }
+
+    pub(super) fn ram_search_command(&mut self, command: RamSearchCommand) {
+        match command {
+            RamSearchCommand::Start { size, bcd } => {
+                let candidates = self.ram_search_start(size, bcd);
+                println!("search started, {} candidates", candidates);
+            }
+            RamSearchCommand::Filter(filter) => match self.ram_search_filter(filter) {
+                Some(candidates) => println!("{} candidates left", candidates),
+                None => println!("no search started, use 'search new [8|16] [bcd]'"),
+            },
+            RamSearchCommand::List => {
+                let results = self.ram_search_results();
+                let format = |value: Option<u16>| value.map_or("-".to_string(), |value| value.to_string());
+                for result in results.iter().take(MAX_LISTED_CANDIDATES) {
+                    println!(
+                        "{:#06X}: {:>5} -> {:>5}",
+                        result.addr,
+                        format(result.previous),
+                        format(result.current)
+                    );
+                }
+                if results.len() > MAX_LISTED_CANDIDATES {
+                    println!("... {} more", results.len() - MAX_LISTED_CANDIDATES);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn ram_search_test() {
+    let mut memory = vec![0; 0x10000];
+    memory[0xC000] = 3;
+    memory[0xC100] = 0x99;
+    memory[0xC101] = 0x01;
+    let mut search = RamSearch::new(SearchSize::Byte, false, memory.clone());
+    assert_eq!(search.candidates().len(), 0x2000 + 0x2000 + 0x7F);
+
+    memory[0xC000] = 2;
+    assert_eq!(search.filter(SearchFilter::Decreased, memory.clone()), 1);
+    assert_eq!(search.filter(SearchFilter::Equal, memory.clone()), 1);
+    assert_eq!(search.filter(SearchFilter::Value(3), memory.clone()), 0);
+
+    // 199 stored as little endian bcd
+    let mut search = RamSearch::new(SearchSize::Word, true, memory.clone());
+    assert_eq!(search.filter(SearchFilter::Value(199), memory.clone()), 1);
+    memory[0xC100] = 0x00;
+    memory[0xC101] = 0x02;
+    assert_eq!(search.filter(SearchFilter::Increased, memory.clone()), 1);
+    assert_eq!(search.results(&memory)[0].current, Some(200));
+    memory[0xC100] = 0x0A;
+    assert_eq!(search.filter(SearchFilter::Changed, memory), 0);
+
+    assert_eq!("value $10".parse::<SearchFilter>(), Ok(SearchFilter::Value(0x10)));
+    assert_eq!("= 10".parse::<SearchFilter>(), Ok(SearchFilter::Value(10)));
+}
@@ -0,0 +1,428 @@
+use std::{
+    fmt,
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::gameboy::Gameboy;
+
+use super::parse_u16;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WatchType {
+    U8,
+    I8,
+    U16Le,
+    U16Be,
+    I16Le,
+    I16Be,
+    /// two decimal digits per byte, multi byte values little endian
+    Bcd8,
+    Bcd16,
+}
+
+impl WatchType {
+    const NAMES: [(&'static str, WatchType); 8] = [
+        ("u8", WatchType::U8),
+        ("i8", WatchType::I8),
+        ("u16", WatchType::U16Le),
+        ("u16be", WatchType::U16Be),
+        ("i16", WatchType::I16Le),
+        ("i16be", WatchType::I16Be),
+        ("bcd", WatchType::Bcd8),
+        ("bcd16", WatchType::Bcd16),
+    ];
+
+    pub fn width(&self) -> u16 {
+        match self {
+            WatchType::U8 | WatchType::I8 | WatchType::Bcd8 => 1,
+            _ => 2,
+        }
+    }
+
+    /// None for bcd bytes with a digit above 9
+    pub fn decode(&self, bytes: &[u8]) -> Option<i64> {
+        let le = || (bytes[1] as u16) << 8 | bytes[0] as u16;
+        let be = || (bytes[0] as u16) << 8 | bytes[1] as u16;
+        let bcd = |byte: u8| (byte >> 4 <= 9 && byte & 0xF <= 9).then(|| ((byte >> 4) * 10 + (byte & 0xF)) as i64);
+        match self {
+            WatchType::U8 => Some(bytes[0] as i64),
+            WatchType::I8 => Some(bytes[0] as i8 as i64),
+            WatchType::U16Le => Some(le() as i64),
+            WatchType::U16Be => Some(be() as i64),
+            WatchType::I16Le => Some(le() as i16 as i64),
+            WatchType::I16Be => Some(be() as i16 as i64),
+            WatchType::Bcd8 => bcd(bytes[0]),
+            WatchType::Bcd16 => Some(bcd(bytes[1])? * 100 + bcd(bytes[0])?),
+        }
+    }
+}
+
+impl std::str::FromStr for WatchType {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Self::NAMES
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, kind)| *kind)
+            .ok_or_else(|| format!("unknown watch type '{}'", name))
+    }
+}
+
+impl fmt::Display for WatchType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = Self::NAMES
+            .iter()
+            .find(|(_, kind)| kind == self)
+            .map_or("?", |(n, _)| n);
+        f.pad(name)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Watch {
+    pub addr: u16,
+    pub kind: WatchType,
+    pub name: String,
+}
+
+impl std::str::FromStr for Watch {
+    type Err = String;
+
+    /// `<addr> [type] [name]`, the type defaults to u8 and the name to the address
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let mut parts = line.split_whitespace();
+        let addr = parts.next().ok_or("missing address")?;
+        let addr = parse_u16(addr).ok_or_else(|| format!("invalid address '{}'", addr))?;
+        let kind = match parts.next() {
+            Some(kind) => kind.parse()?,
+            None => WatchType::U8,
+        };
+        let name = parts.collect::<Vec<&str>>().join(" ");
+        Ok(Watch {
+            addr,
+            kind,
+            name: if name.is_empty() { format!("{:04X}", addr) } else { name },
+        })
+    }
+}
+
+impl fmt::Display for Watch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:04X} {} {}", self.addr, self.kind, self.name)
+    }
+}
+
+/// One watch per line in the format parsed by `Watch::from_str`, empty lines and lines starting with `#` are ignored
+pub fn parse_watch_file(text: &str) -> Result<Vec<Watch>, String> {
+    text.lines()
+        .enumerate()
+        .map(|(i, line)| (i, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(i, line)| line.parse().map_err(|err| format!("line {}: {}", i + 1, err)))
+        .collect()
+}
+
+pub enum RamWatchCommand {
+    List,
+    Add(Watch),
+    Delete(usize),
+    Save,
+    Csv(PathBuf),
+}
+
+impl std::str::FromStr for RamWatchCommand {
+    type Err = String;
+
+    fn from_str(args: &str) -> Result<Self, Self::Err> {
+        let (command, rest) = args.split_once(' ').unwrap_or((args, ""));
+        let rest = rest.trim();
+        match command {
+            "" | "list" => Ok(RamWatchCommand::List),
+            "add" => rest.parse().map(RamWatchCommand::Add),
+            "del" | "delete" => rest
+                .parse()
+                .map(RamWatchCommand::Delete)
+                .map_err(|_| format!("invalid watch index {}", rest)),
+            "save" => Ok(RamWatchCommand::Save),
+            "csv" if !rest.is_empty() => Ok(RamWatchCommand::Csv(PathBuf::from(rest))),
+            "csv" => Err("watch csv expects a file".to_string()),
+            _ => Err(format!("unknown watch command '{}'", command)),
+        }
+    }
+}
+
+/// Named addresses read once per frame. The values of the last frame are kept for the watch window
+/// and can be streamed to a csv file with one row per frame.
+pub struct RamWatch {
+    watches: Vec<Watch>,
+    values: Vec<Option<i64>>,
+    previous: Vec<Option<i64>>,
+    // where `watch save` writes the list to
+    path: Option<PathBuf>,
+    csv: Option<BufWriter<File>>,
+    csv_path: Option<PathBuf>,
+    csv_header_pending: bool,
+    // a header was written to the current csv file, a changed list continues in a new one
+    csv_header_written: bool,
+    frame: u64,
+}
+
+impl RamWatch {
+    pub fn new() -> Self {
+        Self {
+            watches: Vec::new(),
+            values: Vec::new(),
+            previous: Vec::new(),
+            path: None,
+            csv: None,
+            csv_path: None,
+            csv_header_pending: false,
+            csv_header_written: false,
+            frame: 0,
+        }
+    }
+
+    pub fn watches(&self) -> &[Watch] {
+        &self.watches
+    }
+
+    /// value of the watch in the last frame and whether it changed since the frame before
+    pub fn value(&self, index: usize) -> (Option<i64>, bool) {
+        let value = self.values.get(index).copied().flatten();
+        (value, value != self.previous.get(index).copied().flatten())
+    }
+
+    fn set_watches(&mut self, watches: Vec<Watch>) {
+        self.watches = watches;
+        self.values = vec![None; self.watches.len()];
+        self.previous = self.values.clone();
+        self.csv_header_pending = true;
+    }
+
+    fn stream(&mut self, path: &Path) -> io::Result<()> {
+        self.csv = Some(BufWriter::new(File::create(path)?));
+        self.csv_path = Some(path.to_path_buf());
+        self.csv_header_pending = true;
+        self.csv_header_written = false;
+        Ok(())
+    }
+
+    fn update(&mut self, values: Vec<Option<i64>>) -> io::Result<()> {
+        self.previous = std::mem::replace(&mut self.values, values);
+        self.frame += 1;
+        if let Some(csv) = self.csv.as_mut() {
+            if self.csv_header_pending {
+                // every row of a file has the columns of its header, a changed list starts `<name>_<frame>.csv`
+                if let Some(path) = self.csv_path.as_ref().filter(|_| self.csv_header_written) {
+                    let mut file_name = path.file_stem().unwrap_or_default().to_os_string();
+                    file_name.push(format!("_{}", self.frame));
+                    let mut next_path = path.with_file_name(file_name);
+                    if let Some(extension) = path.extension() {
+                        next_path.set_extension(extension);
+                    }
+                    csv.flush()?;
+                    *csv = BufWriter::new(File::create(&next_path)?);
+                    log::info!("watch list changed, continuing the csv in {}", next_path.display());
+                }
+                let names: Vec<String> = self.watches.iter().map(|watch| watch.name.replace(',', " ")).collect();
+                writeln!(csv, "frame,{}", names.join(","))?;
+                self.csv_header_pending = false;
+                self.csv_header_written = true;
+            }
+            let values: Vec<String> = self
+                .values
+                .iter()
+                .map(|value| value.map_or(String::new(), |value| value.to_string()))
+                .collect();
+            writeln!(csv, "{},{}", self.frame, values.join(","))?;
+        }
+        Ok(())
+    }
+}
+
+impl Default for RamWatch {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Gameboy {
+    /// replaces the watch list, `watch save` writes back to the same file unless it could not be loaded
+    pub fn load_watches(&mut self, path: &Path) {
+        let watches = fs::read_to_string(path)
+            .map_err(|err| err.to_string())
+            .and_then(|text| parse_watch_file(&text));
+        match watches {
+            Ok(watches) => {
+                log::info!("loaded {} watches from {}", watches.len(), path.display());
+                self.debugger.ram_watch.set_watches(watches);
+                self.set_watch_file(path.to_path_buf());
+            }
+            Err(err) => log::error!("failed to load watches from {}: {}", path.display(), err),
+        }
+    }
+
+    pub fn set_watch_file(&mut self, path: PathBuf) {
+        self.debugger.ram_watch.path = Some(path);
+    }
+
+    pub fn save_watches(&self) -> io::Result<PathBuf> {
+        let ram_watch = &self.debugger.ram_watch;
+        let path = ram_watch
+            .path
+            .clone()
+            .ok_or_else(|| io::Error::other("no watch file"))?;
+        let mut text = String::from("# address type name\n");
+        for watch in ram_watch.watches.iter() {
+            text.push_str(&format!("{}\n", watch));
+        }
+        fs::write(&path, text)?;
+        Ok(path)
+    }
+
+    pub fn add_watch(&mut self, watch: Watch) {
+        let mut watches = self.debugger.ram_watch.watches.clone();
+        watches.push(watch);
+        self.debugger.ram_watch.set_watches(watches);
+    }
+
+    pub fn remove_watch(&mut self, index: usize) -> Option<Watch> {
+        let mut watches = self.debugger.ram_watch.watches.clone();
+        if index >= watches.len() {
+            return None;
+        }
+        let watch = watches.remove(index);
+        self.debugger.ram_watch.set_watches(watches);
+        Some(watch)
+    }
+
+    pub fn ram_watch(&self) -> &RamWatch {
+        &self.debugger.ram_watch
+    }
+
+    /// streams the watched values to the file from the next frame on, one row per frame
+    pub fn stream_watches(&mut self, path: &Path) -> io::Result<()> {
+        self.debugger.ram_watch.stream(path)
+    }
+
+    pub(in crate::gameboy) fn update_watches(&mut self) {
+        if self.debugger.ram_watch.watches.is_empty() {
+            return;
+        }
+        let values = self
+            .debugger
+            .ram_watch
+            .watches
+            .iter()
+            .map(|watch| {
+                let bytes: Vec<u8> = (0..watch.kind.width())
+                    .map(|i| self.read8_unlocked(watch.addr.wrapping_add(i)))
+                    .collect();
+                watch.kind.decode(&bytes)
+            })
+            .collect();
+        if let Err(err) = self.debugger.ram_watch.update(values) {
+            log::error!("failed to write watch csv: {}", err);
+            self.debugger.ram_watch.csv = None;
+        }
+    }
+
+    pub(super) fn ram_watch_command(&mut self, command: RamWatchCommand) {
+        match command {
+            RamWatchCommand::List => {
+                for (i, watch) in self.ram_watch().watches().iter().enumerate() {
+                    let value = self
+                        .ram_watch()
+                        .value(i)
+                        .0
+                        .map_or("-".to_string(), |value| value.to_string());
+                    println!(
+                        "{}: {:04X} {:<5} {:<20} {}",
+                        i, watch.addr, watch.kind, watch.name, value
+                    );
+                }
+            }
+            RamWatchCommand::Add(watch) => {
+                println!("watch {}: {}", self.ram_watch().watches().len(), watch);
+                self.add_watch(watch);
+            }
+            RamWatchCommand::Delete(index) => match self.remove_watch(index) {
+                Some(watch) => println!("removed watch {}: {}", index, watch),
+                None => println!("no watch {}", index),
+            },
+            RamWatchCommand::Save => match self.save_watches() {
+                Ok(path) => println!("watches written to {}", path.display()),
+                Err(err) => println!("failed to save watches: {}", err),
+            },
+            RamWatchCommand::Csv(path) => match self.stream_watches(&path) {
+                Ok(()) => println!("streaming watches to {}", path.display()),
+                Err(err) => println!("failed to create {}: {}", path.display(), err),
+            },
+        }
+    }
+}
+
+#[test]
+fn ram_watch_test() {
+    assert_eq!(WatchType::U16Le.decode(&[0x34, 0x12]), Some(0x1234));
+    assert_eq!(WatchType::U16Be.decode(&[0x34, 0x12]), Some(0x3412));
+    assert_eq!(WatchType::I8.decode(&[0xFF]), Some(-1));
+    assert_eq!(WatchType::I16Be.decode(&[0xFF, 0xFE]), Some(-2));
+    assert_eq!(WatchType::Bcd16.decode(&[0x99, 0x01]), Some(199));
+    assert_eq!(WatchType::Bcd8.decode(&[0x1A]), None);
+
+    let watches = parse_watch_file("# lives\nC0A0 u8 lives\n\n$C0A2 bcd16 score points\nff80").unwrap();
+    assert_eq!(
+        watches[1],
+        Watch {
+            addr: 0xC0A2,
+            kind: WatchType::Bcd16,
+            name: "score points".to_string()
+        }
+    );
+    assert_eq!(watches[2].kind, WatchType::U8);
+    assert_eq!(watches[2].name, "FF80");
+    assert_eq!(watches[1].to_string().parse::<Watch>(), Ok(watches[1].clone()));
+    assert!(parse_watch_file("C0A0 u32").is_err());
+
+    let mut ram_watch = RamWatch::new();
+    ram_watch.set_watches(watches);
+    ram_watch.update(vec![Some(3), Some(199), None]).unwrap();
+    ram_watch.update(vec![Some(2), Some(199), None]).unwrap();
+    assert_eq!(ram_watch.value(0), (Some(2), true));
+    assert_eq!(ram_watch.value(1), (Some(199), false));
+
+    // a changed list continues in a new file instead of adding a second header
+    let dir = std::env::temp_dir().join(format!("gameboy_ram_watch_test_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    ram_watch.stream(&dir.join("values.csv")).unwrap();
+    ram_watch.update(vec![Some(1), Some(100), None]).unwrap();
+    ram_watch.update(vec![Some(1), Some(100), None]).unwrap();
+    let mut watches = ram_watch.watches().to_vec();
+    watches.pop();
+    ram_watch.set_watches(watches);
+    ram_watch.update(vec![Some(0), Some(100)]).unwrap();
+    ram_watch.csv = None;
+    assert_eq!(
+        fs::read_to_string(dir.join("values.csv")).unwrap(),
+        "frame,lives,score points,FF80\n3,1,100,\n4,1,100,\n"
+    );
+    assert_eq!(
+        fs::read_to_string(dir.join("values_5.csv")).unwrap(),
+        "frame,lives,score points\n5,0,100\n"
+    );
+
+    // a file that could not be loaded is not the one `watch save` writes to
+    let mut gb = Gameboy::headless("ram_watch", &[0; 0x8000]);
+    let unparsable = dir.join("broken.wch");
+    fs::write(&unparsable, "C0A0 u32 lives\n").unwrap();
+    gb.load_watches(&unparsable);
+    gb.add_watch("C0A0 u8 lives".parse().unwrap());
+    assert_ne!(gb.debugger.ram_watch.path.as_ref(), Some(&unparsable));
+    assert_eq!(fs::read_to_string(&unparsable).unwrap(), "C0A0 u32 lives\n");
+
+    fs::remove_dir_all(&dir).unwrap();
+}
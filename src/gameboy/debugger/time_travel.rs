@@ -0,0 +1,357 @@
+use std::collections::VecDeque;
+
+use crate::gameboy::{Gameboy, GameboyModule};
+
+use super::history::InstructionHistory;
+
+/// Full state of the emulator at an instruction boundary
+pub struct Checkpoint {
+    pub instructions: u64,
+    pub frame: u64,
+    state: Vec<u8>,
+    history: InstructionHistory,
+}
+
+/// Checkpoints taken every `INTERVAL` instructions together with the buttons pressed in every frame since the oldest one.
+/// Going back restores the newest checkpoint before the target and executes forward again with the recorded buttons,
+/// which lands on exactly the same state as long as no script or memory editor changed memory in between.
+pub struct TimeTravel {
+    checkpoints: VecDeque<Checkpoint>,
+    // (frame, button mask) for every frame since the oldest checkpoint
+    inputs: VecDeque<(u64, u8)>,
+    frame: u64,
+}
+
+impl TimeTravel {
+    const INTERVAL: u64 = 100_000;
+    const MAX_CHECKPOINTS: usize = 64;
+
+    pub fn new() -> Self {
+        Self {
+            checkpoints: VecDeque::new(),
+            inputs: VecDeque::new(),
+            frame: 0,
+        }
+    }
+
+    pub fn is_due(&self, instructions: u64) -> bool {
+        self.checkpoints
+            .back()
+            .is_none_or(|checkpoint| instructions >= checkpoint.instructions + Self::INTERVAL)
+    }
+
+    pub fn push_checkpoint(&mut self, checkpoint: Checkpoint) {
+        self.checkpoints.push_back(checkpoint);
+        if self.checkpoints.len() > Self::MAX_CHECKPOINTS {
+            self.checkpoints.pop_front();
+        }
+        let oldest = self.checkpoints.front().map_or(0, |checkpoint| checkpoint.frame);
+        while self.inputs.front().is_some_and(|(frame, _)| *frame < oldest) {
+            self.inputs.pop_front();
+        }
+    }
+
+    /// called at the end of every frame, a frame that is run again replaces what was recorded for it before
+    pub fn record_input(&mut self, buttons: u8) {
+        while self.inputs.back().is_some_and(|(frame, _)| *frame >= self.frame) {
+            self.inputs.pop_back();
+        }
+        self.inputs.push_back((self.frame, buttons));
+        self.frame += 1;
+    }
+
+    /// buttons recorded at the end of a frame, none pressed if the frame was not recorded
+    pub fn input(&self, frame: u64) -> u8 {
+        self.inputs
+            .binary_search_by_key(&frame, |(frame, _)| *frame)
+            .map_or(0, |i| self.inputs[i].1)
+    }
+
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    pub fn checkpoints(&self) -> &VecDeque<Checkpoint> {
+        &self.checkpoints
+    }
+
+    /// index of the newest checkpoint at or before the instruction
+    pub fn checkpoint_before(&self, instructions: u64) -> Option<usize> {
+        self.checkpoints
+            .iter()
+            .rposition(|checkpoint| checkpoint.instructions <= instructions)
+    }
+
+    /// drops what was recorded after the point emulation went back to, it is recorded again from there on
+    fn discard_after(&mut self, instructions: u64, frame: u64) {
+        while self
+            .checkpoints
+            .back()
+            .is_some_and(|checkpoint| checkpoint.instructions > instructions)
+        {
+            self.checkpoints.pop_back();
+        }
+        self.frame = frame;
+    }
+}
+
+impl Default for TimeTravel {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Gameboy {
+    pub fn enable_time_travel(&mut self) {
+        if self.debugger.time_travel.is_none() {
+            self.debugger.time_travel = Some(TimeTravel::new());
+        }
+    }
+
+    /// called at every instruction boundary of the run loop
+    pub(in crate::gameboy) fn checkpoint_if_due(&mut self) {
+        let instructions = self.debugger.instructions;
+        let frame = match self.debugger.time_travel.as_ref() {
+            Some(time_travel) if time_travel.is_due(instructions) => time_travel.frame(),
+            _ => return,
+        };
+        let checkpoint = Checkpoint {
+            instructions,
+            frame,
+            state: self.save_state(),
+            history: self.debugger.history.clone(),
+        };
+        if let Some(time_travel) = self.debugger.time_travel.as_mut() {
+            time_travel.push_checkpoint(checkpoint);
+        }
+    }
+
+    /// called after the joypad was updated at the end of a frame
+    pub(in crate::gameboy) fn record_input(&mut self) {
+        let buttons = self.joypad.buttons();
+        if let Some(time_travel) = self.debugger.time_travel.as_mut() {
+            time_travel.record_input(buttons);
+        }
+    }
+
+    /// Restores a checkpoint and executes until the instruction count reaches the target, with `stop_at_break`
+    /// the first point with that count where a breakpoint fired. Returns the instruction counts breakpoints fired at
+    /// on the way and the frame emulation ended in. Of the frame end work of the run loop only cheats and the
+    /// recorded buttons are repeated, they change emulation. Left out are hooks, scripts, the watch list, frame
+    /// stepping, the event log and heatmap frames and the autosave of the cartridge, which all already ran when the
+    /// frames were emulated the first time. The profiler and the loggers do not see the instructions a second time
+    /// and the replayed audio is dropped.
+    fn replay(
+        &mut self,
+        time_travel: &TimeTravel,
+        checkpoint: usize,
+        target: u64,
+        stop_at_break: bool,
+    ) -> Result<(Vec<u64>, u64), String> {
+        let checkpoint = &time_travel.checkpoints[checkpoint];
+        self.load_state(&checkpoint.state)?;
+        self.debugger.history = checkpoint.history.clone();
+        self.debugger.instructions = checkpoint.instructions;
+        self.debugger.break_reason = None;
+        let mut frame = checkpoint.frame;
+
+        let hooks = std::mem::take(&mut self.hooks);
+        let profiler = self.debugger.profiler.take();
+        let code_data_logger = self.debugger.code_data_logger.take();
+        let event_log = self.debugger.event_log.take();
+        let heatmap = self.debugger.heatmap.take();
+        self.debugger.replaying = true;
+        let breaks = unsafe { self.replay_until(time_travel, &mut frame, target, stop_at_break) };
+        self.debugger.replaying = false;
+        self.apu.discard_samples();
+        self.debugger.heatmap.replace(heatmap);
+        self.debugger.event_log = event_log;
+        self.debugger.code_data_logger.replace(code_data_logger);
+        self.debugger.profiler = profiler;
+        self.hooks = hooks;
+        Ok((breaks?, frame))
+    }
+
+    unsafe fn replay_until(
+        &mut self,
+        time_travel: &TimeTravel,
+        frame: &mut u64,
+        target: u64,
+        stop_at_break: bool,
+    ) -> Result<Vec<u64>, String> {
+        let self_ptr = self as *mut Self;
+        let mut breaks = Vec::new();
+        // same order as the run loop, which hands over to the debugger before finishing a frame
+        loop {
+            if self.cpu.is_instruction_boundary() {
+                let instructions = self.debugger.instructions;
+                if instructions > target
+                    || (instructions == target && (!stop_at_break || self.debugger.is_break_pending()))
+                {
+                    self.debugger.break_reason = None;
+                    return Ok(breaks);
+                }
+                if self.debugger.break_reason.take().is_some() {
+                    breaks.push(instructions);
+                }
+            }
+            if let Some(frame_buffer) = self.ppu.get_frame_buffer() {
+                self.screen.set_frame_buffer(frame_buffer);
+                self.apply_cheats();
+                self.joypad.tick_with_buttons(self_ptr, time_travel.input(*frame));
+                // the frames were already heard, the run loop plays what comes after them
+                self.apu.discard_samples();
+                *frame += 1;
+            }
+            let tick_error = |_| "emulation failed during replay".to_string();
+            self.cpu.tick(self_ptr).map_err(tick_error)?;
+            self.ppu.tick(self_ptr).map_err(tick_error)?;
+            self.timer.tick(self_ptr).map_err(tick_error)?;
+            self.apu.tick(self_ptr).map_err(tick_error)?;
+            self.cartridge.tick(self_ptr).map_err(tick_error)?;
+        }
+    }
+
+    /// replays from the checkpoint and continues from there, what was recorded after it is recorded again
+    fn travel_to(&mut self, mut time_travel: TimeTravel, checkpoint: usize, target: u64, stop_at_break: bool) {
+        match self.replay(&time_travel, checkpoint, target, stop_at_break) {
+            Ok((_, frame)) => time_travel.discard_after(self.debugger.instructions, frame),
+            Err(err) => println!("replay failed: {}", err),
+        }
+        self.debugger.time_travel = Some(time_travel);
+    }
+
+    pub(super) fn reverse_step(&mut self, count: u64) {
+        let time_travel = match self.debugger.time_travel.take() {
+            Some(time_travel) => time_travel,
+            None => {
+                println!("time travel is not enabled");
+                return;
+            }
+        };
+        let target = self.debugger.instructions.saturating_sub(count);
+        let checkpoint = match time_travel.checkpoint_before(target) {
+            Some(checkpoint) => checkpoint,
+            None => {
+                match time_travel.checkpoints().front() {
+                    Some(oldest) => println!("history only reaches back to instruction {}", oldest.instructions),
+                    None => println!("no checkpoint taken yet"),
+                }
+                self.debugger.time_travel = Some(time_travel);
+                return;
+            }
+        };
+        self.travel_to(time_travel, checkpoint, target, false);
+        println!(
+            "[debugger] back at instruction {}, pc {:#06X}",
+            self.debugger.instructions, self.cpu.pc
+        );
+    }
+
+    /// goes back to the last point a breakpoint fired at, or to the oldest checkpoint if none did
+    pub(super) fn reverse_continue(&mut self) {
+        let time_travel = match self.debugger.time_travel.take() {
+            Some(time_travel) => time_travel,
+            None => {
+                println!("time travel is not enabled");
+                return;
+            }
+        };
+        // search the segments between checkpoints from the newest to the oldest
+        let mut end = self.debugger.instructions;
+        for checkpoint in (0..time_travel.checkpoints().len()).rev() {
+            let start = time_travel.checkpoints()[checkpoint].instructions;
+            if start >= end {
+                continue;
+            }
+            let breaks = match self.replay(&time_travel, checkpoint, end, false) {
+                Ok((breaks, _)) => breaks,
+                Err(err) => {
+                    println!("replay failed: {}", err);
+                    break;
+                }
+            };
+            if let Some(&last) = breaks.last() {
+                self.travel_to(time_travel, checkpoint, last, true);
+                println!(
+                    "[debugger] breakpoint hit at instruction {}, pc {:#06X}",
+                    self.debugger.instructions, self.cpu.pc
+                );
+                return;
+            }
+            end = start;
+        }
+        let oldest = match time_travel.checkpoints().front() {
+            Some(oldest) => oldest.instructions,
+            None => {
+                println!("no checkpoint taken yet");
+                self.debugger.time_travel = Some(time_travel);
+                return;
+            }
+        };
+        self.travel_to(time_travel, 0, oldest, false);
+        println!(
+            "[debugger] no breakpoint hit since instruction {}, pc {:#06X}",
+            self.debugger.instructions, self.cpu.pc
+        );
+    }
+}
+
+#[test]
+fn time_travel_test() {
+    let checkpoint = |instructions, frame| Checkpoint {
+        instructions,
+        frame,
+        state: Vec::new(),
+        history: InstructionHistory::new(0),
+    };
+    let mut time_travel = TimeTravel::new();
+    assert!(time_travel.is_due(0));
+    time_travel.push_checkpoint(checkpoint(0, 0));
+    assert!(!time_travel.is_due(TimeTravel::INTERVAL - 1));
+    assert!(time_travel.is_due(TimeTravel::INTERVAL));
+    for buttons in 1..=4 {
+        time_travel.record_input(buttons);
+    }
+    time_travel.push_checkpoint(checkpoint(TimeTravel::INTERVAL, 2));
+    assert_eq!(time_travel.input(2), 3);
+    assert_eq!(time_travel.input(9), 0);
+    assert_eq!(time_travel.checkpoint_before(TimeTravel::INTERVAL - 1), Some(0));
+    assert_eq!(time_travel.checkpoint_before(TimeTravel::INTERVAL), Some(1));
+
+    // going back before the newest checkpoint records the following frames again
+    time_travel.discard_after(10, 1);
+    assert_eq!(time_travel.checkpoints().len(), 1);
+    time_travel.record_input(0x80);
+    assert_eq!(time_travel.input(0), 1);
+    assert_eq!(time_travel.input(1), 0x80);
+    assert_eq!(time_travel.input(2), 0);
+
+    for i in 1..=TimeTravel::MAX_CHECKPOINTS as u64 {
+        time_travel.push_checkpoint(checkpoint(i * TimeTravel::INTERVAL, 1));
+    }
+    assert_eq!(time_travel.checkpoints().len(), TimeTravel::MAX_CHECKPOINTS);
+    assert_eq!(time_travel.checkpoint_before(0), None);
+    // inputs before the oldest checkpoint are dropped
+    assert_eq!(time_travel.input(0), 0);
+    assert_eq!(time_travel.input(1), 0x80);
+}
+
+#[test]
+fn replay_frame_end_test() {
+    // sound and lcd on, then an endless loop
+    let mut rom = vec![0; 0x8000];
+    rom[0x100..0x10A].copy_from_slice(&[0x3E, 0x80, 0xE0, 0x26, 0x3E, 0x91, 0xE0, 0x40, 0x18, 0xFE]);
+    let mut gb = Gameboy::headless("replay_frame_end", &rom);
+    gb.enable_time_travel();
+    unsafe { gb.run_instructions(20_000) };
+    gb.apu.discard_samples();
+    gb.debugger.frames_left = 5;
+
+    gb.reverse_step(10);
+    assert_eq!(gb.debugger.instructions, 19_990);
+    // several frames were replayed without being heard or counted by frame stepping
+    assert!(gb.debugger.time_travel.as_ref().unwrap().frame() > 2);
+    assert_eq!(gb.apu.pending_samples(), 0);
+    assert_eq!(gb.debugger.frames_left, 5);
+}
@@ -0,0 +1,259 @@
+use std::{
+    fmt::{self, Write as _},
+    fs::File,
+    io::{self, BufRead, BufReader},
+    path::Path,
+};
+
+use crate::gameboy::Gameboy;
+
+use super::history::HistoryEntry;
+
+const PRECEDING_INSTRUCTIONS: usize = 16;
+
+/// Registers of one line of a reference trace, registers the trace does not contain are not compared.
+/// Understands `A:01 F:B0 ... SP:FFFE PC:0100` as written for gameboy-doctor as well as `AF=01B0 BC=0013 ...`
+/// pairs like in the instruction history, other tokens are ignored.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct TraceState {
+    pub a: Option<u8>,
+    pub f: Option<u8>,
+    pub b: Option<u8>,
+    pub c: Option<u8>,
+    pub d: Option<u8>,
+    pub e: Option<u8>,
+    pub h: Option<u8>,
+    pub l: Option<u8>,
+    pub sp: Option<u16>,
+    pub pc: Option<u16>,
+}
+
+impl TraceState {
+    /// None if the line holds no register
+    pub fn parse(line: &str) -> Option<Self> {
+        let mut state = TraceState::default();
+        let mut found = false;
+        for token in line.split(|c: char| c.is_whitespace() || c == ',' || c == '|') {
+            let (name, value) = match token.split_once([':', '=']) {
+                Some(pair) => pair,
+                None => continue,
+            };
+            let value = match u16::from_str_radix(value.trim_start_matches('$'), 16) {
+                Ok(value) => value,
+                Err(_) => continue,
+            };
+            let (high, low) = (Some((value >> 8) as u8), Some(value as u8));
+            match name.to_ascii_uppercase().as_str() {
+                "A" => state.a = low,
+                "F" => state.f = low,
+                "B" => state.b = low,
+                "C" => state.c = low,
+                "D" => state.d = low,
+                "E" => state.e = low,
+                "H" => state.h = low,
+                "L" => state.l = low,
+                "AF" => (state.a, state.f) = (high, low),
+                "BC" => (state.b, state.c) = (high, low),
+                "DE" => (state.d, state.e) = (high, low),
+                "HL" => (state.h, state.l) = (high, low),
+                "SP" => state.sp = Some(value),
+                "PC" => state.pc = Some(value),
+                _ => continue,
+            }
+            found = true;
+        }
+        found.then_some(state)
+    }
+
+    /// registers that differ as `name emulator (reference value)`
+    pub fn differences(&self, entry: &HistoryEntry) -> Vec<String> {
+        let mut differences = Vec::new();
+        let mut compare8 = |name: &str, reference: Option<u8>, value: u8| {
+            if let Some(reference) = reference.filter(|reference| *reference != value) {
+                differences.push(format!("{} {:02X} (reference {:02X})", name, value, reference));
+            }
+        };
+        compare8("A", self.a, entry.a);
+        compare8("F", self.f, entry.f);
+        compare8("B", self.b, entry.b);
+        compare8("C", self.c, entry.c);
+        compare8("D", self.d, entry.d);
+        compare8("E", self.e, entry.e);
+        compare8("H", self.h, entry.h);
+        compare8("L", self.l, entry.l);
+        for (name, reference, value) in [("SP", self.sp, entry.sp), ("PC", self.pc, entry.pc)] {
+            if let Some(reference) = reference.filter(|reference| *reference != value) {
+                differences.push(format!("{} {:04X} (reference {:04X})", name, value, reference));
+            }
+        }
+        differences
+    }
+}
+
+pub struct Divergence {
+    pub line_number: usize,
+    pub reference: String,
+    pub differences: Vec<String>,
+}
+
+pub enum TraceCheck {
+    Match,
+    /// the reference has no more lines
+    Ended,
+    Diverged(Divergence),
+}
+
+/// Compares the cpu state before every instruction with the next line of a reference trace.
+/// Comparison starts at the first instruction at the pc of the first reference line,
+/// so traces that begin after the boot rom line up with emulation that runs it.
+pub struct TraceDiff {
+    lines: io::Lines<Box<dyn BufRead>>,
+    line_number: usize,
+    next: Option<(String, TraceState)>,
+    started: bool,
+    matched: u64,
+}
+
+impl TraceDiff {
+    pub fn new(reader: Box<dyn BufRead>) -> Self {
+        let mut trace_diff = Self {
+            lines: reader.lines(),
+            line_number: 0,
+            next: None,
+            started: false,
+            matched: 0,
+        };
+        trace_diff.advance();
+        trace_diff
+    }
+
+    fn advance(&mut self) {
+        self.next = None;
+        for line in self.lines.by_ref() {
+            self.line_number += 1;
+            let line = match line {
+                Ok(line) => line,
+                Err(err) => {
+                    log::error!("failed to read reference trace: {}", err);
+                    return;
+                }
+            };
+            if let Some(state) = TraceState::parse(&line) {
+                self.next = Some((line, state));
+                return;
+            }
+        }
+    }
+
+    /// instructions that matched the reference so far
+    pub fn matched(&self) -> u64 {
+        self.matched
+    }
+
+    pub fn check(&mut self, entry: &HistoryEntry) -> TraceCheck {
+        let (line, state) = match self.next.as_ref() {
+            Some(next) => next,
+            None => return TraceCheck::Ended,
+        };
+        if !self.started {
+            if state.pc.is_some_and(|pc| pc != entry.pc) {
+                return TraceCheck::Match;
+            }
+            self.started = true;
+        }
+        let differences = state.differences(entry);
+        if !differences.is_empty() {
+            return TraceCheck::Diverged(Divergence {
+                line_number: self.line_number,
+                reference: line.clone(),
+                differences,
+            });
+        }
+        self.matched += 1;
+        self.advance();
+        TraceCheck::Match
+    }
+}
+
+/// the instructions before the divergence followed by the diverging state and its registers
+pub fn divergence_report<T: fmt::Display>(
+    divergence: &Divergence,
+    matched: u64,
+    entry: T,
+    history: impl Iterator<Item = T>,
+) -> String {
+    let mut report = String::new();
+    writeln!(
+        report,
+        "trace diverged at reference line {} after {} matching instructions",
+        divergence.line_number, matched
+    )
+    .unwrap();
+    let history: Vec<T> = history.collect();
+    for previous in history
+        .iter()
+        .skip(history.len().saturating_sub(PRECEDING_INSTRUCTIONS))
+    {
+        writeln!(report, "             {}", previous).unwrap();
+    }
+    writeln!(report, "  emulator:  {}", entry).unwrap();
+    writeln!(report, "  reference: {}", divergence.reference.trim()).unwrap();
+    for difference in divergence.differences.iter() {
+        writeln!(report, "  {}", difference).unwrap();
+    }
+    report
+}
+
+impl Gameboy {
+    pub fn enable_trace_diff(&mut self, path: &Path) -> io::Result<()> {
+        let reader: Box<dyn BufRead> = Box::new(BufReader::new(File::open(path)?));
+        self.debugger.trace_diff = Some(TraceDiff::new(reader));
+        Ok(())
+    }
+}
+
+#[test]
+fn trace_diff_test() {
+    use crate::gameboy::cpu::CPU;
+
+    let state = TraceState::parse("A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0100 PCMEM:00,C3,13,02").unwrap();
+    assert_eq!(state.f, Some(0xB0));
+    assert_eq!(state.pc, Some(0x0100));
+    let state = TraceState::parse("00:0150  AF=1180 BC=0000 SP=DFFF").unwrap();
+    assert_eq!(
+        (state.a, state.f, state.c, state.h),
+        (Some(0x11), Some(0x80), Some(0x00), None)
+    );
+    assert_eq!(TraceState::parse("nothing here"), None);
+
+    let reference = "A:01 F:B0 PC:0100\n\nA:01 F:B0 PC:0101\nA:02 F:00 PC:0104\n";
+    let mut trace_diff = TraceDiff::new(Box::new(io::Cursor::new(reference)));
+    let cpu = CPU::new();
+    let mut entry = HistoryEntry::new(&cpu, 0, 0x00);
+    (entry.a, entry.f, entry.pc) = (0x01, 0xB0, 0x0000);
+    // waits for the pc of the first line
+    assert!(matches!(trace_diff.check(&entry), TraceCheck::Match));
+    assert_eq!(trace_diff.matched(), 0);
+    entry.pc = 0x0100;
+    assert!(matches!(trace_diff.check(&entry), TraceCheck::Match));
+    entry.pc = 0x0101;
+    assert!(matches!(trace_diff.check(&entry), TraceCheck::Match));
+    (entry.a, entry.f, entry.pc) = (0x02, 0x80, 0x0104);
+    let divergence = match trace_diff.check(&entry) {
+        TraceCheck::Diverged(divergence) => divergence,
+        _ => panic!("trace did not diverge"),
+    };
+    assert_eq!(divergence.line_number, 4);
+    assert_eq!(divergence.differences, vec!["F 80 (reference 00)".to_string()]);
+    let report = divergence_report(
+        &divergence,
+        trace_diff.matched(),
+        entry.decode([0x00, 0x00]),
+        std::iter::empty(),
+    );
+    assert!(report.starts_with("trace diverged at reference line 4 after 2 matching instructions"));
+
+    entry.f = 0x00;
+    assert!(matches!(trace_diff.check(&entry), TraceCheck::Match));
+    assert!(matches!(trace_diff.check(&entry), TraceCheck::Ended));
+}
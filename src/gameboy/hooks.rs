@@ -0,0 +1,234 @@
+use std::{cell::RefCell, ops::RangeInclusive};
+
+use super::{cpu::CPU, Gameboy};
+
+pub type HookId = usize;
+
+struct InstructionHook {
+    id: HookId,
+    addr: u16,
+    callback: Box<dyn FnMut(&CPU)>,
+}
+
+type MemoryCallback = Box<dyn FnMut(u16, u8)>;
+
+struct MemoryHook<C> {
+    id: HookId,
+    range: RangeInclusive<u16>,
+    callback: C,
+}
+
+struct EventHook<T> {
+    id: HookId,
+    callback: Box<dyn FnMut(T)>,
+}
+
+/// Callbacks registered by external tools. Every kind of hook has its own list
+/// so the emulator only checks for an empty list while nothing is registered.
+#[derive(Default)]
+pub struct Hooks {
+    next_id: HookId,
+    instructions: Vec<InstructionHook>,
+    // reads happen with shared access to the gameboy
+    reads: Vec<MemoryHook<RefCell<MemoryCallback>>>,
+    writes: Vec<MemoryHook<MemoryCallback>>,
+    interrupts: Vec<EventHook<u16>>,
+    frames: Vec<EventHook<u64>>,
+    rumble: Vec<EventHook<bool>>,
+    frame: u64,
+}
+
+impl Hooks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn next_id(&mut self) -> HookId {
+        self.next_id += 1;
+        self.next_id
+    }
+
+    pub fn remove(&mut self, id: HookId) -> bool {
+        let count = self.len();
+        self.instructions.retain(|hook| hook.id != id);
+        self.reads.retain(|hook| hook.id != id);
+        self.writes.retain(|hook| hook.id != id);
+        self.interrupts.retain(|hook| hook.id != id);
+        self.frames.retain(|hook| hook.id != id);
+        self.rumble.retain(|hook| hook.id != id);
+        self.len() != count
+    }
+
+    fn len(&self) -> usize {
+        self.instructions.len()
+            + self.reads.len()
+            + self.writes.len()
+            + self.interrupts.len()
+            + self.frames.len()
+            + self.rumble.len()
+    }
+
+    /// the cpu holds the state right before the instruction at pc is executed
+    #[inline(always)]
+    pub fn on_instruction(&mut self, cpu: &CPU) {
+        if self.instructions.is_empty() {
+            return;
+        }
+        for hook in self.instructions.iter_mut().filter(|hook| hook.addr == cpu.pc) {
+            (hook.callback)(cpu);
+        }
+    }
+
+    #[inline(always)]
+    pub fn on_read(&self, addr: u16, value: u8) {
+        if self.reads.is_empty() {
+            return;
+        }
+        for hook in self.reads.iter().filter(|hook| hook.range.contains(&addr)) {
+            (hook.callback.borrow_mut())(addr, value);
+        }
+    }
+
+    #[inline(always)]
+    pub fn on_write(&mut self, addr: u16, value: u8) {
+        if self.writes.is_empty() {
+            return;
+        }
+        for hook in self.writes.iter_mut().filter(|hook| hook.range.contains(&addr)) {
+            (hook.callback)(addr, value);
+        }
+    }
+
+    #[inline(always)]
+    pub fn on_interrupt(&mut self, vector: u16) {
+        if self.interrupts.is_empty() {
+            return;
+        }
+        for hook in self.interrupts.iter_mut() {
+            (hook.callback)(vector);
+        }
+    }
+
+    pub fn on_rumble(&mut self, on: bool) {
+        if self.rumble.is_empty() {
+            return;
+        }
+        for hook in self.rumble.iter_mut() {
+            (hook.callback)(on);
+        }
+    }
+
+    pub fn on_frame(&mut self) {
+        self.frame += 1;
+        for hook in self.frames.iter_mut() {
+            (hook.callback)(self.frame);
+        }
+    }
+}
+
+impl Gameboy {
+    /// called before the instruction at `addr` is executed
+    pub fn add_instruction_hook(&mut self, addr: u16, callback: impl FnMut(&CPU) + 'static) -> HookId {
+        let id = self.hooks.next_id();
+        self.hooks.instructions.push(InstructionHook {
+            id,
+            addr,
+            callback: Box::new(callback),
+        });
+        id
+    }
+
+    /// called with the address and the value after every bus read inside the range
+    pub fn add_read_hook(&mut self, range: RangeInclusive<u16>, callback: impl FnMut(u16, u8) + 'static) -> HookId {
+        let id = self.hooks.next_id();
+        self.hooks.reads.push(MemoryHook {
+            id,
+            range,
+            callback: RefCell::new(Box::new(callback)),
+        });
+        id
+    }
+
+    /// called with the address and the value before every bus write inside the range
+    pub fn add_write_hook(&mut self, range: RangeInclusive<u16>, callback: impl FnMut(u16, u8) + 'static) -> HookId {
+        let id = self.hooks.next_id();
+        self.hooks.writes.push(MemoryHook {
+            id,
+            range,
+            callback: Box::new(callback),
+        });
+        id
+    }
+
+    /// called with the interrupt vector whenever an interrupt is taken
+    pub fn add_interrupt_hook(&mut self, callback: impl FnMut(u16) + 'static) -> HookId {
+        let id = self.hooks.next_id();
+        self.hooks.interrupts.push(EventHook {
+            id,
+            callback: Box::new(callback),
+        });
+        id
+    }
+
+    /// called with the number of finished frames at the end of every frame
+    pub fn add_frame_hook(&mut self, callback: impl FnMut(u64) + 'static) -> HookId {
+        let id = self.hooks.next_id();
+        self.hooks.frames.push(EventHook {
+            id,
+            callback: Box::new(callback),
+        });
+        id
+    }
+
+    /// called whenever the rumble motor of the cartridge starts or stops
+    pub fn add_rumble_hook(&mut self, callback: impl FnMut(bool) + 'static) -> HookId {
+        let id = self.hooks.next_id();
+        self.hooks.rumble.push(EventHook {
+            id,
+            callback: Box::new(callback),
+        });
+        id
+    }
+
+    /// returns false if there is no hook with the given id
+    pub fn remove_hook(&mut self, id: HookId) -> bool {
+        self.hooks.remove(id)
+    }
+}
+
+#[test]
+fn hooks_test() {
+    use std::{cell::Cell, rc::Rc};
+
+    let mut hooks = Hooks::new();
+    let writes = Rc::new(Cell::new(0));
+    let counter = writes.clone();
+    let id = hooks.next_id();
+    hooks.writes.push(MemoryHook {
+        id,
+        range: 0xC000..=0xC0FF,
+        callback: Box::new(move |_, value| counter.set(counter.get() + value as u32)),
+    });
+    let frames = Rc::new(Cell::new(0));
+    let last_frame = frames.clone();
+    let frame_id = hooks.next_id();
+    hooks.frames.push(EventHook {
+        id: frame_id,
+        callback: Box::new(move |frame| last_frame.set(frame)),
+    });
+
+    hooks.on_write(0xC010, 2);
+    hooks.on_write(0xC100, 5);
+    hooks.on_write(0xC0FF, 3);
+    assert_eq!(writes.get(), 5);
+
+    hooks.on_frame();
+    hooks.on_frame();
+    assert_eq!(frames.get(), 2);
+
+    assert!(hooks.remove(id));
+    assert!(!hooks.remove(id));
+    hooks.on_write(0xC010, 2);
+    assert_eq!(writes.get(), 5);
+    assert_eq!(hooks.len(), 1);
+}
@@ -1,11 +1,15 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use log::{info};
 use minifb::Key;
 
 use crate::bit;
 
-use super::{memory, Gameboy, GameboyModule};
+use super::{
+    memory,
+    savestate::{SaveState, StateReader, StateWriter},
+    Gameboy, GameboyModule,
+};
 
 #[derive(Clone, Debug, Hash, Eq, PartialEq)]
 pub enum Button {
@@ -19,6 +23,24 @@ pub enum Button {
     DOWN,
 }
 
+impl std::str::FromStr for Button {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_lowercase().as_str() {
+            "a" => Ok(Button::A),
+            "b" => Ok(Button::B),
+            "select" => Ok(Button::SELECT),
+            "start" => Ok(Button::START),
+            "right" => Ok(Button::RIGHT),
+            "left" => Ok(Button::LEFT),
+            "up" => Ok(Button::UP),
+            "down" => Ok(Button::DOWN),
+            _ => Err(format!("unknown button '{}'", name)),
+        }
+    }
+}
+
 impl std::convert::From<Joypad> for u8 {
     fn from(jp: Joypad) -> u8 {
         let mut byte: u8 = 0x00;
@@ -61,6 +83,8 @@ pub struct Joypad {
     pub select: bool,
 
     key_pressed: bool,
+    // held independently of the keyboard, e.g. by scripts
+    held_buttons: HashSet<Button>,
 }
 
 impl super::MemoryInterface for Joypad {
@@ -98,7 +122,62 @@ impl GameboyModule for Joypad {
     }
 }
 
+impl SaveState for Joypad {
+    fn save_state(&self, writer: &mut StateWriter) {
+        for flag in [
+            self.unused_7th_bit,
+            self.unused_6th_bit,
+            self.action_buttons_select,
+            self.direction_buttons_select,
+            self.up,
+            self.down,
+            self.left,
+            self.right,
+            self.a,
+            self.b,
+            self.start,
+            self.select,
+            self.key_pressed,
+        ] {
+            writer.write_bool(flag);
+        }
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), String> {
+        for flag in [
+            &mut self.unused_7th_bit,
+            &mut self.unused_6th_bit,
+            &mut self.action_buttons_select,
+            &mut self.direction_buttons_select,
+            &mut self.up,
+            &mut self.down,
+            &mut self.left,
+            &mut self.right,
+            &mut self.a,
+            &mut self.b,
+            &mut self.start,
+            &mut self.select,
+            &mut self.key_pressed,
+        ] {
+            *flag = reader.read_bool()?;
+        }
+        Ok(())
+    }
+}
+
 impl Joypad {
+    // bit order of the button mask of `buttons`
+    const BUTTONS: [Button; 8] = [
+        Button::A,
+        Button::B,
+        Button::SELECT,
+        Button::START,
+        Button::RIGHT,
+        Button::LEFT,
+        Button::UP,
+        Button::DOWN,
+    ];
+
     pub fn new() -> Self {
         Self {
             key_map: HashMap::from([
@@ -124,6 +203,7 @@ impl Joypad {
             start: false,
             select: false,
             key_pressed: false,
+            held_buttons: HashSet::new(),
         }
     }
 
@@ -137,24 +217,80 @@ impl Joypad {
         self.up = false;
         self.down = false;
         for key in keys {
-            match self.key_map.get(key) {
+            match self.key_map.get(key).cloned() {
                 Some(value) => {
-                    match value {
-                        Button::A => self.a = true,
-                        Button::B => self.b = true,
-                        Button::SELECT => self.select = true,
-                        Button::START => self.start = true,
-                        Button::RIGHT => self.right = true,
-                        Button::LEFT => self.left = true,
-                        Button::UP => self.up = true,
-                        Button::DOWN => self.down = true,
-                    };
-                    self.key_pressed = true;
+                    self.press(&value);
                     info!("Button pressed: {:?}", value)
                 }
                 None => continue,
             }
         }
+        for button in self.held_buttons.clone().iter() {
+            self.press(button);
+        }
+    }
+
+    fn press(&mut self, button: &Button) {
+        match button {
+            Button::A => self.a = true,
+            Button::B => self.b = true,
+            Button::SELECT => self.select = true,
+            Button::START => self.start = true,
+            Button::RIGHT => self.right = true,
+            Button::LEFT => self.left = true,
+            Button::UP => self.up = true,
+            Button::DOWN => self.down = true,
+        };
+        self.key_pressed = true;
+    }
+
+    /// keeps a button pressed until it is released again, on top of the keyboard state
+    pub fn set_button(&mut self, button: Button, pressed: bool) {
+        if pressed {
+            self.held_buttons.insert(button);
+        } else {
+            self.held_buttons.remove(&button);
+        }
+    }
+
+    fn is_pressed(&self, button: &Button) -> bool {
+        match button {
+            Button::A => self.a,
+            Button::B => self.b,
+            Button::SELECT => self.select,
+            Button::START => self.start,
+            Button::RIGHT => self.right,
+            Button::LEFT => self.left,
+            Button::UP => self.up,
+            Button::DOWN => self.down,
+        }
+    }
+
+    /// pressed buttons as a mask, bit 0 is A up to bit 7 for down
+    pub fn buttons(&self) -> u8 {
+        Self::BUTTONS
+            .iter()
+            .enumerate()
+            .fold(0, |mask, (i, button)| mask | (self.is_pressed(button) as u8) << i)
+    }
+
+    /// like `tick` but presses the buttons of a recorded mask instead of reading the keyboard
+    pub(super) unsafe fn tick_with_buttons(&mut self, gb_ptr: *mut Gameboy, buttons: u8) {
+        let gb = &mut *gb_ptr;
+        self.handle_int(gb);
+        self.a = false;
+        self.b = false;
+        self.select = false;
+        self.start = false;
+        self.right = false;
+        self.left = false;
+        self.up = false;
+        self.down = false;
+        for (i, button) in Self::BUTTONS.iter().enumerate() {
+            if buttons & (1 << i) != 0 {
+                self.press(button);
+            }
+        }
     }
 
     fn handle_int(&mut self, gb: &mut Gameboy) {
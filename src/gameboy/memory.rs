@@ -1,4 +1,7 @@
-use super::MemoryInterface;
+use super::{
+    savestate::{SaveState, StateReader, StateWriter},
+    MemoryInterface,
+};
 pub struct Memory {
     ram: [u8; 0x10000],
 }
@@ -14,6 +17,16 @@ impl MemoryInterface for Memory {
     }
 }
 
+impl SaveState for Memory {
+    fn save_state(&self, writer: &mut StateWriter) {
+        writer.write_bytes(&self.ram);
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), String> {
+        reader.read_bytes(&mut self.ram)
+    }
+}
+
 impl Memory {
     pub fn new() -> Self {
         Self { ram: [0; 0x10000] }
@@ -32,6 +45,24 @@ pub const HRAM: MemoryRange = MemoryRange {
     size: 0x007F,
 };
 
+pub const WRAM: MemoryRange = MemoryRange {
+    begin: 0xC000,
+    end: 0xDFFF,
+    size: 0x2000,
+};
+
+pub const ECHO_RAM: MemoryRange = MemoryRange {
+    begin: 0xE000,
+    end: 0xFDFF,
+    size: 0x1E00,
+};
+
+pub const IO_REGISTERS: MemoryRange = MemoryRange {
+    begin: 0xFF00,
+    end: 0xFF7F,
+    size: 0x0080,
+};
+
 pub mod cartridge {
     use super::MemoryRange;
 
@@ -1,9 +1,9 @@
 mod fetcher;
 mod fifo;
-mod lcdc;
-mod palette;
-mod sprite;
-mod stat;
+pub mod lcdc;
+pub mod palette;
+pub mod sprite;
+pub mod stat;
 
 use crate::{bit, gameboy::memory, screen::MonochromeColor, utils};
 use colored::Colorize;
@@ -16,7 +16,11 @@ use self::{
     stat::{LCDModeFlag, LCDStatus},
 };
 
-use super::{Gameboy, GameboyModule};
+use super::{
+    debugger::DebugEvent,
+    savestate::{SaveState, StateReader, StateWriter},
+    Gameboy, GameboyModule,
+};
 
 pub struct PPU {
     frame_buffer: [u32; PPU::ROWS * PPU::COLUMNS],
@@ -63,7 +67,7 @@ impl GameboyModule for PPU {
             match self.stat.mode_flag {
                 LCDModeFlag::HBlank => self.handle_hblank(gb),
                 LCDModeFlag::VBlank => self.handle_vblank(gb),
-                LCDModeFlag::SearchingOAM => self.handle_oam_search(),
+                LCDModeFlag::SearchingOAM => self.handle_oam_search(gb),
                 LCDModeFlag::TransferringDataToLCD => {
                     self.fetcher.tick(gb_ptr)?;
                     let popped = self.fifo.tick(gb_ptr)?;
@@ -196,6 +200,62 @@ impl super::MemoryInterface for PPU {
     }
 }
 
+impl SaveState for PPU {
+    fn save_state(&self, writer: &mut StateWriter) {
+        for pixel in self.frame_buffer.iter().chain(self.back_buffer.iter()) {
+            writer.write_u32(*pixel);
+        }
+        writer.write_u32(self.back_buffer_index as u32);
+        writer.write_bytes(&self.vram);
+        writer.write_bytes(&self.oam);
+        writer.write_u8(self.lcdc.clone().into());
+        writer.write_u8(self.stat.clone().into());
+        for reg in [self.scy, self.scx, self.ly, self.lyc, self.dma, self.dma_cycles] {
+            writer.write_u8(reg);
+        }
+        writer.write_u8(self.bgp.clone().into());
+        writer.write_u8(self.obp0.clone().into());
+        writer.write_u8(self.obp1.clone().into());
+        writer.write_u8(self.wy);
+        writer.write_u8(self.wx);
+        self.fetcher.save_state(writer);
+        self.fifo.save_state(writer);
+        writer.write_u16(self.dots);
+        writer.write_bool(self.frame_ready);
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), String> {
+        for pixel in self.frame_buffer.iter_mut().chain(self.back_buffer.iter_mut()) {
+            *pixel = reader.read_u32()?;
+        }
+        self.back_buffer_index = reader.read_u32()? as usize;
+        reader.read_bytes(&mut self.vram)?;
+        reader.read_bytes(&mut self.oam)?;
+        self.lcdc = reader.read_u8()?.into();
+        self.stat = reader.read_u8()?.into();
+        for reg in [
+            &mut self.scy,
+            &mut self.scx,
+            &mut self.ly,
+            &mut self.lyc,
+            &mut self.dma,
+            &mut self.dma_cycles,
+        ] {
+            *reg = reader.read_u8()?;
+        }
+        self.bgp = reader.read_u8()?.into();
+        self.obp0 = reader.read_u8()?.into();
+        self.obp1 = reader.read_u8()?.into();
+        self.wy = reader.read_u8()?;
+        self.wx = reader.read_u8()?;
+        self.fetcher.load_state(reader)?;
+        self.fifo.load_state(reader)?;
+        self.dots = reader.read_u16()?;
+        self.frame_ready = reader.read_bool()?;
+        Ok(())
+    }
+}
+
 impl PPU {
     const ROWS: usize = 144;
     const COLUMNS: usize = 160;
@@ -203,6 +263,9 @@ impl PPU {
     const TILE_SIZE: usize = 8; //this is one line i.e. size*size=total pixels
     const BYTES_PER_TILE: usize = 16;
     const TILE_MAP_SIZE: usize = 32; //this is one line of tiles i.e. size*size=total tiles
+    const OAM_ENTRIES: usize = 40;
+    pub const DOTS_PER_LINE: u16 = 456;
+    pub const LINES_PER_FRAME: u16 = 154;
 
     pub fn new() -> Self {
         let mut ppu = Self {
@@ -253,6 +316,14 @@ impl PPU {
         }
     }
 
+    fn set_mode(&mut self, gb: &mut Gameboy, mode: LCDModeFlag) {
+        self.stat.mode_flag = mode;
+        gb.debugger.on_event(DebugEvent::PPUMode {
+            mode: mode as u8,
+            ly: self.ly,
+        });
+    }
+
     fn handle_dma(&mut self, gb: &Gameboy) {
         let oam_addr = 0x00A0 - self.dma_cycles;
         let src_addr = ((self.dma & 0xDF) as u16) << 8 | oam_addr as u16;
@@ -266,8 +337,9 @@ impl PPU {
         if self.dots == 0 {
             log::trace!("hblank fifo {}", self.fifo.bg_fifo.len());
 
+            self.ly += 1;
             if self.back_buffer_index == 0 {
-                self.stat.mode_flag = LCDModeFlag::VBlank;
+                self.set_mode(gb, LCDModeFlag::VBlank);
                 self.dots = 4560;
                 if gb.cpu.interrupt_master_enable {
                     if self.stat.mode1_vblank_interrupt_enable {
@@ -275,7 +347,7 @@ impl PPU {
                     }
                 }
             } else {
-                self.stat.mode_flag = LCDModeFlag::SearchingOAM;
+                self.set_mode(gb, LCDModeFlag::SearchingOAM);
                 self.dots = 80;
                 if gb.cpu.interrupt_master_enable {
                     if self.stat.mode2_oam_interrupt_enable {
@@ -283,7 +355,6 @@ impl PPU {
                     }
                 }
             }
-            self.ly += 1;
         }
     }
 
@@ -291,9 +362,9 @@ impl PPU {
         if self.dots == 0 {
             self.frame_ready = true;
             log::trace!("---vblank fifo {}", self.fifo.bg_fifo.len());
-            self.stat.mode_flag = LCDModeFlag::SearchingOAM;
-            self.dots = 80;
             self.ly = 0;
+            self.set_mode(gb, LCDModeFlag::SearchingOAM);
+            self.dots = 80;
             if gb.cpu.interrupt_master_enable {
                 if self.stat.mode2_oam_interrupt_enable {
                     gb.cpu.if_register.lcd_stat = true;
@@ -307,25 +378,33 @@ impl PPU {
         }
     }
 
-    fn handle_oam_search(&mut self) {
+    fn handle_oam_search(&mut self, gb: &mut Gameboy) {
         if self.dots == 0 {
-            self.stat.mode_flag = LCDModeFlag::TransferringDataToLCD;
+            self.set_mode(gb, LCDModeFlag::TransferringDataToLCD);
         } else if self.dots % 2 == 0 {
             //content takes 2 dots to complete
             let addr: usize = (40 - (self.dots as usize / 2 + 1)) * 4;
             let y_pos: u8 = self.oam[addr];
             let x_pos: u8 = self.oam[addr + 1];
-            if (x_pos != 0) && ((self.ly + 16) >= y_pos) {
-                if (!self.lcdc.obj_size && ((self.ly + 16) < (y_pos.wrapping_add(PPU::TILE_SIZE as u8))))
-                    || (self.lcdc.obj_size && ((self.ly + 16) < (y_pos.wrapping_add(PPU::TILE_SIZE as u8 * 2))))
-                {
-                    self.fetcher
-                        .add_visible_object(addr as u16 + memory::ppu::OAM.begin, x_pos, y_pos);
-                }
+            if self.is_object_on_line(x_pos, y_pos, self.ly) {
+                self.fetcher
+                    .add_visible_object(addr as u16 + memory::ppu::OAM.begin, x_pos, y_pos);
             }
         }
     }
 
+    fn object_height(&self) -> usize {
+        if self.lcdc.obj_size {
+            PPU::TILE_SIZE * 2
+        } else {
+            PPU::TILE_SIZE
+        }
+    }
+
+    fn is_object_on_line(&self, x_pos: u8, y_pos: u8, ly: u8) -> bool {
+        (x_pos != 0) && ((ly + 16) >= y_pos) && ((ly + 16) < (y_pos.wrapping_add(self.object_height() as u8)))
+    }
+
     fn handle_pixel_transfer(&mut self, gb: &mut Gameboy, popped: u32) {
         self.dots = self.dots.wrapping_add(1);
         if self.dots > 4000 {
@@ -347,7 +426,7 @@ impl PPU {
             self.fetcher.reset();
             self.fifo.reset();
             // self.fifo.reset(); //doesnt work
-            self.stat.mode_flag = LCDModeFlag::HBlank;
+            self.set_mode(gb, LCDModeFlag::HBlank);
             if gb.cpu.interrupt_master_enable {
                 if self.stat.mode0_hblank_interrupt_enable {
                     gb.cpu.if_register.lcd_stat = true;
@@ -470,7 +549,97 @@ impl PPU {
         &self,
     ) -> [u32; PPU::TILE_MAP_SIZE * PPU::TILE_MAP_SIZE * (PPU::TILE_SIZE * PPU::TILE_SIZE)] {
         self.ppu_debug
-            .get_objects_frame_buffer(&self.oam, &self.obp0, &self.obp1)
+            .get_objects_frame_buffer(&self.vram, &self.oam, &self.lcdc, &self.obp0, &self.obp1)
+    }
+
+    /// map at $9800 or $9C00 decoded with either tile data addressing mode, call `process_tile_data` first
+    pub fn get_map_frame_buffer(
+        &self,
+        map_area_9c00: bool,
+        tile_data_area_8000: bool,
+    ) -> [u32; PPU::TILE_MAP_SIZE * PPU::TILE_MAP_SIZE * (PPU::TILE_SIZE * PPU::TILE_SIZE)] {
+        let tile_map_start = if map_area_9c00 {
+            memory::ppu::TILE_MAP_AREA_9C00.begin
+        } else {
+            memory::ppu::TILE_MAP_AREA_9800.begin
+        };
+        self.ppu_debug
+            .get_map_frame_buffer(tile_map_start, tile_data_area_8000, &self.vram, &self.bgp)
+    }
+
+    pub fn get_lcdc(&self) -> &LCDControl {
+        &self.lcdc
+    }
+
+    pub fn get_stat(&self) -> &LCDStatus {
+        &self.stat
+    }
+
+    /// (BGP, OBP0, OBP1)
+    pub fn get_palettes(&self) -> (&palette::PaletteData, &palette::PaletteData, &palette::PaletteData) {
+        (&self.bgp, &self.obp0, &self.obp1)
+    }
+
+    /// (SCX, SCY)
+    pub fn get_scroll(&self) -> (u8, u8) {
+        (self.scx, self.scy)
+    }
+
+    /// (WX, WY)
+    pub fn get_window_position(&self) -> (u8, u8) {
+        (self.wx, self.wy)
+    }
+
+    pub fn get_ly(&self) -> u8 {
+        self.ly
+    }
+
+    pub fn get_lyc(&self) -> u8 {
+        self.lyc
+    }
+
+    /// dot within the current line from 0 to 455, derived from the per mode dot counter
+    pub fn get_line_dot(&self) -> u16 {
+        if !self.lcdc.lcd_ppu_enable {
+            return 0;
+        }
+        let dot = match self.stat.mode_flag {
+            // counts down from 80
+            LCDModeFlag::SearchingOAM => 80_u16.saturating_sub(self.dots),
+            // counts up from 0
+            LCDModeFlag::TransferringDataToLCD => 80 + self.dots,
+            // counts down to the end of the line
+            LCDModeFlag::HBlank => PPU::DOTS_PER_LINE.saturating_sub(self.dots),
+            // counts down over all vblank lines
+            LCDModeFlag::VBlank => {
+                ((PPU::LINES_PER_FRAME - PPU::ROWS as u16) * PPU::DOTS_PER_LINE).saturating_sub(self.dots)
+                    % PPU::DOTS_PER_LINE
+            }
+        };
+        std::cmp::min(dot, PPU::DOTS_PER_LINE - 1)
+    }
+
+    /// 8 or 16 depending on LCDC bit 2
+    pub fn get_object_height(&self) -> usize {
+        self.object_height()
+    }
+
+    pub fn get_oam_entry(&self, index: usize) -> sprite::OAMTableEntry {
+        sprite::OAMTableEntry::new(&self.oam, (index * 4) as u16)
+    }
+
+    /// oam indices the oam search selects for the given line
+    pub fn get_objects_on_line(&self, ly: u8) -> Vec<usize> {
+        (0..PPU::OAM_ENTRIES)
+            .filter(|index| self.is_object_on_line(self.oam[index * 4 + 1], self.oam[index * 4], ly))
+            .take(Fetcher::MAX_SPRITES_PER_ROW)
+            .collect()
+    }
+
+    /// 8 pixels wide, `get_object_height` pixels high, None where the object is transparent
+    pub fn get_object_pixels(&self, index: usize) -> Vec<Option<u32>> {
+        self.ppu_debug
+            .get_object_pixels(&self.vram, &self.oam, index, &self.lcdc, &self.obp0, &self.obp1)
     }
 }
 
@@ -545,7 +714,7 @@ impl PPUDebug {
         &self,
         tile_map_start: u16,
         vram: &[u8; memory::ppu::VRAM.size],
-        lcdc: &LCDControl,
+        tile_data_area_8000: bool,
     ) -> [&[[u8; 8]; 8]; PPU::TILE_MAP_SIZE * PPU::TILE_MAP_SIZE] {
         let mut map_tiles: [&[[u8; 8]; 8]; PPU::TILE_MAP_SIZE * PPU::TILE_MAP_SIZE] =
             [&[[0; 8]; 8]; PPU::TILE_MAP_SIZE * PPU::TILE_MAP_SIZE];
@@ -553,7 +722,7 @@ impl PPUDebug {
         for addr in tile_map_start..tile_map_start + 0x0400 {
             let mut tile_id = vram[addr as usize - memory::ppu::VRAM.begin as usize];
             let mut offset = 0;
-            if !lcdc.bg_and_window_tile_data_area {
+            if !tile_data_area_8000 {
                 tile_id = tile_id.wrapping_sub(128);
                 offset = 128;
             }
@@ -575,7 +744,10 @@ impl PPUDebug {
             memory::ppu::TILE_MAP_AREA_9800.begin
         };
 
-        self.get_tile_map_frame_buffer(self.get_tiles_from_tile_map(tile_map_start, vram, lcdc), bgp)
+        self.get_tile_map_frame_buffer(
+            self.get_tiles_from_tile_map(tile_map_start, vram, lcdc.bg_and_window_tile_data_area),
+            bgp,
+        )
     }
 
     pub fn get_window_frame_buffer(
@@ -590,7 +762,23 @@ impl PPUDebug {
             memory::ppu::TILE_MAP_AREA_9800.begin
         };
 
-        self.get_tile_map_frame_buffer(self.get_tiles_from_tile_map(tile_map_start, vram, lcdc), bgp)
+        self.get_tile_map_frame_buffer(
+            self.get_tiles_from_tile_map(tile_map_start, vram, lcdc.bg_and_window_tile_data_area),
+            bgp,
+        )
+    }
+
+    pub fn get_map_frame_buffer(
+        &self,
+        tile_map_start: u16,
+        tile_data_area_8000: bool,
+        vram: &[u8; memory::ppu::VRAM.size],
+        bgp: &PaletteData,
+    ) -> [u32; PPU::TILE_MAP_SIZE * PPU::TILE_MAP_SIZE * (PPU::TILE_SIZE * PPU::TILE_SIZE)] {
+        self.get_tile_map_frame_buffer(
+            self.get_tiles_from_tile_map(tile_map_start, vram, tile_data_area_8000),
+            bgp,
+        )
     }
 
     fn get_tile_map_frame_buffer(
@@ -628,38 +816,25 @@ impl PPUDebug {
 
     pub fn get_objects_frame_buffer(
         &self,
+        vram: &[u8; memory::ppu::VRAM.size],
         oam: &[u8; memory::ppu::OAM.size],
+        lcdc: &LCDControl,
         obp0: &PaletteData,
         obp1: &PaletteData,
     ) -> [u32; PPU::TILE_MAP_SIZE * PPU::TILE_MAP_SIZE * (PPU::TILE_SIZE * PPU::TILE_SIZE)] {
         let mut frame_buffer: [u32; PPU::TILE_MAP_SIZE * PPU::TILE_MAP_SIZE * (PPU::TILE_SIZE * PPU::TILE_SIZE)] =
             [0; PPU::TILE_MAP_SIZE * PPU::TILE_MAP_SIZE * (PPU::TILE_SIZE * PPU::TILE_SIZE)];
-        //TODO: if LCDC bit 2: 1 -> 2 tile objects
-        for addr in (0..0x00A0).step_by(4) {
-            // print(self.vram[addr])
-            let entry = sprite::OAMTableEntry::new(oam, addr);
-            let curr_tile = self.tiles[entry.tile_index as usize];
-
-            if entry.x_pos <= 0 || entry.x_pos >= 168 || entry.y_pos <= 0 || entry.y_pos >= 160 {
-                log::trace!("sprite is offscreen");
-            } else {
-                for row in entry.y_pos as usize..entry.y_pos as usize + PPU::TILE_SIZE {
-                    for col in entry.x_pos as usize..entry.x_pos as usize + PPU::TILE_SIZE {
-                        let palette_id = curr_tile[row - entry.y_pos as usize][col - entry.x_pos as usize];
-                        let palette: &PaletteData;
-                        if entry.attributes.palette_number == 0 {
-                            palette = obp0;
-                        } else {
-                            palette = obp1;
-                        }
-                        frame_buffer[row * PPU::TILE_MAP_SIZE * PPU::TILE_SIZE + col] =
-                            match palette.color_map[palette_id as usize] {
-                                0 => MonochromeColor::White as u32,
-                                1 => MonochromeColor::LightGray as u32,
-                                2 => MonochromeColor::DarkGray as u32,
-                                3 => MonochromeColor::Black as u32,
-                                _ => MonochromeColor::Off as u32,
-                            };
+        let width = PPU::TILE_MAP_SIZE * PPU::TILE_SIZE;
+        // lower oam indices are drawn last so they end up on top
+        for index in (0..PPU::OAM_ENTRIES).rev() {
+            let entry = sprite::OAMTableEntry::new(oam, (index * 4) as u16);
+            let pixels = self.get_object_pixels(vram, oam, index, lcdc, obp0, obp1);
+            for (i, pixel) in pixels.iter().enumerate() {
+                let col = entry.x_pos + (i % PPU::TILE_SIZE) as i16;
+                let row = entry.y_pos + (i / PPU::TILE_SIZE) as i16;
+                if let Some(color) = pixel {
+                    if col >= 0 && (col as usize) < width && row >= 0 && (row as usize) < width {
+                        frame_buffer[row as usize * width + col as usize] = *color;
                     }
                 }
             }
@@ -667,6 +842,51 @@ impl PPUDebug {
         frame_buffer
     }
 
+    pub fn get_object_pixels(
+        &self,
+        vram: &[u8; memory::ppu::VRAM.size],
+        oam: &[u8; memory::ppu::OAM.size],
+        index: usize,
+        lcdc: &LCDControl,
+        obp0: &PaletteData,
+        obp1: &PaletteData,
+    ) -> Vec<Option<u32>> {
+        let entry = sprite::OAMTableEntry::new(oam, (index * 4) as u16);
+        let (height, tile_index) = if lcdc.obj_size {
+            (PPU::TILE_SIZE * 2, entry.tile_index & 0xFE)
+        } else {
+            (PPU::TILE_SIZE, entry.tile_index)
+        };
+        let palette = if entry.attributes.palette_number == 0 {
+            obp0
+        } else {
+            obp1
+        };
+
+        let mut pixels = vec![None; PPU::TILE_SIZE * height];
+        for row in 0..height {
+            let tile_row = if entry.attributes.y_flip { height - 1 - row } else { row };
+            // the second tile of 8x16 objects directly follows the first one
+            let addr = tile_index as usize * PPU::BYTES_PER_TILE + tile_row * 2;
+            let low = vram[addr];
+            let high = vram[addr + 1];
+            for col in 0..PPU::TILE_SIZE {
+                let bit = if entry.attributes.x_flip { col } else { 7 - col };
+                let color_id = bit!(high, bit) << 1 | bit!(low, bit);
+                if color_id != 0 {
+                    pixels[row * PPU::TILE_SIZE + col] = Some(match palette.color_map[color_id as usize] {
+                        0 => MonochromeColor::White as u32,
+                        1 => MonochromeColor::LightGray as u32,
+                        2 => MonochromeColor::DarkGray as u32,
+                        3 => MonochromeColor::Black as u32,
+                        _ => MonochromeColor::Off as u32,
+                    });
+                }
+            }
+        }
+        pixels
+    }
+
     pub fn _print_vram(&self, vram: &[u8; memory::ppu::VRAM.size]) {
         utils::print_memory_bytes(vram, "vram", 0x100);
     }
@@ -698,3 +918,33 @@ impl PPUDebug {
         }
     }
 }
+
+#[test]
+fn object_debug_test() {
+    let mut ppu = PPU::new();
+    // object 0 at the top left corner, object 1 hidden, object 2 one line further down
+    ppu.oam[0..4].copy_from_slice(&[16, 8, 0x03, 0x20]);
+    ppu.oam[8..12].copy_from_slice(&[17, 8, 0x02, 0x00]);
+    // tile 2 has its first line set to color 1 in the leftmost pixel, tile 3 color 3 in its last line
+    ppu.vram[2 * PPU::BYTES_PER_TILE] = 0x80;
+    ppu.vram[3 * PPU::BYTES_PER_TILE + 14] = 0x01;
+    ppu.vram[3 * PPU::BYTES_PER_TILE + 15] = 0x01;
+    ppu.obp0 = PaletteData::from(0xE4);
+
+    assert_eq!(ppu.get_objects_on_line(0), vec![0]);
+    assert_eq!(ppu.get_objects_on_line(8), vec![2]);
+
+    // x flipped 8x8 object shows the last pixel of tile 3 on the left
+    let pixels = ppu.get_object_pixels(0);
+    assert_eq!(pixels.len(), 64);
+    assert_eq!(pixels[7 * 8], Some(MonochromeColor::Black as u32));
+    assert_eq!(pixels[7 * 8 + 7], None);
+
+    // in 8x16 mode the tile index ignores bit 0 and tile 3 becomes the lower half
+    ppu.lcdc.obj_size = true;
+    assert_eq!(ppu.get_objects_on_line(15), vec![0, 2]);
+    let pixels = ppu.get_object_pixels(2);
+    assert_eq!(pixels.len(), 128);
+    assert_eq!(pixels[0], Some(MonochromeColor::LightGray as u32));
+    assert_eq!(pixels[15 * 8 + 7], Some(MonochromeColor::Black as u32));
+}
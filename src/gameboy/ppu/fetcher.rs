@@ -2,7 +2,11 @@ use core::panic;
 
 use crate::{
     bit,
-    gameboy::{memory, GameboyModule},
+    gameboy::{
+        memory,
+        savestate::{SaveState, StateReader, StateWriter},
+        GameboyModule,
+    },
 };
 
 use super::{fifo::FifoElement, sprite::SpriteAttributes, PPU};
@@ -31,18 +35,80 @@ pub struct Fetcher {
     fetching_object: bool,
     curr_object_index: usize,
     drawing_window: bool,
+    // tile data address read in the last step, consumed by the code data logger
+    tile_data_fetch: Option<u16>,
 }
 
 impl GameboyModule for Fetcher {
     unsafe fn tick(&mut self, gb_ptr: *mut crate::gameboy::Gameboy) -> Result<u32, std::fmt::Error> {
         let gb = &mut *gb_ptr;
         self.step(&mut gb.ppu);
+        if let Some(addr) = self.tile_data_fetch.take() {
+            gb.cdl_log_tile_fetch(addr);
+        }
         Ok(0)
     }
 }
 
+impl std::convert::From<u8> for FetcherState {
+    fn from(byte: u8) -> Self {
+        match byte {
+            0 => FetcherState::GetTile,
+            1 => FetcherState::GetDataLow,
+            2 => FetcherState::GetDataHigh,
+            3 => FetcherState::Sleep,
+            4 => FetcherState::Push,
+            _ => FetcherState::Wait,
+        }
+    }
+}
+
+impl SaveState for Fetcher {
+    fn save_state(&self, writer: &mut StateWriter) {
+        writer.write_u8(self.state as u8);
+        writer.write_u8(self.prev_state as u8);
+        writer.write_u8(self.x);
+        writer.write_u8(self.y);
+        writer.write_u16(self.tile_map_start);
+        writer.write_u16(self.tile_data_start);
+        writer.write_u8(self.next_tile_id);
+        writer.write_u8(self.low);
+        writer.write_u8(self.high);
+        writer.write_u8(self.visible_objects.len() as u8);
+        for (addr, x, y) in self.visible_objects.iter() {
+            writer.write_u16(*addr);
+            writer.write_u8(*x);
+            writer.write_u8(*y);
+        }
+        writer.write_bool(self.fetching_object);
+        writer.write_u8(self.curr_object_index as u8);
+        writer.write_bool(self.drawing_window);
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), String> {
+        self.state = reader.read_u8()?.into();
+        self.prev_state = reader.read_u8()?.into();
+        self.x = reader.read_u8()?;
+        self.y = reader.read_u8()?;
+        self.tile_map_start = reader.read_u16()?;
+        self.tile_data_start = reader.read_u16()?;
+        self.next_tile_id = reader.read_u8()?;
+        self.low = reader.read_u8()?;
+        self.high = reader.read_u8()?;
+        self.visible_objects.clear();
+        for _i in 0..reader.read_u8()? {
+            self.visible_objects
+                .push((reader.read_u16()?, reader.read_u8()?, reader.read_u8()?));
+        }
+        self.fetching_object = reader.read_bool()?;
+        self.curr_object_index = reader.read_u8()? as usize;
+        self.drawing_window = reader.read_bool()?;
+        Ok(())
+    }
+}
+
 impl Fetcher {
-    const MAX_SPRITES_PER_ROW: usize = 10;
+    pub const MAX_SPRITES_PER_ROW: usize = 10;
     pub fn new() -> Self {
         Self {
             state: FetcherState::GetTile,
@@ -58,6 +124,7 @@ impl Fetcher {
             fetching_object: false,
             curr_object_index: 0,
             drawing_window: false,
+            tile_data_fetch: None,
         }
     }
 
@@ -159,6 +226,7 @@ impl Fetcher {
                     * 2);
         }
 
+        self.tile_data_fetch = Some(addr);
         self.low = match ppu.read8_unlocked(addr) {
             Some(val) => val,
             None => panic!(
@@ -186,6 +254,7 @@ impl Fetcher {
                     * 2)
                 + 1;
         }
+        self.tile_data_fetch = Some(addr);
         self.high = match ppu.read8_unlocked(addr) {
             Some(val) => val,
             None => panic!(
@@ -1,6 +1,9 @@
 use std::collections::VecDeque;
 
-use crate::gameboy::GameboyModule;
+use crate::gameboy::{
+    savestate::{SaveState, StateReader, StateWriter},
+    GameboyModule,
+};
 
 use super::{palette::MonochromeColor, PPU};
 
@@ -39,6 +42,53 @@ impl GameboyModule for Fifo {
     }
 }
 
+impl FifoElement {
+    fn save_state(&self, writer: &mut StateWriter) {
+        writer.write_u8(self.color_id);
+        writer.write_u8(self.palette_nummber);
+        writer.write_bool(self.bg_priority);
+        writer.write_bool(self.is_object);
+    }
+
+    fn load_state(reader: &mut StateReader) -> Result<Self, String> {
+        Ok(Self {
+            color_id: reader.read_u8()?,
+            palette_nummber: reader.read_u8()?,
+            bg_priority: reader.read_bool()?,
+            is_object: reader.read_bool()?,
+        })
+    }
+}
+
+impl SaveState for Fifo {
+    fn save_state(&self, writer: &mut StateWriter) {
+        for fifo in [&self.bg_fifo, &self.object_fifo] {
+            writer.write_u8(fifo.len() as u8);
+            for elem in fifo.iter() {
+                elem.save_state(writer);
+            }
+        }
+        writer.write_u8(self.x);
+        writer.write_bool(self.is_suspended);
+        writer.write_bool(self.flush);
+        writer.write_bool(self.popped);
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), String> {
+        for fifo in [&mut self.bg_fifo, &mut self.object_fifo] {
+            fifo.clear();
+            for _i in 0..reader.read_u8()? {
+                fifo.push_back(FifoElement::load_state(reader)?);
+            }
+        }
+        self.x = reader.read_u8()?;
+        self.is_suspended = reader.read_bool()?;
+        self.flush = reader.read_bool()?;
+        self.popped = reader.read_bool()?;
+        Ok(())
+    }
+}
+
 impl Fifo {
     pub fn new() -> Self {
         Self {
@@ -0,0 +1,192 @@
+use super::Gameboy;
+
+/// Serializes the complete emulator state into a flat byte buffer.
+/// Every module writes its fields in a fixed order and reads them back in the same order.
+pub trait SaveState {
+    fn save_state(&self, writer: &mut StateWriter);
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), String>;
+}
+
+pub struct StateWriter {
+    buffer: Vec<u8>,
+}
+
+impl StateWriter {
+    pub fn new() -> Self {
+        Self { buffer: Vec::new() }
+    }
+
+    pub fn write_u8(&mut self, value: u8) {
+        self.buffer.push(value);
+    }
+
+    pub fn write_bool(&mut self, value: bool) {
+        self.buffer.push(value as u8);
+    }
+
+    pub fn write_u16(&mut self, value: u16) {
+        self.buffer.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn write_u32(&mut self, value: u32) {
+        self.buffer.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn write_u64(&mut self, value: u64) {
+        self.buffer.extend_from_slice(&value.to_le_bytes());
+    }
+
+    /// writes a slice of known size, the reader has to know the length
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        self.buffer.extend_from_slice(bytes);
+    }
+
+    /// writes a length prefixed byte vector
+    pub fn write_vec(&mut self, bytes: &[u8]) {
+        self.write_u32(bytes.len() as u32);
+        self.write_bytes(bytes);
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.buffer
+    }
+}
+
+impl Default for StateWriter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub struct StateReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> StateReader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    fn take(&mut self, count: usize) -> Result<&'a [u8], String> {
+        if self.pos + count > self.data.len() {
+            return Err(format!(
+                "save state truncated, {} bytes requested at offset {} but only {} left",
+                count,
+                self.pos,
+                self.data.len() - self.pos
+            ));
+        }
+        let bytes = &self.data[self.pos..self.pos + count];
+        self.pos += count;
+        Ok(bytes)
+    }
+
+    pub fn read_u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub fn read_bool(&mut self) -> Result<bool, String> {
+        Ok(self.read_u8()? != 0)
+    }
+
+    pub fn read_u16(&mut self) -> Result<u16, String> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    pub fn read_u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    pub fn read_u64(&mut self) -> Result<u64, String> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    /// fills the whole destination slice
+    pub fn read_bytes(&mut self, dst: &mut [u8]) -> Result<(), String> {
+        dst.copy_from_slice(self.take(dst.len())?);
+        Ok(())
+    }
+
+    /// reads a length prefixed byte vector
+    pub fn read_vec(&mut self) -> Result<Vec<u8>, String> {
+        let len = self.read_u32()? as usize;
+        Ok(self.take(len)?.to_vec())
+    }
+}
+
+impl Gameboy {
+    const SAVE_STATE_MAGIC: &'static [u8; 4] = b"GBSS";
+    const SAVE_STATE_VERSION: u8 = 2;
+
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut writer = StateWriter::new();
+        writer.write_bytes(Self::SAVE_STATE_MAGIC);
+        writer.write_u8(Self::SAVE_STATE_VERSION);
+        writer.write_vec(self.cartridge.title().as_bytes());
+
+        self.cpu.save_state(&mut writer);
+        self.ppu.save_state(&mut writer);
+        self.apu.save_state(&mut writer);
+        self.timer.save_state(&mut writer);
+        self.joypad.save_state(&mut writer);
+        self.memory.save_state(&mut writer);
+        self.cartridge.save_state(&mut writer);
+        writer.write_bool(self.dma_active);
+
+        writer.into_bytes()
+    }
+
+    pub fn load_state(&mut self, data: &[u8]) -> Result<(), String> {
+        let mut reader = StateReader::new(data);
+        let mut magic = [0; 4];
+        reader.read_bytes(&mut magic)?;
+        if &magic != Self::SAVE_STATE_MAGIC {
+            return Err("not a save state".to_string());
+        }
+        let version = reader.read_u8()?;
+        if version != Self::SAVE_STATE_VERSION {
+            return Err(format!("unsupported save state version {}", version));
+        }
+        let title = reader.read_vec()?;
+        if title != self.cartridge.title().as_bytes() {
+            return Err(format!(
+                "save state belongs to '{}' but '{}' is loaded",
+                String::from_utf8_lossy(&title),
+                self.cartridge.title()
+            ));
+        }
+
+        self.cpu.load_state(&mut reader)?;
+        self.ppu.load_state(&mut reader)?;
+        self.apu.load_state(&mut reader)?;
+        self.timer.load_state(&mut reader)?;
+        self.joypad.load_state(&mut reader)?;
+        self.memory.load_state(&mut reader)?;
+        self.cartridge.load_state(&mut reader)?;
+        self.dma_active = reader.read_bool()?;
+
+        Ok(())
+    }
+}
+
+#[test]
+fn state_reader_writer_test() {
+    let mut writer = StateWriter::new();
+    writer.write_u8(0xAB);
+    writer.write_bool(true);
+    writer.write_u16(0x1234);
+    writer.write_u32(0xDEADBEEF);
+    writer.write_u64(0x0123456789ABCDEF);
+    writer.write_vec(&[1, 2, 3]);
+    let data = writer.into_bytes();
+
+    let mut reader = StateReader::new(&data);
+    assert_eq!(reader.read_u8().unwrap(), 0xAB);
+    assert!(reader.read_bool().unwrap());
+    assert_eq!(reader.read_u16().unwrap(), 0x1234);
+    assert_eq!(reader.read_u32().unwrap(), 0xDEADBEEF);
+    assert_eq!(reader.read_u64().unwrap(), 0x0123456789ABCDEF);
+    assert_eq!(reader.read_vec().unwrap(), vec![1, 2, 3]);
+    assert!(reader.read_u8().is_err());
+}
@@ -0,0 +1,370 @@
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    path::{Path, PathBuf},
+    rc::Rc,
+    sync::mpsc::{self, Receiver, Sender},
+    thread,
+};
+
+use rhai::{Dynamic, Engine, EvalAltResult, FnPtr, Map, NativeCallContext};
+
+use super::{hooks::HookId, joypad::Button, Gameboy};
+
+type ScriptResult<T> = Result<T, Box<EvalAltResult>>;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ScriptHook {
+    Instruction(u16),
+    Read(u16, u16),
+    Write(u16, u16),
+    Interrupt,
+    Frame,
+}
+
+/// sent by the script thread, the emulator answers them at the end of a frame
+#[derive(Debug, PartialEq)]
+enum Request {
+    Read(u16),
+    Write(u16, u8),
+    Registers,
+    Button(Button, bool),
+    Screenshot(PathBuf),
+    AddHook(usize, ScriptHook),
+    RemoveHook(usize),
+    Advance(u32),
+    Quit,
+}
+
+#[derive(Debug, PartialEq)]
+struct HookEvent {
+    id: usize,
+    args: Vec<i64>,
+}
+
+#[derive(Debug, PartialEq)]
+enum Response {
+    Done,
+    Value(i64),
+    Registers(Vec<(&'static str, i64)>),
+    Error(String),
+    /// the requested frames ran, with the hooks that fired in the meantime
+    Advanced(Vec<HookEvent>),
+}
+
+/// script side of the channels to the emulator
+struct ScriptLink {
+    requests: Sender<Request>,
+    responses: Receiver<Response>,
+    callbacks: RefCell<HashMap<usize, FnPtr>>,
+    next_hook: Cell<usize>,
+}
+
+impl ScriptLink {
+    fn call(&self, request: Request) -> ScriptResult<Response> {
+        self.requests
+            .send(request)
+            .map_err(|_| "emulator stopped".to_string())?;
+        match self.responses.recv() {
+            Ok(Response::Error(err)) => Err(err.into()),
+            Ok(response) => Ok(response),
+            Err(_) => Err("emulator stopped".into()),
+        }
+    }
+
+    fn value(&self, request: Request) -> ScriptResult<i64> {
+        match self.call(request)? {
+            Response::Value(value) => Ok(value),
+            response => Err(format!("unexpected response {:?}", response).into()),
+        }
+    }
+
+    fn done(&self, request: Request) -> ScriptResult<()> {
+        self.call(request).map(|_| ())
+    }
+
+    fn add_hook(&self, hook: ScriptHook, callback: FnPtr) -> ScriptResult<i64> {
+        let id = self.next_hook.get() + 1;
+        self.next_hook.set(id);
+        self.done(Request::AddHook(id, hook))?;
+        self.callbacks.borrow_mut().insert(id, callback);
+        Ok(id as i64)
+    }
+
+    /// runs the callbacks of the hooks that fired while the frames advanced
+    fn advance(&self, context: &NativeCallContext, frames: i64) -> ScriptResult<()> {
+        let events = match self.call(Request::Advance(std::cmp::max(frames, 1) as u32))? {
+            Response::Advanced(events) => events,
+            response => return Err(format!("unexpected response {:?}", response).into()),
+        };
+        for event in events {
+            let callback = self.callbacks.borrow().get(&event.id).cloned();
+            if let Some(callback) = callback {
+                let _: Dynamic = callback.call_within_context(context, event.args)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn address(value: i64) -> ScriptResult<u16> {
+    u16::try_from(value).map_err(|_| format!("invalid address {:#X}", value).into())
+}
+
+fn script_engine(link: Rc<ScriptLink>) -> Engine {
+    let mut engine = Engine::new();
+
+    let l = link.clone();
+    engine.register_fn("read", move |addr: i64| l.value(Request::Read(address(addr)?)));
+    let l = link.clone();
+    engine.register_fn("read16", move |addr: i64| -> ScriptResult<i64> {
+        let low = l.value(Request::Read(address(addr)?))?;
+        let high = l.value(Request::Read(address(addr + 1)?))?;
+        Ok(high << 8 | low)
+    });
+    let l = link.clone();
+    engine.register_fn("write", move |addr: i64, value: i64| {
+        l.done(Request::Write(address(addr)?, value as u8))
+    });
+    let l = link.clone();
+    engine.register_fn("registers", move || -> ScriptResult<Map> {
+        match l.call(Request::Registers)? {
+            Response::Registers(registers) => Ok(registers
+                .into_iter()
+                .map(|(name, value)| (name.into(), Dynamic::from(value)))
+                .collect()),
+            response => Err(format!("unexpected response {:?}", response).into()),
+        }
+    });
+    let l = link.clone();
+    engine.register_fn("press", move |name: &str| {
+        l.done(Request::Button(name.parse::<Button>()?, true))
+    });
+    let l = link.clone();
+    engine.register_fn("release", move |name: &str| {
+        l.done(Request::Button(name.parse::<Button>()?, false))
+    });
+    let l = link.clone();
+    engine.register_fn("frame_advance", move |context: NativeCallContext| {
+        l.advance(&context, 1)
+    });
+    let l = link.clone();
+    engine.register_fn("frame_advance", move |context: NativeCallContext, frames: i64| {
+        l.advance(&context, frames)
+    });
+    let l = link.clone();
+    engine.register_fn("screenshot", move |path: &str| {
+        l.done(Request::Screenshot(PathBuf::from(path)))
+    });
+    let l = link.clone();
+    engine.register_fn("on_exec", move |addr: i64, callback: FnPtr| {
+        l.add_hook(ScriptHook::Instruction(address(addr)?), callback)
+    });
+    let l = link.clone();
+    engine.register_fn("on_read", move |from: i64, to: i64, callback: FnPtr| {
+        l.add_hook(ScriptHook::Read(address(from)?, address(to)?), callback)
+    });
+    let l = link.clone();
+    engine.register_fn("on_write", move |from: i64, to: i64, callback: FnPtr| {
+        l.add_hook(ScriptHook::Write(address(from)?, address(to)?), callback)
+    });
+    let l = link.clone();
+    engine.register_fn("on_interrupt", move |callback: FnPtr| {
+        l.add_hook(ScriptHook::Interrupt, callback)
+    });
+    let l = link.clone();
+    engine.register_fn("on_frame", move |callback: FnPtr| {
+        l.add_hook(ScriptHook::Frame, callback)
+    });
+    let l = link.clone();
+    engine.register_fn("remove_hook", move |id: i64| -> ScriptResult<bool> {
+        l.callbacks.borrow_mut().remove(&(id as usize));
+        Ok(l.value(Request::RemoveHook(id as usize))? != 0)
+    });
+    let l = link;
+    engine.register_fn("quit", move || l.done(Request::Quit));
+
+    engine
+}
+
+/// emulator side of a running script
+pub(super) struct ScriptHost {
+    requests: Receiver<Request>,
+    responses: Sender<Response>,
+    events: Sender<HookEvent>,
+    fired: Receiver<HookEvent>,
+    // script hook ids to the registered gameboy hooks
+    hooks: HashMap<usize, HookId>,
+    frames_left: u32,
+}
+
+impl Gameboy {
+    /// Runs a rhai script on its own thread. Every call of the script waits for the end of the current frame,
+    /// `frame_advance` lets the emulator run. Hook callbacks are called once the frame they fired in has finished.
+    pub fn start_script(&mut self, path: &Path) {
+        let (request_sender, requests) = mpsc::channel();
+        let (responses, response_receiver) = mpsc::channel();
+        let (events, fired) = mpsc::channel();
+        let path = path.to_path_buf();
+        thread::spawn(move || {
+            let link = Rc::new(ScriptLink {
+                requests: request_sender,
+                responses: response_receiver,
+                callbacks: RefCell::new(HashMap::new()),
+                next_hook: Cell::new(0),
+            });
+            match script_engine(link).run_file(path.clone()) {
+                Ok(()) => log::info!("script {} finished", path.display()),
+                Err(err) => log::error!("script {} failed: {}", path.display(), err),
+            }
+        });
+        self.script = Some(ScriptHost {
+            requests,
+            responses,
+            events,
+            fired,
+            hooks: HashMap::new(),
+            frames_left: 0,
+        });
+    }
+
+    /// called at the end of every frame, blocks until the script advances or finishes
+    pub(super) fn service_script(&mut self) {
+        let mut host = match self.script.take() {
+            Some(host) => host,
+            None => return,
+        };
+        if host.frames_left > 0 {
+            host.frames_left -= 1;
+            if host.frames_left > 0 {
+                self.script = Some(host);
+                return;
+            }
+            let events = host.fired.try_iter().collect();
+            // a failed send means the script is gone, which the next recv notices
+            let _ = host.responses.send(Response::Advanced(events));
+        }
+        loop {
+            let request = match host.requests.recv() {
+                Ok(request) => request,
+                Err(_) => {
+                    for id in host.hooks.values() {
+                        self.remove_hook(*id);
+                    }
+                    return;
+                }
+            };
+            let response = match request {
+                Request::Advance(frames) => {
+                    host.frames_left = frames;
+                    self.script = Some(host);
+                    return;
+                }
+                Request::Quit => {
+                    self.running = false;
+                    let _ = host.responses.send(Response::Done);
+                    self.script = Some(host);
+                    return;
+                }
+                Request::Read(addr) => Response::Value(self.read8_unlocked(addr) as i64),
+                Request::Write(addr, value) => {
+                    self.write8(addr, value);
+                    Response::Done
+                }
+                Request::Registers => Response::Registers(vec![
+                    ("a", self.cpu.a as i64),
+                    ("f", self.cpu.f as i64),
+                    ("b", self.cpu.b as i64),
+                    ("c", self.cpu.c as i64),
+                    ("d", self.cpu.d as i64),
+                    ("e", self.cpu.e as i64),
+                    ("h", self.cpu.h as i64),
+                    ("l", self.cpu.l as i64),
+                    ("sp", self.cpu.sp as i64),
+                    ("pc", self.cpu.pc as i64),
+                    ("ime", self.cpu.interrupt_master_enable as i64),
+                    ("bank", self.cartridge.rom_bank(self.cpu.pc) as i64),
+                ]),
+                Request::Button(button, pressed) => {
+                    self.joypad.set_button(button, pressed);
+                    Response::Done
+                }
+                Request::Screenshot(path) => match self.screen.save_png(&path) {
+                    Ok(()) => Response::Done,
+                    Err(err) => Response::Error(format!("failed to write {}: {}", path.display(), err)),
+                },
+                Request::AddHook(id, hook) => {
+                    let hook_id = self.add_script_hook(id, hook, host.events.clone());
+                    host.hooks.insert(id, hook_id);
+                    Response::Done
+                }
+                Request::RemoveHook(id) => match host.hooks.remove(&id) {
+                    Some(hook_id) => Response::Value(self.remove_hook(hook_id) as i64),
+                    None => Response::Value(0),
+                },
+            };
+            let _ = host.responses.send(response);
+        }
+    }
+
+    fn add_script_hook(&mut self, id: usize, hook: ScriptHook, events: Sender<HookEvent>) -> HookId {
+        let send = move |args: Vec<i64>| {
+            let _ = events.send(HookEvent { id, args });
+        };
+        match hook {
+            ScriptHook::Instruction(addr) => self.add_instruction_hook(addr, move |cpu| send(vec![cpu.pc as i64])),
+            ScriptHook::Read(from, to) => {
+                self.add_read_hook(from..=to, move |addr, value| send(vec![addr as i64, value as i64]))
+            }
+            ScriptHook::Write(from, to) => {
+                self.add_write_hook(from..=to, move |addr, value| send(vec![addr as i64, value as i64]))
+            }
+            ScriptHook::Interrupt => self.add_interrupt_hook(move |vector| send(vec![vector as i64])),
+            ScriptHook::Frame => self.add_frame_hook(move |frame| send(vec![frame as i64])),
+        }
+    }
+}
+
+#[test]
+fn script_test() {
+    let (request_sender, requests) = mpsc::channel();
+    let (responses, response_receiver) = mpsc::channel();
+    let script = thread::spawn(move || {
+        let link = Rc::new(ScriptLink {
+            requests: request_sender,
+            responses: response_receiver,
+            callbacks: RefCell::new(HashMap::new()),
+            next_hook: Cell::new(0),
+        });
+        script_engine(link)
+            .run(
+                "write(0xC000, read(0xC000) + 1);
+            on_write(0xC000, 0xC0FF, |addr, value| write(0xC100, value));
+            press(\"start\");
+            frame_advance(2);",
+            )
+            .map_err(|err| err.to_string())
+    });
+
+    assert_eq!(requests.recv(), Ok(Request::Read(0xC000)));
+    responses.send(Response::Value(41)).unwrap();
+    assert_eq!(requests.recv(), Ok(Request::Write(0xC000, 42)));
+    responses.send(Response::Done).unwrap();
+    assert_eq!(
+        requests.recv(),
+        Ok(Request::AddHook(1, ScriptHook::Write(0xC000, 0xC0FF)))
+    );
+    responses.send(Response::Done).unwrap();
+    assert_eq!(requests.recv(), Ok(Request::Button(Button::START, true)));
+    responses.send(Response::Done).unwrap();
+    assert_eq!(requests.recv(), Ok(Request::Advance(2)));
+    responses
+        .send(Response::Advanced(vec![HookEvent {
+            id: 1,
+            args: vec![0xC010, 7],
+        }]))
+        .unwrap();
+    assert_eq!(requests.recv(), Ok(Request::Write(0xC100, 7)));
+    responses.send(Response::Done).unwrap();
+    assert!(script.join().unwrap().is_ok());
+}
@@ -1,9 +1,13 @@
 use crate::bit;
 
-use super::{memory, Gameboy, GameboyModule};
+use super::{
+    memory,
+    savestate::{SaveState, StateReader, StateWriter},
+    Gameboy, GameboyModule,
+};
 
 #[derive(Copy, Clone, Debug)]
-enum TimerPrescaler {
+pub enum TimerPrescaler {
     Presc1024 = 1024,
     Presc16 = 16,
     Presc64 = 64,
@@ -11,10 +15,10 @@ enum TimerPrescaler {
 }
 
 #[derive(Clone, Debug)]
-struct TimerControl {
-    unused_bits: u8,
-    enable: bool,
-    prescaler: TimerPrescaler,
+pub struct TimerControl {
+    pub unused_bits: u8,
+    pub enable: bool,
+    pub prescaler: TimerPrescaler,
 }
 
 impl std::convert::From<TimerControl> for u8 {
@@ -105,6 +109,29 @@ impl super::MemoryInterface for Timer {
     }
 }
 
+impl SaveState for Timer {
+    fn save_state(&self, writer: &mut StateWriter) {
+        writer.write_u16(self.div_timer_tick);
+        writer.write_u16(self.timer_tick);
+        writer.write_u16(self.glitch_tick);
+        writer.write_u8(self.div);
+        writer.write_u8(self.tima);
+        writer.write_u8(self.tma);
+        writer.write_u8(self.tac.clone().into());
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), String> {
+        self.div_timer_tick = reader.read_u16()?;
+        self.timer_tick = reader.read_u16()?;
+        self.glitch_tick = reader.read_u16()?;
+        self.div = reader.read_u8()?;
+        self.tima = reader.read_u8()?;
+        self.tma = reader.read_u8()?;
+        self.tac = reader.read_u8()?.into();
+        Ok(())
+    }
+}
+
 impl Timer {
     const DIV_PRESCALER: u16 = 256;
 
@@ -120,6 +147,10 @@ impl Timer {
         }
     }
 
+    pub fn get_tac(&self) -> &TimerControl {
+        &self.tac
+    }
+
     fn tick_tima(&mut self) {
         self.tima = self.tima.wrapping_add(1);
         if self.tima == 0 {
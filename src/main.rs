@@ -1,9 +1,14 @@
-use std::env;
+use std::{
+    env,
+    panic::{self, AssertUnwindSafe},
+    path::PathBuf,
+};
 
 pub mod gameboy;
 pub mod screen;
 pub mod utils;
 
+use gameboy::cartridge::infrared::UdpIrTransport;
 use std::process::Command;
 
 fn main() {
@@ -19,15 +24,85 @@ fn main() {
 
     let mut gb = gameboy::Gameboy::new(args[1].clone(), args[2].clone());
 
-    unsafe {
-        if args.len() >= 4 {
-            if args[3] == "--debug" {
-                gb.run(true).unwrap();
-            } else {
-                gb.run(false).unwrap();
-            }
-        } else {
-            gb.run(false).unwrap();
+    let mut debug_windows = false;
+    let mut options = args.iter().skip(3);
+    while let Some(option) = options.next() {
+        match option.as_str() {
+            "--debug" => debug_windows = true,
+            "--debugger" => gb.enable_debugger(),
+            "--save-dir" => match options.next() {
+                Some(dir) => gb.set_save_dir(PathBuf::from(dir)),
+                None => log::warn!("--save-dir expects a directory"),
+            },
+            "--rtc-host-clock" => gb.set_rtc_host_sync(true),
+            "--ir" => match (
+                options.next().and_then(|addr| addr.parse().ok()),
+                options.next().and_then(|addr| addr.parse().ok()),
+            ) {
+                (Some(local), Some(peer)) => match UdpIrTransport::new(local, peer) {
+                    Ok(transport) => gb.set_ir_transport(Box::new(transport)),
+                    Err(err) => log::error!("failed to open infrared link on {}: {}", local, err),
+                },
+                _ => log::warn!("--ir expects a local and a peer address"),
+            },
+            "--crash-dir" => match options.next() {
+                Some(dir) => gb.set_crash_report_dir(PathBuf::from(dir)),
+                None => log::warn!("--crash-dir expects a directory"),
+            },
+            "--cdl" => match options.next() {
+                Some(path) => gb.enable_code_data_logger(PathBuf::from(path)),
+                None => log::warn!("--cdl expects a file"),
+            },
+            "--profile" => match options.next() {
+                Some(dir) => gb.enable_profiler(PathBuf::from(dir)),
+                None => log::warn!("--profile expects a directory"),
+            },
+            "--cheats" => match options.next() {
+                Some(path) => gb.load_cheats(&PathBuf::from(path)),
+                None => log::warn!("--cheats expects a file"),
+            },
+            "--watch" => match options.next() {
+                Some(path) => gb.load_watches(&PathBuf::from(path)),
+                None => log::warn!("--watch expects a file"),
+            },
+            "--watch-csv" => match options.next() {
+                Some(path) => {
+                    if let Err(err) = gb.stream_watches(&PathBuf::from(&path)) {
+                        log::error!("failed to create {}: {}", path, err);
+                    }
+                }
+                None => log::warn!("--watch-csv expects a file"),
+            },
+            "--script" => match options.next() {
+                Some(path) => gb.start_script(&PathBuf::from(path)),
+                None => log::warn!("--script expects a file"),
+            },
+            "--trace-diff" => match options.next() {
+                Some(path) => {
+                    if let Err(err) = gb.enable_trace_diff(&PathBuf::from(&path)) {
+                        log::error!("failed to open reference trace {}: {}", path, err);
+                    }
+                }
+                None => log::warn!("--trace-diff expects a file"),
+            },
+            "--history-size" => match options.next().and_then(|size| size.parse().ok()) {
+                Some(size) => gb.set_history_size(size),
+                None => log::warn!("--history-size expects a number of instructions"),
+            },
+            _ => log::warn!("unknown option {}", option),
         }
     }
+
+    let result = panic::catch_unwind(AssertUnwindSafe(|| unsafe { gb.run(debug_windows).unwrap() }));
+    if let Err(payload) = result {
+        let message = if let Some(message) = payload.downcast_ref::<&str>() {
+            message.to_string()
+        } else if let Some(message) = payload.downcast_ref::<String>() {
+            message.clone()
+        } else {
+            "unknown panic".to_string()
+        };
+        gb.report_panic(message);
+        panic::resume_unwind(payload);
+    }
 }
@@ -1,7 +1,10 @@
 extern crate minifb;
 use std::time::SystemTime;
 
-use minifb::{Key, Window, WindowOptions};
+use minifb::{Key, KeyRepeat, MouseButton, MouseMode, Window, WindowOptions};
+
+pub mod canvas;
+pub mod font;
 
 pub enum MonochromeColor {
     Off = 0x00CADC9F,
@@ -13,12 +16,16 @@ pub enum MonochromeColor {
 
 pub struct Screen {
     buffer: Vec<u32>,
-    window: Window,
+    // None for headless screens
+    window: Option<Window>,
     prev: SystemTime,
     width: usize,
     height: usize,
     title_time: SystemTime,
     key_buffer: Vec<Key>,
+    show_fps: bool,
+    mouse_down: bool,
+    mouse_clicked: bool,
 }
 
 impl Screen {
@@ -29,23 +36,48 @@ impl Screen {
             width: width,
             height: height,
             buffer: vec![0; width * height],
-            window: Window::new("Test - ESC to exit", width, height, window_options).unwrap_or_else(|e| {
-                panic!("{}", e);
-            }),
+            window: Some(
+                Window::new("Test - ESC to exit", width, height, window_options).unwrap_or_else(|e| {
+                    panic!("{}", e);
+                }),
+            ),
             prev: SystemTime::now(),
             title_time: SystemTime::now(),
             key_buffer: Vec::new(),
+            show_fps: true,
+            mouse_down: false,
+            mouse_clicked: false,
         };
         ppu
     }
 
+    /// keeps the frame buffer without opening a window, for tests
+    pub fn headless(height: usize, width: usize) -> Self {
+        Self {
+            width,
+            height,
+            buffer: vec![0; width * height],
+            window: None,
+            prev: SystemTime::now(),
+            title_time: SystemTime::now(),
+            key_buffer: Vec::new(),
+            show_fps: false,
+            mouse_down: false,
+            mouse_clicked: false,
+        }
+    }
+
     pub fn set_frame_buffer(&mut self, frame_buffer: &[u32]) {
         self.buffer = frame_buffer.to_vec();
     }
 
     pub fn update(&mut self) -> (bool, bool, bool) {
+        let window = match self.window.as_mut() {
+            Some(window) => window,
+            None => return (true, false, false),
+        };
         // We unwrap here as we want this code to exit if it fails. Real applications may want to handle this in a different way
-        self.window
+        window
             .update_with_buffer(&self.buffer, self.width, self.height)
             .unwrap();
 
@@ -55,21 +87,24 @@ impl Screen {
         let diff_time = now
             .duration_since(self.title_time)
             .expect("elapsed clock operation failed");
-        if diff_time.as_micros() > 1e5 as u128 {
-            self.window
-                .set_title(format!("{:.2} fps", 1e6 / diff.as_micros() as f32).as_str());
+        if self.show_fps && diff_time.as_micros() > 1e5 as u128 {
+            window.set_title(format!("{:.2} fps", 1e6 / diff.as_micros() as f32).as_str());
             self.title_time = now;
         }
 
         self.prev = now;
 
         //update keys
-        self.key_buffer = self.window.get_keys();
+        self.key_buffer = window.get_keys();
+
+        let mouse_down = window.get_mouse_down(MouseButton::Left);
+        self.mouse_clicked = mouse_down && !self.mouse_down;
+        self.mouse_down = mouse_down;
 
         (
-            self.window.is_open() && !self.window.is_key_down(Key::Escape),
-            self.window.is_key_pressed(Key::Space, minifb::KeyRepeat::No),
-            self.window.is_key_pressed(Key::LeftShift, minifb::KeyRepeat::No),
+            window.is_open() && !window.is_key_down(Key::Escape),
+            window.is_key_pressed(Key::Space, minifb::KeyRepeat::No),
+            window.is_key_pressed(Key::LeftShift, minifb::KeyRepeat::No),
         )
     }
 
@@ -77,4 +112,59 @@ impl Screen {
         // self.key_buffer = self.window.get_keys();
         &self.key_buffer
     }
+
+    /// replaces the fps counter in the title bar
+    pub fn set_title(&mut self, title: &str) {
+        self.show_fps = false;
+        if let Some(window) = self.window.as_mut() {
+            window.set_title(title);
+        }
+    }
+
+    /// writes the current frame buffer as an rgb png
+    pub fn save_png(&self, path: &std::path::Path) -> std::io::Result<()> {
+        let file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        let mut encoder = png::Encoder::new(file, self.width as u32, self.height as u32);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        let data: Vec<u8> = self
+            .buffer
+            .iter()
+            .flat_map(|pixel| [(pixel >> 16) as u8, (pixel >> 8) as u8, *pixel as u8])
+            .collect();
+        encoder.write_header()?.write_image_data(&data)?;
+        Ok(())
+    }
+
+    /// mouse position in buffer coordinates, None if the mouse is outside of the window
+    pub fn get_mouse_pos(&self) -> Option<(usize, usize)> {
+        self.window
+            .as_ref()?
+            .get_mouse_pos(MouseMode::Discard)
+            .map(|(x, y)| (x as usize, y as usize))
+    }
+
+    /// position of a left click that happened since the last update
+    pub fn get_mouse_click(&self) -> Option<(usize, usize)> {
+        if self.mouse_clicked {
+            self.get_mouse_pos()
+        } else {
+            None
+        }
+    }
+
+    /// vertical scroll wheel movement since the last update, positive is up
+    pub fn get_scroll(&self) -> f32 {
+        self.window
+            .as_ref()
+            .and_then(|window| window.get_scroll_wheel())
+            .map_or(0., |(_, y)| y)
+    }
+
+    /// keys pressed since the last update including key repeats
+    pub fn get_keys_pressed(&self) -> Vec<Key> {
+        self.window
+            .as_ref()
+            .map_or_else(Vec::new, |window| window.get_keys_pressed(KeyRepeat::Yes))
+    }
 }
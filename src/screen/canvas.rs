@@ -0,0 +1,98 @@
+use super::font;
+
+/// Software frame buffer for the debug windows with simple shape and text drawing
+pub struct Canvas {
+    pub width: usize,
+    pub height: usize,
+    buffer: Vec<u32>,
+}
+
+impl Canvas {
+    pub const CHAR_WIDTH: usize = font::GLYPH_WIDTH + 1;
+    pub const CHAR_HEIGHT: usize = font::GLYPH_HEIGHT + 1;
+
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            buffer: vec![0; width * height],
+        }
+    }
+
+    pub fn buffer(&self) -> &[u32] {
+        &self.buffer
+    }
+
+    pub fn clear(&mut self, color: u32) {
+        self.buffer.fill(color);
+    }
+
+    /// pixels outside of the canvas are ignored
+    pub fn set_pixel(&mut self, x: usize, y: usize, color: u32) {
+        if x < self.width && y < self.height {
+            self.buffer[y * self.width + x] = color;
+        }
+    }
+
+    pub fn fill_rect(&mut self, x: usize, y: usize, width: usize, height: usize, color: u32) {
+        for py in y..std::cmp::min(y + height, self.height) {
+            for px in x..std::cmp::min(x + width, self.width) {
+                self.buffer[py * self.width + px] = color;
+            }
+        }
+    }
+
+    pub fn draw_rect(&mut self, x: usize, y: usize, width: usize, height: usize, color: u32) {
+        if width == 0 || height == 0 {
+            return;
+        }
+        for px in x..x + width {
+            self.set_pixel(px, y, color);
+            self.set_pixel(px, y + height - 1, color);
+        }
+        for py in y..y + height {
+            self.set_pixel(x, py, color);
+            self.set_pixel(x + width - 1, py, color);
+        }
+    }
+
+    /// copies a buffer of the given width into the canvas, every source pixel becomes a zoom x zoom block
+    pub fn blit(&mut self, x: usize, y: usize, source: &[u32], source_width: usize, zoom: usize) {
+        for (i, color) in source.iter().enumerate() {
+            let sx = x + (i % source_width) * zoom;
+            let sy = y + (i / source_width) * zoom;
+            self.fill_rect(sx, sy, zoom, zoom, *color);
+        }
+    }
+
+    /// draws a single line of text, returns the x position after the last character
+    pub fn draw_text(&mut self, x: usize, y: usize, text: &str, color: u32) -> usize {
+        let mut cx = x;
+        for c in text.chars() {
+            for (column, bits) in font::glyph(c).iter().enumerate() {
+                for row in 0..font::GLYPH_HEIGHT {
+                    if bits & (1 << row) != 0 {
+                        self.set_pixel(cx + column, y + row, color);
+                    }
+                }
+            }
+            cx += Self::CHAR_WIDTH;
+        }
+        cx
+    }
+}
+
+#[test]
+fn canvas_test() {
+    let mut canvas = Canvas::new(16, 8);
+    canvas.clear(1);
+    canvas.fill_rect(14, 6, 4, 4, 2);
+    assert_eq!(canvas.buffer()[7 * 16 + 15], 2);
+    assert_eq!(canvas.buffer()[5 * 16 + 15], 1);
+
+    canvas.clear(0);
+    let end = canvas.draw_text(0, 0, "|", 3);
+    assert_eq!(end, Canvas::CHAR_WIDTH);
+    assert!((0..7).all(|row| canvas.buffer()[row * 16 + 2] == 3));
+    assert_eq!(canvas.buffer()[7 * 16 + 2], 0);
+}